@@ -9,11 +9,14 @@
 //! '''
 
 use serde_generate::{
-    cpp, csharp, dart, golang, java, ocaml, python3, rust, swift, typescript, CodeGeneratorConfig,
-    Encoding, SourceInstaller,
+    analyzer, cpp, csharp, dart, golang, java, ocaml, python3, rust, swift, typescript,
+    CodeGeneratorConfig, Encoding, SourceInstaller,
 };
 use serde_reflection::Registry;
-use std::path::PathBuf;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
 use structopt::{clap::arg_enum, StructOpt};
 
 arg_enum! {
@@ -29,9 +32,24 @@ enum Language {
     CSharp,
     Swift,
     OCaml,
+    All,
 }
 }
 
+/// Languages generated by `--language all`, with the names of their subdirectories.
+const ALL_LANGUAGES: &[(Language, &str)] = &[
+    (Language::Python3, "python3"),
+    (Language::Cpp, "cpp"),
+    (Language::Rust, "rust"),
+    (Language::Java, "java"),
+    (Language::Go, "go"),
+    (Language::Dart, "dart"),
+    (Language::TypeScript, "typescript"),
+    (Language::CSharp, "csharp"),
+    (Language::Swift, "swift"),
+    (Language::OCaml, "ocaml"),
+];
+
 arg_enum! {
 #[derive(Debug, StructOpt, PartialEq, Eq, PartialOrd, Ord)]
 enum Runtime {
@@ -47,21 +65,44 @@ enum Runtime {
     about = "Generate code for Serde containers"
 )]
 struct Options {
-    /// Path to the YAML-encoded Serde formats.
+    /// Paths to the YAML-encoded Serde formats. Several registries are merged into one.
+    /// Containers defined in more than one file must have the same definition.
     #[structopt(parse(from_os_str))]
-    input: Option<PathBuf>,
+    input: Vec<PathBuf>,
 
-    /// Language for code generation.
+    /// Language for code generation. With `all`, every language is generated in a subdirectory
+    /// of `--target-source-dir`.
     #[structopt(long, possible_values = &Language::variants(), case_insensitive = true, default_value = "Python3")]
     language: Language,
 
     /// Directory where to write generated modules (otherwise print code on stdout).
-    #[structopt(long)]
+    #[structopt(long, alias = "install-dir")]
     target_source_dir: Option<PathBuf>,
 
+    /// File where to write generated code (otherwise print code on stdout).
+    /// Not available for languages requiring `--target-source-dir`.
+    #[structopt(long, parse(from_os_str), conflicts_with = "target-source-dir")]
+    output: Option<PathBuf>,
+
+    /// Compare the generated code with the content of the given file instead of printing it.
+    /// Exit with a non-zero status and print a unified diff if they differ (ignoring line
+    /// endings). Not available for languages requiring `--target-source-dir`.
+    #[structopt(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["target-source-dir", "output"]
+    )]
+    check: Option<PathBuf>,
+
     /// Optional runtimes to install in the `target_source_dir` (if applicable).
     /// Also triggers the generation of specialized methods for each runtime.
-    #[structopt(long, possible_values = &Runtime::variants(), case_insensitive = true)]
+    /// The option `--encoding` is an alias.
+    #[structopt(
+        long,
+        alias = "encoding",
+        possible_values = &Runtime::variants(),
+        case_insensitive = true
+    )]
     with_runtimes: Vec<Runtime>,
 
     /// Module name for the Serde formats installed in the `target_source_dir`.
@@ -101,10 +142,76 @@ where
         .with_c_style_enums(c_style_enums)
 }
 
+fn get_installer(
+    language: &Language,
+    install_dir: PathBuf,
+    serde_package_name: Option<String>,
+) -> Box<dyn SourceInstaller<Error = Box<dyn std::error::Error>>> {
+    match language {
+        Language::Python3 => Box::new(python3::Installer::new(install_dir, serde_package_name)),
+        Language::Rust => Box::new(rust::Installer::new(install_dir)),
+        Language::Cpp => Box::new(cpp::Installer::new(install_dir)),
+        Language::Java => Box::new(java::Installer::new(install_dir)),
+        Language::Go => Box::new(golang::Installer::new(install_dir, serde_package_name)),
+        Language::Dart => Box::new(dart::Installer::new(install_dir)),
+        Language::TypeScript => Box::new(typescript::Installer::new(install_dir)),
+        Language::CSharp => Box::new(csharp::Installer::new(install_dir)),
+        Language::Swift => Box::new(swift::Installer::new(install_dir)),
+        Language::OCaml => Box::new(ocaml::Installer::new(install_dir)),
+        Language::All => panic!("no single installer for all languages"),
+    }
+}
+
+/// Install the module (if any) and the requested runtimes, stopping at the first error.
+fn install(
+    installer: &dyn SourceInstaller<Error = Box<dyn std::error::Error>>,
+    language: &Language,
+    named_registry: Option<&(Registry, String)>,
+    runtimes: &std::collections::BTreeSet<Runtime>,
+    c_style_enums: bool,
+) -> Result<(), String> {
+    if let Some((registry, name)) = named_registry {
+        let config = get_codegen_config(name.clone(), runtimes, c_style_enums);
+        installer
+            .install_module(&config, registry)
+            .map_err(|error| format!("Failed to install the module for {}: {}", language, error))?;
+    }
+    for runtime in runtimes {
+        let result = match runtime {
+            Runtime::Serde => installer.install_serde_runtime(),
+            Runtime::Bincode => installer.install_bincode_runtime(),
+            Runtime::Bcs => installer.install_bcs_runtime(),
+        };
+        result.map_err(|error| {
+            format!(
+                "Failed to install the {} runtime for {}: {}",
+                runtime, language, error
+            )
+        })?;
+    }
+    Ok(())
+}
+
+fn check_file(path: &Path, content: &str) {
+    let expected = std::fs::read_to_string(path).expect("checked file must be readable");
+    let expected = expected.replace("\r\n", "\n");
+    let content = content.replace("\r\n", "\n");
+    if expected != content {
+        let diff = similar::TextDiff::from_lines(&expected, &content);
+        eprint!(
+            "{}",
+            diff.unified_diff()
+                .header(&path.to_string_lossy(), "generated")
+        );
+        eprintln!("Generated code differs from {}", path.display());
+        std::process::exit(1);
+    }
+}
+
 fn main() {
     let options = Options::from_args();
     let serde_package_name_opt = options.serde_package_name.clone();
-    let named_registry_opt = match &options.input {
+    let named_registry_opt = match options.input.first() {
         None => None,
         Some(input) => {
             let name = options.module_name.clone().unwrap_or_else(|| {
@@ -114,8 +221,16 @@ fn main() {
                     .to_string_lossy()
                     .into_owned()
             });
-            let content = std::fs::read_to_string(input).expect("input file must be readable");
-            let registry = serde_yaml::from_str::<Registry>(content.as_str()).unwrap();
+            let registries = options
+                .input
+                .iter()
+                .map(|input| {
+                    let content =
+                        std::fs::read_to_string(input).expect("input file must be readable");
+                    serde_yaml::from_str::<Registry>(content.as_str()).unwrap()
+                })
+                .collect();
+            let registry = analyzer::merge_registries(registries).unwrap();
             Some((registry, name))
         }
     };
@@ -126,8 +241,7 @@ fn main() {
             if let Some((registry, name)) = named_registry_opt {
                 let config = get_codegen_config(name, &runtimes, options.use_c_style_enums);
 
-                let stdout = std::io::stdout();
-                let mut out = stdout.lock();
+                let mut out = Vec::new();
                 match options.language {
                     Language::Python3 => python3::CodeGenerator::new(&config)
                         .with_serde_package_name(serde_package_name_opt)
@@ -160,39 +274,64 @@ fn main() {
                     Language::OCaml => ocaml::CodeGenerator::new(&config)
                         .output(&mut out, &registry)
                         .unwrap(),
+                    Language::All => {
+                        eprintln!(
+                            "Code generation in all languages requires `--target-source-dir`"
+                        );
+                        std::process::exit(1);
+                    }
+                }
+                let content = String::from_utf8(out).expect("generated code must be UTF-8");
+                match (&options.output, &options.check) {
+                    (Some(path), _) => {
+                        std::fs::write(path, content).expect("output file must be writable")
+                    }
+                    (None, Some(path)) => check_file(path, &content),
+                    (None, None) => std::io::stdout()
+                        .write_all(content.as_bytes())
+                        .expect("failed to write on stdout"),
                 }
             }
         }
 
         Some(install_dir) => {
-            let installer: Box<dyn SourceInstaller<Error = Box<dyn std::error::Error>>> =
-                match options.language {
-                    Language::Python3 => {
-                        Box::new(python3::Installer::new(install_dir, serde_package_name_opt))
+            if let Language::All = options.language {
+                // Keep going after an error so that every language gets a chance.
+                let mut errors = Vec::new();
+                for (language, subdir) in ALL_LANGUAGES {
+                    let installer = get_installer(
+                        language,
+                        install_dir.join(subdir),
+                        serde_package_name_opt.clone(),
+                    );
+                    if let Err(error) = install(
+                        installer.as_ref(),
+                        language,
+                        named_registry_opt.as_ref(),
+                        &runtimes,
+                        options.use_c_style_enums,
+                    ) {
+                        errors.push(error);
                     }
-                    Language::Rust => Box::new(rust::Installer::new(install_dir)),
-                    Language::Cpp => Box::new(cpp::Installer::new(install_dir)),
-                    Language::Java => Box::new(java::Installer::new(install_dir)),
-                    Language::Go => {
-                        Box::new(golang::Installer::new(install_dir, serde_package_name_opt))
+                }
+                if !errors.is_empty() {
+                    for error in &errors {
+                        eprintln!("{}", error);
                     }
-                    Language::Dart => Box::new(dart::Installer::new(install_dir)),
-                    Language::TypeScript => Box::new(typescript::Installer::new(install_dir)),
-                    Language::CSharp => Box::new(csharp::Installer::new(install_dir)),
-                    Language::Swift => Box::new(swift::Installer::new(install_dir)),
-                    Language::OCaml => Box::new(ocaml::Installer::new(install_dir)),
-                };
-
-            if let Some((registry, name)) = named_registry_opt {
-                let config = get_codegen_config(name, &runtimes, options.use_c_style_enums);
-                installer.install_module(&config, &registry).unwrap();
-            }
-
-            for runtime in runtimes {
-                match runtime {
-                    Runtime::Serde => installer.install_serde_runtime().unwrap(),
-                    Runtime::Bincode => installer.install_bincode_runtime().unwrap(),
-                    Runtime::Bcs => installer.install_bcs_runtime().unwrap(),
+                    std::process::exit(1);
+                }
+            } else {
+                let installer =
+                    get_installer(&options.language, install_dir, serde_package_name_opt);
+                if let Err(error) = install(
+                    installer.as_ref(),
+                    &options.language,
+                    named_registry_opt.as_ref(),
+                    &runtimes,
+                    options.use_c_style_enums,
+                ) {
+                    eprintln!("{}", error);
+                    std::process::exit(1);
                 }
             }
         }
@@ -1,6 +1,7 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use serde_reflection::Registry;
 use std::process::Command;
 use tempfile::tempdir;
 
@@ -371,3 +372,234 @@ fn test_that_installed_ocaml_code_compiles() {
         .unwrap();
     assert!(status.success());
 }
+
+#[test]
+fn test_that_generated_code_is_written_to_output_file() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let yaml_path = dir.path().join("test.yaml");
+    std::fs::write(yaml_path.clone(), serde_yaml::to_string(&registry).unwrap()).unwrap();
+    let output_path = dir.path().join("test.py");
+
+    let status = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("python3")
+        .arg("--output")
+        .arg(&output_path)
+        .arg(yaml_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let content = std::fs::read_to_string(output_path).unwrap();
+    assert!(content.contains("class SerdeData:"));
+}
+
+#[test]
+fn test_that_output_file_and_install_dir_are_exclusive() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let yaml_path = dir.path().join("test.yaml");
+    std::fs::write(yaml_path.clone(), serde_yaml::to_string(&registry).unwrap()).unwrap();
+    let output_path = dir.path().join("test.py");
+
+    let status = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("python3")
+        .arg("--output")
+        .arg(&output_path)
+        .arg("--install-dir")
+        .arg(dir.path())
+        .arg(yaml_path)
+        .status()
+        .unwrap();
+    assert!(!status.success());
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn test_that_all_languages_require_install_dir() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let yaml_path = dir.path().join("test.yaml");
+    std::fs::write(yaml_path.clone(), serde_yaml::to_string(&registry).unwrap()).unwrap();
+
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("all")
+        .arg(yaml_path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("requires `--target-source-dir`"));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn test_that_python_bincode_runtime_is_installed() {
+    let dir = tempdir().unwrap();
+
+    let status = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("python3")
+        .arg("--install-dir")
+        .arg(dir.path())
+        .arg("--encoding")
+        .arg("bincode")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let content = std::fs::read_to_string(dir.path().join("bincode/__init__.py")).unwrap();
+    assert!(content.contains("class BincodeSerializer"));
+    assert!(!dir.path().join("bcs").exists());
+}
+
+#[test]
+fn test_that_all_languages_are_installed() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let yaml_path = dir.path().join("test.yaml");
+    std::fs::write(yaml_path.clone(), serde_yaml::to_string(&registry).unwrap()).unwrap();
+    let install_dir = dir.path().join("generated");
+
+    let status = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("all")
+        .arg(yaml_path)
+        .arg("--install-dir")
+        .arg(&install_dir)
+        .arg("--with-runtimes")
+        .arg("serde")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for language in &[
+        "python3",
+        "cpp",
+        "rust",
+        "java",
+        "go",
+        "dart",
+        "typescript",
+        "csharp",
+        "swift",
+        "ocaml",
+    ] {
+        assert!(install_dir.join(language).is_dir(), "{}", language);
+    }
+    assert!(install_dir.join("rust/test/src/lib.rs").exists());
+}
+
+#[test]
+fn test_that_multiple_inputs_are_merged() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let (first, second): (Vec<_>, Vec<_>) = registry
+        .into_iter()
+        .partition(|(name, _)| name.as_str() < "P");
+    let first_path = dir.path().join("first.yaml");
+    let second_path = dir.path().join("second.yaml");
+    std::fs::write(
+        first_path.clone(),
+        serde_yaml::to_string(&first.into_iter().collect::<Registry>()).unwrap(),
+    )
+    .unwrap();
+    std::fs::write(
+        second_path.clone(),
+        serde_yaml::to_string(&second.into_iter().collect::<Registry>()).unwrap(),
+    )
+    .unwrap();
+    let output_path = dir.path().join("test.py");
+
+    let status = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("python3")
+        .arg("--output")
+        .arg(&output_path)
+        .arg(first_path)
+        .arg(second_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let content = std::fs::read_to_string(output_path).unwrap();
+    assert!(content.contains("class CStyleEnum:"));
+    assert!(content.contains("class SerdeData:"));
+}
+
+#[test]
+fn test_that_check_mode_detects_outdated_files() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let yaml_path = dir.path().join("test.yaml");
+    std::fs::write(yaml_path.clone(), serde_yaml::to_string(&registry).unwrap()).unwrap();
+    let output_path = dir.path().join("test.py");
+
+    let status = Command::new("cargo")
+        .arg("run")
+        .arg("-p")
+        .arg("serde-generate-bin")
+        .arg("--")
+        .arg("--language")
+        .arg("python3")
+        .arg("--output")
+        .arg(&output_path)
+        .arg(&yaml_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let check = || {
+        Command::new("cargo")
+            .arg("run")
+            .arg("-p")
+            .arg("serde-generate-bin")
+            .arg("--")
+            .arg("--language")
+            .arg("python3")
+            .arg("--check")
+            .arg(&output_path)
+            .arg(&yaml_path)
+            .output()
+            .unwrap()
+    };
+    assert!(check().status.success());
+
+    // Line endings are ignored.
+    let content = std::fs::read_to_string(&output_path).unwrap();
+    std::fs::write(&output_path, content.replace('\n', "\r\n")).unwrap();
+    assert!(check().status.success());
+
+    std::fs::write(&output_path, content.replace("class Struct:", "class Foo:")).unwrap();
+    let output = check();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("-class Foo:\n+class Struct:\n"));
+}
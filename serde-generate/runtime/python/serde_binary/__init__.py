@@ -9,6 +9,7 @@ Note: This internal module is currently only meant to share code between the BCS
 
 import dataclasses
 import collections
+import enum
 import io
 import typing
 from typing import get_type_hints
@@ -89,7 +90,7 @@ class BinarySerializer:
     def serialize_i64(self, value: st.uint64):
         self.output.write(int(value).to_bytes(8, "little", signed=True))
 
-    def serialize_i128(self, value: st.uint128):
+    def serialize_i128(self, value: st.int128):
         self.output.write(int(value).to_bytes(16, "little", signed=True))
 
     def serialize_f32(self, value: st.float32):
@@ -134,7 +135,10 @@ class BinarySerializer:
         elif hasattr(obj_type, "__origin__"):  # Generic type
             types = getattr(obj_type, "__args__")
 
-            if getattr(obj_type, "__origin__") == collections.abc.Sequence:  # Sequence
+            if getattr(obj_type, "__origin__") in (
+                collections.abc.Sequence,
+                list,
+            ):  # Sequence
                 assert len(types) == 1
                 item_type = types[0]
                 self.serialize_len(len(obj))
@@ -142,7 +146,11 @@ class BinarySerializer:
                     self.serialize_any(item, item_type)
 
             elif getattr(obj_type, "__origin__") == tuple:  # Tuple
-                if len(types) != 1 or types[0] != ():
+                if len(types) == 2 and types[1] == Ellipsis:  # Sequence as a tuple
+                    self.serialize_len(len(obj))
+                    for item in obj:
+                        self.serialize_any(item, types[0])
+                elif len(types) != 1 or types[0] != ():
                     for i in range(len(obj)):
                         self.serialize_any(obj[i], types[i])
 
@@ -167,6 +175,11 @@ class BinarySerializer:
             else:
                 raise st.SerializationError("Unexpected type", obj_type)
 
+        elif isinstance(obj_type, type) and issubclass(obj_type, enum.IntEnum):  # C-style enum
+            if not isinstance(obj, obj_type):
+                raise st.SerializationError("Wrong Value for the type", obj, obj_type)
+            self.serialize_variant_index(int(obj))
+
         else:
             if not dataclasses.is_dataclass(obj_type):  # Enum
                 if not hasattr(obj_type, "VARIANTS"):
@@ -335,7 +348,10 @@ class BinaryDeserializer:
 
         elif hasattr(obj_type, "__origin__"):  # Generic type
             types = getattr(obj_type, "__args__")
-            if getattr(obj_type, "__origin__") == collections.abc.Sequence:  # Sequence
+            if getattr(obj_type, "__origin__") in (
+                collections.abc.Sequence,
+                list,
+            ):  # Sequence
                 assert len(types) == 1
                 item_type = types[0]
                 length = self.deserialize_len()
@@ -350,6 +366,11 @@ class BinaryDeserializer:
                 result = []
                 if len(types) == 1 and types[0] == ():
                     return tuple()
+                if len(types) == 2 and types[1] == Ellipsis:  # Sequence as a tuple
+                    length = self.deserialize_len()
+                    for i in range(0, length):
+                        result.append(self.deserialize_any(types[0]))
+                    return tuple(result)
                 for i in range(len(types)):
                     item = self.deserialize_any(types[i])
                     result.append(item)
@@ -390,6 +411,13 @@ class BinaryDeserializer:
             else:
                 raise st.DeserializationError("Unexpected type", obj_type)
 
+        elif isinstance(obj_type, type) and issubclass(obj_type, enum.IntEnum):  # C-style enum
+            variant_index = self.deserialize_variant_index()
+            try:
+                return obj_type(variant_index)
+            except ValueError:
+                raise st.DeserializationError("Unexpected variant index", variant_index)
+
         else:
             # handle structs
             if dataclasses.is_dataclass(obj_type):
@@ -0,0 +1,306 @@
+# Copyright (c) Facebook, Inc. and its affiliates
+# SPDX-License-Identifier: MIT OR Apache-2.0
+
+"""
+Module implementing the JSON format of the Rust crate `serde_json`.
+
+Values are represented following the default conventions of Serde:
+* structs are JSON objects indexed by field names,
+* newtype structs are represented by their inner value,
+* tuples and tuple structs are JSON arrays,
+* enums are externally tagged (e.g. `"Unit"`, `{"NewType": 1}`, `{"Struct": {"x": 1}}`),
+* map keys must be strings or integers.
+
+Note: Generated classes do not distinguish empty struct variants (e.g. `A {}`) from unit variants
+(e.g. `A`). The former are not supported.
+"""
+
+import dataclasses
+import collections
+import enum
+import json
+import keyword
+import typing
+from typing import get_type_hints
+
+import serde_types as st
+
+
+_INTEGER_TYPES = {
+    st.uint8,
+    st.uint16,
+    st.uint32,
+    st.uint64,
+    st.int8,
+    st.int16,
+    st.int32,
+    st.int64,
+}
+
+_FLOAT_TYPES = {st.float32, st.float64}
+
+
+def _is_newtype(obj_type) -> bool:
+    # Generated classes for newtype and tuple structs (or variants) are marked explicitly.
+    return getattr(obj_type, "NEWTYPE", False)
+
+
+def _variant_name(obj_type, variant_type) -> str:
+    # Variant classes are named `<Enum>__<Variant>`.
+    return variant_type.__name__[len(obj_type.__name__) + 2 :]
+
+
+def _serialize_key(key: typing.Any, key_type) -> str:
+    if key_type == str:
+        return key
+    if key_type in _INTEGER_TYPES or key_type in (st.uint128, st.int128):
+        return str(int(key))
+    raise st.SerializationError("Unsupported type for map keys", key_type)
+
+
+def _deserialize_key(key: str, key_type) -> typing.Any:
+    if key_type == str:
+        return key
+    try:
+        if key_type in _INTEGER_TYPES or key_type in (st.uint128, st.int128):
+            return key_type(int(key))
+    except ValueError:
+        raise st.DeserializationError("Invalid integer key", key)
+    raise st.DeserializationError("Unsupported type for map keys", key_type)
+
+
+# noqa: C901
+def to_json_value(obj: typing.Any, obj_type) -> typing.Any:
+    """Convert a value of the given type into a Python object suitable for `json.dumps`."""
+    if obj_type == bool or obj_type == str:
+        return obj
+    if obj_type in _INTEGER_TYPES or obj_type in (st.uint128, st.int128):
+        return int(obj)
+    if obj_type in _FLOAT_TYPES:
+        return float(obj)
+    if obj_type == st.char:
+        return str(obj)
+    if obj_type == bytes:
+        return list(obj)
+    if obj_type == st.unit:
+        return None
+
+    if hasattr(obj_type, "__origin__"):  # Generic type
+        types = getattr(obj_type, "__args__")
+        origin = getattr(obj_type, "__origin__")
+
+        if origin in (collections.abc.Sequence, list):  # Sequence
+            assert len(types) == 1
+            return [to_json_value(item, types[0]) for item in obj]
+
+        if origin == tuple:  # Tuple
+            if len(types) == 2 and types[1] == Ellipsis:  # Sequence as a tuple
+                return [to_json_value(item, types[0]) for item in obj]
+            if len(types) == 1 and types[0] == ():
+                return []
+            return [to_json_value(obj[i], types[i]) for i in range(len(types))]
+
+        if origin == typing.Union:  # Option
+            assert len(types) == 2 and types[1] == type(None)
+            if obj is None:
+                return None
+            return to_json_value(obj, types[0])
+
+        if origin == dict:  # Map
+            assert len(types) == 2
+            return {
+                _serialize_key(key, types[0]): to_json_value(value, types[1])
+                for key, value in obj.items()
+            }
+
+        raise st.SerializationError("Unexpected type", obj_type)
+
+    if isinstance(obj_type, type) and issubclass(obj_type, enum.IntEnum):  # C-style enum
+        if not isinstance(obj, obj_type):
+            raise st.SerializationError("Wrong Value for the type", obj, obj_type)
+        return _c_style_variant_name(obj.name)
+
+    if not dataclasses.is_dataclass(obj_type):  # Enum
+        if not hasattr(obj_type, "VARIANTS") or not hasattr(obj, "INDEX"):
+            raise st.SerializationError("Wrong Value for the type", obj, obj_type)
+        variant_type = obj_type.VARIANTS[obj.__class__.INDEX]
+        if not isinstance(obj, variant_type):
+            raise st.SerializationError("Wrong Value for the type", obj, obj_type)
+        name = _variant_name(obj_type, variant_type)
+        if not dataclasses.fields(variant_type):
+            return name
+        return {name: _struct_to_json_value(obj, variant_type)}
+
+    # pyre-ignore
+    if not isinstance(obj, obj_type):
+        raise st.SerializationError("Wrong Value for the type", obj, obj_type)
+    if not dataclasses.fields(obj_type):
+        return None
+    return _struct_to_json_value(obj, obj_type)
+
+
+def _c_style_variant_name(member_name: str) -> str:
+    # Variants named after Python keywords are escaped by the code generator (e.g. `None_`).
+    if member_name.endswith("_") and keyword.iskeyword(member_name[:-1]):
+        return member_name[:-1]
+    return member_name
+
+
+def _c_style_member_name(variant_name: str) -> str:
+    if keyword.iskeyword(variant_name):
+        return variant_name + "_"
+    return variant_name
+
+
+def _serde_name(field: dataclasses.Field) -> str:
+    # Fields named after Python keywords are escaped by the code generator.
+    return field.metadata.get("serde_name", field.name)
+
+
+def _struct_to_json_value(obj: typing.Any, obj_type) -> typing.Any:
+    types = get_type_hints(obj_type)
+    if _is_newtype(obj_type):
+        return to_json_value(obj.value, types["value"])
+    return {
+        _serde_name(field): to_json_value(obj.__dict__[field.name], types[field.name])
+        for field in dataclasses.fields(obj_type)
+    }
+
+
+# noqa: C901
+def from_json_value(value: typing.Any, obj_type) -> typing.Any:
+    """Convert a Python object obtained from `json.loads` into a value of the given type."""
+    if obj_type == bool:
+        if not isinstance(value, bool):
+            raise st.DeserializationError("Expected a boolean", value)
+        return value
+    if obj_type == str:
+        if not isinstance(value, str):
+            raise st.DeserializationError("Expected a string", value)
+        return value
+    if obj_type in _INTEGER_TYPES or obj_type in (st.uint128, st.int128):
+        if not isinstance(value, int) or isinstance(value, bool):
+            raise st.DeserializationError("Expected an integer", value)
+        return obj_type(value)
+    if obj_type in _FLOAT_TYPES:
+        if not isinstance(value, (int, float)) or isinstance(value, bool):
+            raise st.DeserializationError("Expected a number", value)
+        return obj_type(value)
+    if obj_type == st.char:
+        if not isinstance(value, str) or len(value) != 1:
+            raise st.DeserializationError("Expected a character", value)
+        return st.char(value)
+    if obj_type == bytes:
+        if not isinstance(value, list):
+            raise st.DeserializationError("Expected an array of bytes", value)
+        try:
+            return bytes(value)
+        except (TypeError, ValueError):
+            raise st.DeserializationError("Expected an array of bytes", value)
+    if obj_type == st.unit:
+        if value is not None:
+            raise st.DeserializationError("Expected null", value)
+        return None
+
+    if hasattr(obj_type, "__origin__"):  # Generic type
+        types = getattr(obj_type, "__args__")
+        origin = getattr(obj_type, "__origin__")
+
+        if origin in (collections.abc.Sequence, list):  # Sequence
+            assert len(types) == 1
+            if not isinstance(value, list):
+                raise st.DeserializationError("Expected an array", value)
+            return [from_json_value(item, types[0]) for item in value]
+
+        if origin == tuple:  # Tuple
+            if not isinstance(value, list):
+                raise st.DeserializationError("Expected an array", value)
+            if len(types) == 2 and types[1] == Ellipsis:  # Sequence as a tuple
+                return tuple(from_json_value(item, types[0]) for item in value)
+            if len(types) == 1 and types[0] == ():
+                types = ()
+            if len(value) != len(types):
+                raise st.DeserializationError("Wrong number of tuple elements", value)
+            return tuple(from_json_value(value[i], types[i]) for i in range(len(types)))
+
+        if origin == typing.Union:  # Option
+            assert len(types) == 2 and types[1] == type(None)
+            if value is None:
+                return None
+            return from_json_value(value, types[0])
+
+        if origin == dict:  # Map
+            assert len(types) == 2
+            if not isinstance(value, dict):
+                raise st.DeserializationError("Expected an object", value)
+            return {
+                _deserialize_key(key, types[0]): from_json_value(item, types[1])
+                for key, item in value.items()
+            }
+
+        raise st.DeserializationError("Unexpected type", obj_type)
+
+    if isinstance(obj_type, type) and issubclass(obj_type, enum.IntEnum):  # C-style enum
+        if not isinstance(value, str):
+            raise st.DeserializationError("Unexpected variant", value)
+        member_name = _c_style_member_name(value)
+        if member_name not in obj_type.__members__:
+            raise st.DeserializationError("Unexpected variant", value)
+        return obj_type[member_name]
+
+    if dataclasses.is_dataclass(obj_type):  # Struct
+        if not dataclasses.fields(obj_type):
+            if value is not None:
+                raise st.DeserializationError("Expected null", value)
+            return obj_type()
+        return _struct_from_json_value(value, obj_type)
+
+    if hasattr(obj_type, "VARIANTS"):  # Enum
+        if isinstance(value, str):
+            name, content = value, None
+        elif isinstance(value, dict) and len(value) == 1:
+            ((name, content),) = value.items()
+        else:
+            raise st.DeserializationError("Expected an enum variant", value)
+        for variant_type in obj_type.VARIANTS:
+            if _variant_name(obj_type, variant_type) != name:
+                continue
+            if not dataclasses.fields(variant_type):
+                if content is not None:
+                    raise st.DeserializationError("Unexpected variant content", value)
+                return variant_type()
+            return _struct_from_json_value(content, variant_type)
+        raise st.DeserializationError("Unexpected variant", name)
+
+    raise st.DeserializationError("Unexpected type", obj_type)
+
+
+def _struct_from_json_value(value: typing.Any, obj_type) -> typing.Any:
+    types = get_type_hints(obj_type)
+    if _is_newtype(obj_type):
+        return obj_type(from_json_value(value, types["value"]))
+    if not isinstance(value, dict):
+        raise st.DeserializationError("Expected an object", value)
+    values = []
+    for field in dataclasses.fields(obj_type):
+        name = _serde_name(field)
+        if name not in value:
+            raise st.DeserializationError("Missing field", name)
+        values.append(from_json_value(value[name], types[field.name]))
+    return obj_type(*values)
+
+
+def serialize(obj: typing.Any, obj_type) -> str:
+    """Serialize a value into a compact JSON string (as `serde_json::to_string`)."""
+    return json.dumps(
+        to_json_value(obj, obj_type), separators=(",", ":"), ensure_ascii=False
+    )
+
+
+def deserialize(content: str, obj_type) -> typing.Any:
+    """Deserialize a value from a JSON string."""
+    try:
+        value = json.loads(content)
+    except json.JSONDecodeError as error:
+        raise st.DeserializationError("Invalid JSON", error)
+    return from_json_value(value, obj_type)
@@ -1,8 +1,10 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use serde_reflection::{ContainerFormat, Format, FormatHolder, Registry, Result};
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use serde_reflection::{
+    ContainerFormat, Error, Format, FormatHolder, Named, Registry, Result, VariantFormat,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Compute dependencies while ignoring external names.
 fn get_dependencies<'a>(
@@ -21,6 +23,28 @@ fn get_dependencies<'a>(
     Ok(result)
 }
 
+/// Call `f` on the name and the format of each container of a `Registry`, sorted by name.
+pub fn visit_containers<'a>(
+    registry: &'a Registry,
+    f: &mut impl FnMut(&'a str, &'a ContainerFormat),
+) {
+    for (name, format) in registry {
+        f(name, format);
+    }
+}
+
+/// Call `f` on all the formats of a `Registry` in a depth-first way, including nested formats
+/// (e.g. both `Seq(U64)` and `U64`). Variables are not supported and will cause an error.
+pub fn visit_formats<'a>(registry: &'a Registry, f: &mut impl FnMut(&'a Format)) -> Result<()> {
+    for format in registry.values() {
+        format.visit(&mut |format| {
+            f(format);
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
 /// Build a map of dependencies between the entries of a `Registry`.
 /// * By definition, an entry named `x` depends on `y` iff the container format of `x` in the registry
 /// syntactically contains a reference to `y` (i.e. an expression `Format::TypeName(y)`).
@@ -43,49 +67,936 @@ pub fn get_dependency_map_with_external_dependencies<'a>(
 }
 
 /// Classic topological sorting algorithm except that it doesn't abort in case of cycles.
+/// The result only depends on the input: ties are broken by increasing keys.
 pub fn best_effort_topological_sort<T>(children: &BTreeMap<T, BTreeSet<T>>) -> Vec<T>
 where
-    T: Clone + std::cmp::Ord + std::cmp::Eq + std::hash::Hash,
+    T: Clone + std::cmp::Ord + std::cmp::Eq,
 {
-    // Build the initial queue so that we pick up nodes with less children first (and otherwise
-    // those with smaller key first).
-    // This is a heuristic to break cycles preferably at large nodes (see comment below).
-    let mut queue: Vec<_> = children.keys().rev().cloned().collect();
-    queue.sort_by(|node1, node2| children[node1].len().cmp(&children[node2].len()));
-
+    let mut state = TopologicalSortState::new(children);
     let mut result = Vec::new();
-    // Nodes already inserted in result.
-    let mut sorted = HashSet::new();
+    while let Some(node) = state.next(children) {
+        result.push(node);
+    }
+    result
+}
+
+/// Lazy version of `best_effort_topological_sort`: nodes are produced one at a time, in the
+/// same order.
+pub struct TopologicalIter<T> {
+    children: BTreeMap<T, BTreeSet<T>>,
+    state: TopologicalSortState<T>,
+}
+
+impl<T> TopologicalIter<T>
+where
+    T: Clone + std::cmp::Ord + std::cmp::Eq,
+{
+    pub fn new(children: BTreeMap<T, BTreeSet<T>>) -> Self {
+        let state = TopologicalSortState::new(&children);
+        Self { children, state }
+    }
+
+    /// The graph being sorted.
+    pub fn children(&self) -> &BTreeMap<T, BTreeSet<T>> {
+        &self.children
+    }
+}
+
+impl<T> Iterator for TopologicalIter<T>
+where
+    T: Clone + std::cmp::Ord + std::cmp::Eq,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.state.next(&self.children)
+    }
+}
+
+/// Iterate over the container names of a `Registry` in the order of
+/// `best_effort_topological_sort` applied to `get_dependency_map`.
+pub fn topological_iter(registry: &Registry) -> Result<TopologicalIter<&str>> {
+    Ok(TopologicalIter::new(get_dependency_map(registry)?))
+}
+
+/// State of the topological sorting algorithm between two produced nodes.
+struct TopologicalSortState<T> {
+    // Nodes to visit, in reverse order.
+    queue: Vec<T>,
+    // Nodes already produced.
+    sorted: BTreeSet<T>,
     // Nodes for which children have been enqueued.
-    let mut seen = HashSet::new();
+    seen: BTreeSet<T>,
+}
 
-    while let Some(node) = queue.pop() {
-        if sorted.contains(&node) {
-            continue;
+impl<T> TopologicalSortState<T>
+where
+    T: Clone + std::cmp::Ord + std::cmp::Eq,
+{
+    fn new(children: &BTreeMap<T, BTreeSet<T>>) -> Self {
+        // Build the initial queue so that we pick up nodes with less children first (and otherwise
+        // those with smaller key first).
+        // This is a heuristic to break cycles preferably at large nodes (see comment below).
+        let mut queue: Vec<_> = children.keys().rev().cloned().collect();
+        queue.sort_by(|node1, node2| children[node1].len().cmp(&children[node2].len()));
+        Self {
+            queue,
+            sorted: BTreeSet::new(),
+            seen: BTreeSet::new(),
         }
-        if seen.contains(&node) {
-            // Second time we see this node.
-            // * If `node` does not belong to a cycle in the graph, then by now, all its children
-            // have been sorted.
-            // * If `node` has children that depend back on it. We may be visiting `node` again
-            // before some of those children. Just insert `node` here. By ignoring edges going back
-            // to `node` now, we are effectively deciding to "break the cycle" there in future
-            // applications (e.g. `node` may be forward-declared in C++ and `Box`-ed in Rust).
-            sorted.insert(node.clone());
-            result.push(node);
+    }
+
+    fn next(&mut self, children: &BTreeMap<T, BTreeSet<T>>) -> Option<T> {
+        while let Some(node) = self.queue.pop() {
+            if self.sorted.contains(&node) {
+                continue;
+            }
+            if self.seen.contains(&node) {
+                // Second time we see this node.
+                // * If `node` does not belong to a cycle in the graph, then by now, all its children
+                // have been sorted.
+                // * If `node` has children that depend back on it. We may be visiting `node` again
+                // before some of those children. Just insert `node` here. By ignoring edges going back
+                // to `node` now, we are effectively deciding to "break the cycle" there in future
+                // applications (e.g. `node` may be forward-declared in C++ and `Box`-ed in Rust).
+                self.sorted.insert(node.clone());
+                return Some(node);
+            }
+            // First time we see this node:
+            // 1. Mark it so that it is no longer enqueued.
+            self.seen.insert(node.clone());
+            // 2. Schedule all the (yet unseen) children then this node for a second visit.
+            // (If possible, visit children by increasing key.)
+            self.queue.push(node.clone());
+            for child in children[&node].iter().rev() {
+                if !self.seen.contains(child) {
+                    self.queue.push(child.clone());
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Compute the strongly connected components of a graph using Tarjan's algorithm.
+/// Each component is sorted. Components are listed so that each component only depends on itself
+/// and the components listed before it.
+pub fn get_strongly_connected_components<T>(children: &BTreeMap<T, BTreeSet<T>>) -> Vec<Vec<T>>
+where
+    T: Clone + std::cmp::Ord + std::cmp::Eq,
+{
+    struct State<T> {
+        next_index: usize,
+        indices: BTreeMap<T, usize>,
+        lowlinks: BTreeMap<T, usize>,
+        stack: Vec<T>,
+        on_stack: BTreeSet<T>,
+        components: Vec<Vec<T>>,
+    }
+
+    fn visit<T>(node: &T, children: &BTreeMap<T, BTreeSet<T>>, state: &mut State<T>)
+    where
+        T: Clone + std::cmp::Ord + std::cmp::Eq,
+    {
+        state.indices.insert(node.clone(), state.next_index);
+        state.lowlinks.insert(node.clone(), state.next_index);
+        state.next_index += 1;
+        state.stack.push(node.clone());
+        state.on_stack.insert(node.clone());
+
+        for child in children.get(node).into_iter().flatten() {
+            let lowlink = if !state.indices.contains_key(child) {
+                visit(child, children, state);
+                state.lowlinks[child]
+            } else if state.on_stack.contains(child) {
+                state.indices[child]
+            } else {
+                continue;
+            };
+            if lowlink < state.lowlinks[node] {
+                state.lowlinks.insert(node.clone(), lowlink);
+            }
+        }
+
+        if state.lowlinks[node] == state.indices[node] {
+            let mut component = Vec::new();
+            while let Some(item) = state.stack.pop() {
+                state.on_stack.remove(&item);
+                let is_root = &item == node;
+                component.push(item);
+                if is_root {
+                    break;
+                }
+            }
+            component.sort();
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        next_index: 0,
+        indices: BTreeMap::new(),
+        lowlinks: BTreeMap::new(),
+        stack: Vec::new(),
+        on_stack: BTreeSet::new(),
+        components: Vec::new(),
+    };
+    for node in children.keys() {
+        if !state.indices.contains_key(node) {
+            visit(node, children, &mut state);
+        }
+    }
+    state.components
+}
+
+/// Compute the strongly connected components of the dependency graph of a `Registry`.
+/// * Containers in the same component are mutually recursive.
+/// * Components are listed so that each component only depends on itself and the components
+/// listed before it.
+pub fn strongly_connected_components(registry: &Registry) -> Result<Vec<Vec<String>>> {
+    let dependencies = get_dependency_map(registry)?;
+    Ok(get_strongly_connected_components(&dependencies)
+        .into_iter()
+        .map(|component| component.into_iter().map(String::from).collect())
+        .collect())
+}
+
+/// Compute the names reachable from the given roots in a dependency map.
+fn get_reachable_names<'a>(
+    dependencies: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+    roots: &[&'a str],
+) -> BTreeSet<&'a str> {
+    let mut reachable = BTreeSet::new();
+    let mut queue = roots.to_vec();
+    while let Some(name) = queue.pop() {
+        if !reachable.insert(name) {
             continue;
         }
-        // First time we see this node:
-        // 1. Mark it so that it is no longer enqueued.
-        seen.insert(node.clone());
-        // 2. Schedule all the (yet unseen) children then this node for a second visit.
-        // (If possible, visit children by increasing key.)
-        queue.push(node.clone());
-        for child in children[&node].iter().rev() {
-            if !seen.contains(child) {
-                queue.push(child.clone());
+        if let Some(children) = dependencies.get(name) {
+            queue.extend(children.iter().copied());
+        }
+    }
+    reachable
+}
+
+/// Compute the containers of a `Registry` that cannot be reached from the given roots by following
+/// `Format::TypeName` references. Results are sorted by name.
+pub fn unreferenced_containers(registry: &Registry, roots: &[&str]) -> Result<Vec<String>> {
+    let dependencies = get_dependency_map(registry)?;
+    let reachable = get_reachable_names(&dependencies, roots);
+    Ok(registry
+        .keys()
+        .filter(|name| !reachable.contains(name.as_str()))
+        .cloned()
+        .collect())
+}
+
+/// Extract the containers of a `Registry` that can be reached from the given roots by following
+/// `Format::TypeName` references. This is useful to generate code only for these containers.
+pub fn reachable_subregistry(registry: &Registry, roots: &[&str]) -> Result<Registry> {
+    let dependencies = get_dependency_map(registry)?;
+    let reachable = get_reachable_names(&dependencies, roots);
+    Ok(registry
+        .iter()
+        .filter(|(name, _)| reachable.contains(name.as_str()))
+        .map(|(name, format)| (name.clone(), format.clone()))
+        .collect())
+}
+
+/// Merge several registries into one. Containers defined in more than one registry must have
+/// the same definition everywhere, otherwise an error is returned.
+pub fn merge_registries(registries: Vec<Registry>) -> Result<Registry> {
+    let mut result = Registry::new();
+    for registry in registries {
+        for (name, format) in registry {
+            match result.get(&name) {
+                Some(existing) if existing != &format => {
+                    return Err(Error::Custom(format!(
+                        "Conflicting definitions for container `{}`",
+                        name
+                    )));
+                }
+                Some(_) => (),
+                None => {
+                    result.insert(name, format);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Check that the formats of a `Registry` do not contain any `Format::Variable` or
+/// `VariantFormat::Variable`, as code generators only support fully resolved formats. The error
+/// names the container and the path of the first unresolved format.
+pub fn check_fully_resolved(registry: &Registry) -> Result<()> {
+    for (name, format) in registry {
+        match format {
+            ContainerFormat::UnitStruct => (),
+            ContainerFormat::NewTypeStruct(format) => check_format_is_resolved(format, name)?,
+            ContainerFormat::TupleStruct(formats) => {
+                for (index, format) in formats.iter().enumerate() {
+                    check_format_is_resolved(format, &format!("{}.{}", name, index))?;
+                }
+            }
+            ContainerFormat::Struct(fields) => {
+                for field in fields {
+                    check_format_is_resolved(&field.value, &format!("{}.{}", name, field.name))?;
+                }
+            }
+            ContainerFormat::Enum(variants) => {
+                for variant in variants.values() {
+                    let path = format!("{}::{}", name, variant.name);
+                    match &variant.value {
+                        VariantFormat::Variable(_) => return Err(unresolved_format_error(&path)),
+                        VariantFormat::Unit => (),
+                        VariantFormat::NewType(format) => check_format_is_resolved(format, &path)?,
+                        VariantFormat::Tuple(formats) => {
+                            for (index, format) in formats.iter().enumerate() {
+                                check_format_is_resolved(format, &format!("{}.{}", path, index))?;
+                            }
+                        }
+                        VariantFormat::Struct(fields) => {
+                            for field in fields {
+                                check_format_is_resolved(
+                                    &field.value,
+                                    &format!("{}.{}", path, field.name),
+                                )?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that the variant indices of each enum in a `Registry` form the sequence `0..n`, as
+/// expected by code generators. This may not be the case for hand-edited registries.
+pub fn check_variant_indices(registry: &Registry) -> Result<()> {
+    for (name, format) in registry {
+        if let ContainerFormat::Enum(variants) = format {
+            for (expected, found) in variants.keys().enumerate() {
+                if *found != expected as u32 {
+                    return Err(Error::Custom(format!(
+                        "Non-contiguous variant indices in enum `{}`: expected {}, found {}",
+                        name, expected, found
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_format_is_resolved(format: &Format, path: &str) -> Result<()> {
+    match format {
+        Format::Variable(_) => Err(unresolved_format_error(path)),
+        Format::Option(format) | Format::Seq(format) => check_format_is_resolved(format, path),
+        Format::Map { key, value } => {
+            check_format_is_resolved(key, path)?;
+            check_format_is_resolved(value, path)
+        }
+        Format::Tuple(formats) => formats
+            .iter()
+            .try_for_each(|format| check_format_is_resolved(format, path)),
+        Format::TupleArray { content, .. } => check_format_is_resolved(content, path),
+        _ => Ok(()),
+    }
+}
+
+fn unresolved_format_error(path: &str) -> Error {
+    Error::Custom(format!("Unresolved format variable at {}", path))
+}
+
+/// The kind of a container, as returned by `container_kinds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ContainerKind {
+    /// A unit struct, e.g. `struct A;`.
+    Unit,
+    /// A newtype struct, e.g. `struct A(u32);`.
+    NewType,
+    /// A tuple struct, e.g. `struct A(u32, u64);`.
+    Tuple,
+    /// A struct with named fields.
+    Struct,
+    /// An enum.
+    Enum,
+}
+
+/// Compute the kind of each container of a `Registry`. Entries are sorted by name, as in the
+/// registry. (A topological order is given by `topological_iter`.)
+pub fn container_kinds(registry: &Registry) -> BTreeMap<String, ContainerKind> {
+    registry
+        .iter()
+        .map(|(name, format)| (name.clone(), container_kind(format)))
+        .collect()
+}
+
+fn container_kind(format: &ContainerFormat) -> ContainerKind {
+    match format {
+        ContainerFormat::UnitStruct => ContainerKind::Unit,
+        ContainerFormat::NewTypeStruct(_) => ContainerKind::NewType,
+        ContainerFormat::TupleStruct(_) => ContainerKind::Tuple,
+        ContainerFormat::Struct(_) => ContainerKind::Struct,
+        ContainerFormat::Enum(_) => ContainerKind::Enum,
+    }
+}
+
+/// Whether a format contains a floating-point number (`F32` or `F64`), either directly or
+/// through one of the given containers.
+pub fn format_contains_float(format: &Format, float_containers: &BTreeSet<&str>) -> bool {
+    match format {
+        Format::F32 | Format::F64 => true,
+        Format::TypeName(name) => float_containers.contains(name.as_str()),
+        Format::Option(format) | Format::Seq(format) => {
+            format_contains_float(format, float_containers)
+        }
+        Format::Map { key, value } => {
+            format_contains_float(key, float_containers)
+                || format_contains_float(value, float_containers)
+        }
+        Format::Tuple(formats) => formats
+            .iter()
+            .any(|format| format_contains_float(format, float_containers)),
+        Format::TupleArray { content, .. } => format_contains_float(content, float_containers),
+        _ => false,
+    }
+}
+
+/// Whether the values of a format are totally ordered (e.g. implement `Ord` in Rust), assuming
+/// that the values of the given containers are. Floating-point numbers are not.
+pub fn format_is_orderable(format: &Format, orderable_containers: &BTreeSet<&str>) -> bool {
+    match format {
+        Format::Variable(_) | Format::F32 | Format::F64 => false,
+        Format::TypeName(name) => orderable_containers.contains(name.as_str()),
+        Format::Option(format) | Format::Seq(format) => {
+            format_is_orderable(format, orderable_containers)
+        }
+        Format::Map { key, value } => {
+            format_is_orderable(key, orderable_containers)
+                && format_is_orderable(value, orderable_containers)
+        }
+        Format::Tuple(formats) => formats
+            .iter()
+            .all(|format| format_is_orderable(format, orderable_containers)),
+        Format::TupleArray { content, .. } => format_is_orderable(content, orderable_containers),
+        _ => true,
+    }
+}
+
+/// Compute the containers of a `Registry` that (transitively) contain a floating-point number.
+pub fn containers_with_floats(registry: &Registry) -> BTreeSet<&str> {
+    let mut result = BTreeSet::new();
+    // Iterate until we reach a fixpoint.
+    loop {
+        let previous_len = result.len();
+        for (name, format) in registry {
+            let contains_float = match format {
+                ContainerFormat::UnitStruct => false,
+                ContainerFormat::NewTypeStruct(format) => format_contains_float(format, &result),
+                ContainerFormat::TupleStruct(formats) => formats
+                    .iter()
+                    .any(|format| format_contains_float(format, &result)),
+                ContainerFormat::Struct(fields) => fields
+                    .iter()
+                    .any(|field| format_contains_float(&field.value, &result)),
+                ContainerFormat::Enum(variants) => {
+                    variants.values().any(|variant| match &variant.value {
+                        VariantFormat::NewType(format) => format_contains_float(format, &result),
+                        VariantFormat::Tuple(formats) => formats
+                            .iter()
+                            .any(|format| format_contains_float(format, &result)),
+                        VariantFormat::Struct(fields) => fields
+                            .iter()
+                            .any(|field| format_contains_float(&field.value, &result)),
+                        VariantFormat::Unit | VariantFormat::Variable(_) => false,
+                    })
+                }
+            };
+            if contains_float {
+                result.insert(name.as_str());
             }
         }
+        if result.len() == previous_len {
+            return result;
+        }
+    }
+}
+
+/// Results of the analysis of the dependencies between the containers of a `Registry`, as
+/// returned by `analyze`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Analysis<'a> {
+    /// Dependencies of each container, as returned by `get_dependency_map`.
+    pub dependencies: BTreeMap<&'a str, BTreeSet<&'a str>>,
+    /// Containers sorted by `best_effort_topological_sort`.
+    pub sorted_names: Vec<&'a str>,
+    /// Containers involved in a cycle of dependencies (including containers that refer to
+    /// themselves). Depending on the language, these may require boxing or other indirections.
+    pub recursive_names: BTreeSet<&'a str>,
+}
+
+/// Compute the dependency map, the topological order, and the recursive containers of a
+/// `Registry` at once.
+pub fn analyze(registry: &Registry) -> Result<Analysis<'_>> {
+    let dependencies = get_dependency_map(registry)?;
+    let sorted_names = best_effort_topological_sort(&dependencies);
+    let mut recursive_names = BTreeSet::new();
+    for component in get_strongly_connected_components(&dependencies) {
+        let is_recursive = component.len() > 1
+            || dependencies
+                .get(component[0])
+                .map_or(false, |children| children.contains(component[0]));
+        if is_recursive {
+            recursive_names.extend(component);
+        }
+    }
+    Ok(Analysis {
+        dependencies,
+        sorted_names,
+        recursive_names,
+    })
+}
+
+/// Describe the dependencies between the containers of a `Registry` as a graph in the DOT
+/// format of Graphviz (e.g. `dot -Tsvg`). Nodes are container names and edges go from each
+/// container to the containers that it references. Containers involved in a cycle of
+/// dependencies are colored in red.
+pub fn to_dot(registry: &Registry) -> Result<String> {
+    let Analysis {
+        dependencies,
+        recursive_names,
+        ..
+    } = analyze(registry)?;
+    let mut result = String::from("digraph {\n");
+    for name in dependencies.keys() {
+        if recursive_names.contains(name) {
+            result.push_str(&format!("    {:?} [color=red];\n", name));
+        } else {
+            result.push_str(&format!("    {:?};\n", name));
+        }
+    }
+    for (name, children) in &dependencies {
+        for child in children {
+            result.push_str(&format!("    {:?} -> {:?};\n", name, child));
+        }
+    }
+    result.push_str("}\n");
+    Ok(result)
+}
+
+/// Differences between two registries, as returned by `diff_registries`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RegistryDiff {
+    /// Containers only defined in the new registry.
+    pub added_containers: Vec<String>,
+    /// Containers only defined in the old registry.
+    pub removed_containers: Vec<String>,
+    /// Containers defined differently in both registries.
+    pub changed_containers: BTreeMap<String, ContainerDiff>,
+    /// Containers whose values serialized with the old definition cannot be read with the new
+    /// one in the Bincode (or BCS) format. Since fields and variants are encoded by position and
+    /// index, renaming them in place is compatible, but changing the sequence of field formats,
+    /// moving fields or variants, or removing and changing variants is breaking. Adding
+    /// variants at the end of an enum is compatible. Containers that reference incompatible
+    /// containers are incompatible as well.
+    pub bincode_incompatible_containers: BTreeSet<String>,
+}
+
+/// Differences between two definitions of a container. Members of tuple structs and tuple
+/// variants are named after their position (e.g. `"0"`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerDiff {
+    /// Whether the kind of container changed (e.g. from a struct to an enum). Members are not
+    /// compared in this case.
+    pub kind_changed: bool,
+    /// Fields only present in the new definition.
+    pub added_fields: Vec<String>,
+    /// Fields only present in the old definition.
+    pub removed_fields: Vec<String>,
+    /// Fields present in both definitions with a different format.
+    pub changed_fields: Vec<String>,
+    /// Whether the fields present in both definitions appear in a different order.
+    pub reordered_fields: bool,
+    /// Variants only present in the new definition.
+    pub added_variants: Vec<String>,
+    /// Variants only present in the old definition.
+    pub removed_variants: Vec<String>,
+    /// Variants present in both definitions with a different format.
+    pub changed_variants: Vec<String>,
+    /// Variants present in both definitions with a different index.
+    pub reindexed_variants: Vec<String>,
+}
+
+impl RegistryDiff {
+    /// Whether the two registries are identical.
+    pub fn is_empty(&self) -> bool {
+        self.added_containers.is_empty()
+            && self.removed_containers.is_empty()
+            && self.changed_containers.is_empty()
+    }
+}
+
+/// Compare two versions of a `Registry`, e.g. to detect wire-incompatible changes before a
+/// migration. Containers are matched by name, fields and variants of each container as well.
+pub fn diff_registries(old: &Registry, new: &Registry) -> RegistryDiff {
+    let mut result = RegistryDiff::default();
+    for (name, old_format) in old {
+        match new.get(name) {
+            None => result.removed_containers.push(name.clone()),
+            Some(new_format) if new_format != old_format => {
+                let diff = diff_containers(old_format, new_format);
+                result.changed_containers.insert(name.clone(), diff);
+            }
+            Some(_) => (),
+        }
+    }
+    result.added_containers = new
+        .keys()
+        .filter(|name| !old.contains_key(*name))
+        .cloned()
+        .collect();
+    // Propagate incompatibilities to the containers that reference them until a fixpoint is
+    // reached.
+    let incompatible = &mut result.bincode_incompatible_containers;
+    loop {
+        let mut changed = false;
+        for (name, new_format) in new {
+            if incompatible.contains(name) {
+                continue;
+            }
+            if let Some(old_format) = old.get(name) {
+                // Members that keep their names but move change meaning.
+                let moved = result.changed_containers.get(name).map_or(false, |diff| {
+                    diff.reordered_fields || !diff.reindexed_variants.is_empty()
+                });
+                if moved || !is_bincode_compatible_container(old_format, new_format, incompatible) {
+                    incompatible.insert(name.clone());
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
     }
     result
 }
+
+/// Whether values of the old container can be read as the new one in the Bincode format,
+/// assuming that the referenced containers are compatible unless listed in `incompatible`.
+fn is_bincode_compatible_container(
+    old: &ContainerFormat,
+    new: &ContainerFormat,
+    incompatible: &BTreeSet<String>,
+) -> bool {
+    match (old, new) {
+        (ContainerFormat::Enum(old), ContainerFormat::Enum(new)) => {
+            old.iter().all(|(index, old_variant)| match new.get(index) {
+                Some(new_variant) => are_bincode_compatible_formats(
+                    &variant_formats(&old_variant.value),
+                    &variant_formats(&new_variant.value),
+                    incompatible,
+                ),
+                None => false,
+            })
+        }
+        (ContainerFormat::Enum(_), _) | (_, ContainerFormat::Enum(_)) => false,
+        _ => {
+            let formats = |format| {
+                container_fields(format)
+                    .into_iter()
+                    .map(|field| field.value)
+                    .collect::<Vec<_>>()
+            };
+            are_bincode_compatible_formats(&formats(old), &formats(new), incompatible)
+        }
+    }
+}
+
+/// Formats of the content of a variant, in order.
+fn variant_formats(format: &VariantFormat) -> Vec<Format> {
+    match format {
+        VariantFormat::Variable(_) | VariantFormat::Unit => Vec::new(),
+        VariantFormat::NewType(format) => vec![format.as_ref().clone()],
+        VariantFormat::Tuple(formats) => formats.clone(),
+        VariantFormat::Struct(fields) => fields.iter().map(|field| field.value.clone()).collect(),
+    }
+}
+
+fn are_bincode_compatible_formats(
+    old: &[Format],
+    new: &[Format],
+    incompatible: &BTreeSet<String>,
+) -> bool {
+    old.len() == new.len()
+        && old
+            .iter()
+            .zip(new)
+            .all(|(old, new)| is_bincode_compatible_format(old, new, incompatible))
+}
+
+fn is_bincode_compatible_format(
+    old: &Format,
+    new: &Format,
+    incompatible: &BTreeSet<String>,
+) -> bool {
+    use Format::*;
+    match (old, new) {
+        (TypeName(old), TypeName(new)) => old == new && !incompatible.contains(new),
+        (Option(old), Option(new)) | (Seq(old), Seq(new)) => {
+            is_bincode_compatible_format(old, new, incompatible)
+        }
+        (
+            Map {
+                key: old_key,
+                value: old_value,
+            },
+            Map {
+                key: new_key,
+                value: new_value,
+            },
+        ) => {
+            is_bincode_compatible_format(old_key, new_key, incompatible)
+                && is_bincode_compatible_format(old_value, new_value, incompatible)
+        }
+        (Tuple(old), Tuple(new)) => are_bincode_compatible_formats(old, new, incompatible),
+        (
+            TupleArray {
+                content: old_content,
+                size: old_size,
+            },
+            TupleArray {
+                content: new_content,
+                size: new_size,
+            },
+        ) => {
+            old_size == new_size
+                && is_bincode_compatible_format(old_content, new_content, incompatible)
+        }
+        _ => old == new,
+    }
+}
+
+fn diff_containers(old: &ContainerFormat, new: &ContainerFormat) -> ContainerDiff {
+    let mut result = ContainerDiff::default();
+    if container_kind(old) != container_kind(new) {
+        result.kind_changed = true;
+        return result;
+    }
+    match (old, new) {
+        (ContainerFormat::Enum(old), ContainerFormat::Enum(new)) => {
+            diff_variants(old, new, &mut result)
+        }
+        _ => {
+            let old = container_fields(old);
+            let new = container_fields(new);
+            diff_fields(&old, &new, &mut result);
+        }
+    }
+    result
+}
+
+/// Fields of a struct, named after their position in the case of tuple structs.
+fn container_fields(format: &ContainerFormat) -> Vec<Named<Format>> {
+    match format {
+        ContainerFormat::UnitStruct | ContainerFormat::Enum(_) => Vec::new(),
+        ContainerFormat::NewTypeStruct(format) => positional_fields(std::slice::from_ref(format)),
+        ContainerFormat::TupleStruct(formats) => positional_fields(formats),
+        ContainerFormat::Struct(fields) => fields.clone(),
+    }
+}
+
+fn positional_fields(formats: &[Format]) -> Vec<Named<Format>> {
+    formats
+        .iter()
+        .enumerate()
+        .map(|(index, format)| Named {
+            name: index.to_string(),
+            value: format.clone(),
+        })
+        .collect()
+}
+
+fn diff_fields(old: &[Named<Format>], new: &[Named<Format>], result: &mut ContainerDiff) {
+    let find = |fields: &[Named<Format>], name: &str| {
+        fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| field.value.clone())
+    };
+    for field in old {
+        match find(new, &field.name) {
+            None => result.removed_fields.push(field.name.clone()),
+            Some(format) if format != field.value => result.changed_fields.push(field.name.clone()),
+            Some(_) => (),
+        }
+    }
+    for field in new {
+        if find(old, &field.name).is_none() {
+            result.added_fields.push(field.name.clone());
+        }
+    }
+    let common_names = |fields: &[Named<Format>], other: &[Named<Format>]| {
+        fields
+            .iter()
+            .filter(|field| find(other, &field.name).is_some())
+            .map(|field| field.name.clone())
+            .collect::<Vec<_>>()
+    };
+    result.reordered_fields = common_names(old, new) != common_names(new, old);
+}
+
+fn diff_variants(
+    old: &BTreeMap<u32, Named<VariantFormat>>,
+    new: &BTreeMap<u32, Named<VariantFormat>>,
+    result: &mut ContainerDiff,
+) {
+    let find = |variants: &BTreeMap<u32, Named<VariantFormat>>, name: &str| {
+        variants
+            .iter()
+            .find(|(_, variant)| variant.name == name)
+            .map(|(index, variant)| (*index, variant.value.clone()))
+    };
+    for (index, variant) in old {
+        match find(new, &variant.name) {
+            None => result.removed_variants.push(variant.name.clone()),
+            Some((new_index, format)) => {
+                if new_index != *index {
+                    result.reindexed_variants.push(variant.name.clone());
+                }
+                if format != variant.value {
+                    result.changed_variants.push(variant.name.clone());
+                }
+            }
+        }
+    }
+    for variant in new.values() {
+        if find(old, &variant.name).is_none() {
+            result.added_variants.push(variant.name.clone());
+        }
+    }
+}
+
+/// Describe a `Registry` as a language-neutral JSON document, for tools that do not depend on
+/// `serde_reflection`. The document has the following shape:
+/// * `{"version": 1, "containers": [..]}` where containers are sorted by name;
+/// * each container is an object `{"name": .., "kind": ..}` where `kind` is `"unit"`,
+///   `"newtype"` (with a `"type"`), `"tuple"` (with `"types"`), `"struct"` (with `"fields"`), or
+///   `"enum"` (with `"variants"`);
+/// * fields are objects `{"name": .., "type": ..}`;
+/// * variants are objects `{"name": .., "index": .., "kind": ..}` where `kind` is `"unit"`,
+///   `"newtype"`, `"tuple"`, or `"struct"`, with the same additional entries as containers;
+/// * types are objects `{"kind": ..}` where `kind` is a primitive type (`"unit"`, `"bool"`,
+///   `"i8"` to `"i128"`, `"u8"` to `"u128"`, `"f32"`, `"f64"`, `"char"`, `"str"`, or `"bytes"`),
+///   `"ref"` (with the `"name"` of a container), `"option"` or `"seq"` (with a `"type"`), `"map"`
+///   (with a `"key"` and a `"value"`), `"tuple"` (with `"types"`), or `"array"` (with a `"type"`
+///   and a `"size"`).
+///
+/// The registry must be fully resolved (see `check_fully_resolved`).
+pub fn to_schema_json(registry: &Registry) -> Result<serde_json::Value> {
+    check_fully_resolved(registry)?;
+    let containers = registry
+        .iter()
+        .map(|(name, format)| {
+            let mut value = match format {
+                ContainerFormat::UnitStruct => serde_json::json!({"kind": "unit"}),
+                ContainerFormat::NewTypeStruct(format) => {
+                    serde_json::json!({"kind": "newtype", "type": schema_type(format)})
+                }
+                ContainerFormat::TupleStruct(formats) => {
+                    serde_json::json!({"kind": "tuple", "types": schema_types(formats)})
+                }
+                ContainerFormat::Struct(fields) => {
+                    serde_json::json!({"kind": "struct", "fields": schema_fields(fields)})
+                }
+                ContainerFormat::Enum(variants) => {
+                    let variants = variants
+                        .iter()
+                        .map(|(index, variant)| {
+                            let mut value = schema_variant(&variant.value);
+                            value["name"] = variant.name.clone().into();
+                            value["index"] = (*index).into();
+                            value
+                        })
+                        .collect::<Vec<_>>();
+                    serde_json::json!({"kind": "enum", "variants": variants})
+                }
+            };
+            value["name"] = name.clone().into();
+            value
+        })
+        .collect::<Vec<_>>();
+    Ok(serde_json::json!({"version": 1, "containers": containers}))
+}
+
+fn schema_variant(format: &VariantFormat) -> serde_json::Value {
+    match format {
+        VariantFormat::Unit => serde_json::json!({"kind": "unit"}),
+        VariantFormat::NewType(format) => {
+            serde_json::json!({"kind": "newtype", "type": schema_type(format)})
+        }
+        VariantFormat::Tuple(formats) => {
+            serde_json::json!({"kind": "tuple", "types": schema_types(formats)})
+        }
+        VariantFormat::Struct(fields) => {
+            serde_json::json!({"kind": "struct", "fields": schema_fields(fields)})
+        }
+        VariantFormat::Variable(_) => unreachable!("registry is fully resolved"),
+    }
+}
+
+fn schema_fields(fields: &[Named<Format>]) -> Vec<serde_json::Value> {
+    fields
+        .iter()
+        .map(|field| serde_json::json!({"name": field.name, "type": schema_type(&field.value)}))
+        .collect()
+}
+
+fn schema_types(formats: &[Format]) -> Vec<serde_json::Value> {
+    formats.iter().map(schema_type).collect()
+}
+
+fn schema_type(format: &Format) -> serde_json::Value {
+    let primitive = |kind: &str| serde_json::json!({ "kind": kind });
+    match format {
+        Format::Variable(_) => unreachable!("registry is fully resolved"),
+        Format::TypeName(name) => serde_json::json!({"kind": "ref", "name": name}),
+        Format::Unit => primitive("unit"),
+        Format::Bool => primitive("bool"),
+        Format::I8 => primitive("i8"),
+        Format::I16 => primitive("i16"),
+        Format::I32 => primitive("i32"),
+        Format::I64 => primitive("i64"),
+        Format::I128 => primitive("i128"),
+        Format::U8 => primitive("u8"),
+        Format::U16 => primitive("u16"),
+        Format::U32 => primitive("u32"),
+        Format::U64 => primitive("u64"),
+        Format::U128 => primitive("u128"),
+        Format::F32 => primitive("f32"),
+        Format::F64 => primitive("f64"),
+        Format::Char => primitive("char"),
+        Format::Str => primitive("str"),
+        Format::Bytes => primitive("bytes"),
+        Format::Option(format) => {
+            serde_json::json!({"kind": "option", "type": schema_type(format)})
+        }
+        Format::Seq(format) => serde_json::json!({"kind": "seq", "type": schema_type(format)}),
+        Format::Map { key, value } => serde_json::json!({
+            "kind": "map",
+            "key": schema_type(key),
+            "value": schema_type(value),
+        }),
+        Format::Tuple(formats) => {
+            serde_json::json!({"kind": "tuple", "types": schema_types(formats)})
+        }
+        Format::TupleArray { content, size } => serde_json::json!({
+            "kind": "array",
+            "type": schema_type(content),
+            "size": size,
+        }),
+    }
+}
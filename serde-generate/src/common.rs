@@ -1,7 +1,7 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use serde_reflection::Format;
+use serde_reflection::{Format, Registry};
 
 pub(crate) fn mangle_type(format: &Format) -> String {
     use Format::*;
@@ -57,3 +57,49 @@ pub(crate) fn lowercase_first_letter(s: &str) -> String {
         Some(f) => f.to_lowercase().collect::<String>() + c.as_str(),
     }
 }
+
+/// Same as `analyzer::check_fully_resolved` for generators returning I/O errors.
+pub(crate) fn check_fully_resolved(registry: &Registry) -> std::io::Result<()> {
+    crate::analyzer::check_fully_resolved(registry).map_err(into_io_error)
+}
+
+/// Same as `analyzer::check_variant_indices` for generators returning I/O errors.
+pub(crate) fn check_variant_indices(registry: &Registry) -> std::io::Result<()> {
+    crate::analyzer::check_variant_indices(registry).map_err(into_io_error)
+}
+
+/// Report an invalid registry as an I/O error.
+fn into_io_error(error: serde_reflection::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, error.to_string())
+}
+
+/// Whether `name` is made of letters, digits, and underscores, and does not start with a digit.
+/// This is the common syntax of identifiers in most target languages.
+pub(crate) fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => chars.all(|c| c.is_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+/// Check that the name of each container is a valid identifier in the target language, as
+/// decided by `is_valid`.
+pub(crate) fn check_container_names(
+    registry: &Registry,
+    language: &str,
+    is_valid: impl Fn(&str) -> bool,
+) -> std::io::Result<()> {
+    for name in registry.keys() {
+        if !is_valid(name) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Container name `{}` is not a valid identifier in {}",
+                    name, language
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
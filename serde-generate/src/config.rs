@@ -15,9 +15,14 @@ pub struct CodeGeneratorConfig {
     pub(crate) c_style_enums: bool,
 }
 
+/// Binary encodings supported by the generated (de)serialization methods.
 #[derive(Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Encoding {
+    /// The format of the Rust crate `bincode` (version 1) with its default options: lengths and
+    /// variant indices are fixed-size integers and maps are encoded in insertion order.
     Bincode,
+    /// Binary Canonical Serialization, i.e. the format of the Rust crate `bcs`: lengths and
+    /// variant indices are ULEB128-encoded and map entries are sorted by their serialized keys.
     Bcs,
 }
 
@@ -52,7 +57,7 @@ pub trait SourceInstaller {
     /// Install the bincode runtime.
     fn install_bincode_runtime(&self) -> std::result::Result<(), Self::Error>;
 
-    /// Install the Libra Canonical Serialization (BCS) runtime.
+    /// Install the Binary Canonical Serialization (BCS) runtime.
     fn install_bcs_runtime(&self) -> std::result::Result<(), Self::Error>;
 }
 
@@ -120,6 +125,7 @@ impl CodeGeneratorConfig {
 }
 
 impl Encoding {
+    /// The name of the encoding, as used in generated method and module names.
     pub fn name(self) -> &'static str {
         match self {
             Encoding::Bincode => "bincode",
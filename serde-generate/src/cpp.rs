@@ -61,6 +61,8 @@ impl<'a> CodeGenerator<'a> {
         out: &mut dyn Write,
         registry: &Registry,
     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        analyzer::check_fully_resolved(registry)?;
+        analyzer::check_variant_indices(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -78,11 +80,9 @@ impl<'a> CodeGenerator<'a> {
         emitter.output_preamble()?;
         emitter.output_open_namespace()?;
 
-        let dependencies = analyzer::get_dependency_map(registry)?;
-        let entries = analyzer::best_effort_topological_sort(&dependencies);
-
-        for name in entries {
-            for dependency in &dependencies[name] {
+        let mut entries = analyzer::topological_iter(registry)?;
+        while let Some(name) = entries.next() {
+            for dependency in &entries.children()[name] {
                 if !emitter.known_names.contains(dependency) {
                     emitter.output_container_forward_definition(*dependency)?;
                     emitter.known_names.insert(*dependency);
@@ -304,8 +304,7 @@ where
         self.output_comment(name)?;
         writeln!(self.out, "struct {} {{", name)?;
         self.enter_class(name);
-        for (expected_index, (index, variant)) in variants.iter().enumerate() {
-            assert_eq!(*index, expected_index as u32);
+        for variant in variants.values() {
             self.output_variant(&variant.name, &variant.value)?;
         }
         writeln!(
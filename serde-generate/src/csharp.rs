@@ -2,13 +2,13 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    common,
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
 use heck::CamelCase;
 use include_dir::include_dir as include_directory;
-use serde_reflection::{ContainerFormat, Format, FormatHolder, Named, Registry, VariantFormat};
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
 use std::{
     collections::{BTreeMap, HashMap},
     io::{Result, Write},
@@ -66,6 +66,8 @@ impl<'a> CodeGenerator<'a> {
         install_dir: std::path::PathBuf,
         registry: &Registry,
     ) -> Result<std::path::PathBuf> {
+        common::check_fully_resolved(registry)?;
+        common::check_variant_indices(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -228,8 +230,15 @@ using System.Numerics;"
         let mut path = self.current_namespace.clone();
         path.push(name.to_string());
         if let Some(doc) = self.generator.config.comments.get(&path) {
-            let text = textwrap::indent(doc, "/// ").replace("\n\n", "\n///\n");
+            // Use XML documentation comments.
+            let doc = doc
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;");
+            let text = textwrap::indent(&doc, "/// ").replace("\n\n", "\n///\n");
+            writeln!(self.out, "/// <summary>")?;
             write!(self.out, "{}", text)?;
+            writeln!(self.out, "/// </summary>")?;
         }
         Ok(())
     }
@@ -328,16 +337,12 @@ using System.Numerics;"
 
     fn output_trait_helpers(&mut self, registry: &Registry) -> Result<()> {
         let mut subtypes = BTreeMap::new();
-        for format in registry.values() {
-            format
-                .visit(&mut |f| {
-                    if Self::needs_helper(f) {
-                        subtypes.insert(common::mangle_type(f), f.clone());
-                    }
-                    Ok(())
-                })
-                .unwrap();
-        }
+        analyzer::visit_formats(registry, &mut |f| {
+            if Self::needs_helper(f) {
+                subtypes.insert(common::mangle_type(f), f.clone());
+            }
+        })
+        .unwrap();
         writeln!(self.out, "static class TraitHelpers {{")?;
         let reserved_names = &[];
         self.enter_class("TraitHelpers", reserved_names);
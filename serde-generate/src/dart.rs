@@ -2,13 +2,13 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    common,
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
 use heck::{CamelCase, MixedCase, SnakeCase};
 use include_dir::include_dir as include_directory;
-use serde_reflection::{ContainerFormat, Format, FormatHolder, Named, Registry, VariantFormat};
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
 use std::{
     collections::{BTreeMap, HashMap},
     io::{Result, Write},
@@ -48,6 +48,8 @@ impl<'a> CodeGenerator<'a> {
 
     /// Output class definitions for `registry`.
     pub fn output(&self, install_dir: std::path::PathBuf, registry: &Registry) -> Result<()> {
+        common::check_fully_resolved(registry)?;
+        common::check_variant_indices(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -361,16 +363,12 @@ where
 
     fn output_trait_helpers(&mut self, registry: &Registry) -> Result<()> {
         let mut subtypes = BTreeMap::new();
-        for format in registry.values() {
-            format
-                .visit(&mut |f| {
-                    if Self::needs_helper(f) {
-                        subtypes.insert(common::mangle_type(f), f.clone());
-                    }
-                    Ok(())
-                })
-                .unwrap();
-        }
+        analyzer::visit_formats(registry, &mut |f| {
+            if Self::needs_helper(f) {
+                subtypes.insert(common::mangle_type(f), f.clone());
+            }
+        })
+        .unwrap();
         writeln!(self.out, "class TraitHelpers {{")?;
         self.enter_class("TraitHelpers");
         for (mangled_name, subtype) in &subtypes {
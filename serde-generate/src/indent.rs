@@ -12,41 +12,38 @@ pub enum IndentConfig {
 pub struct IndentedWriter<T> {
     out: T,
     indentation: Vec<u8>,
-    config: IndentConfig,
+    unit: Vec<u8>,
+    newline: Vec<u8>,
     at_begining_of_line: bool,
 }
 
 impl<T> IndentedWriter<T> {
     pub fn new(out: T, config: IndentConfig) -> Self {
+        match config {
+            IndentConfig::Tab => Self::with_formatting(out, "\t", "\n"),
+            IndentConfig::Space(n) => Self::with_formatting(out, &" ".repeat(n), "\n"),
+        }
+    }
+
+    /// Use the given text for each level of indentation and write `newline` in place of
+    /// each `\n`.
+    pub fn with_formatting(out: T, indent: &str, newline: &str) -> Self {
         Self {
             out,
             indentation: Vec::new(),
-            config,
+            unit: indent.as_bytes().to_vec(),
+            newline: newline.as_bytes().to_vec(),
             at_begining_of_line: true,
         }
     }
 
     pub fn indent(&mut self) {
-        match self.config {
-            IndentConfig::Tab => {
-                self.indentation.push(b'\t');
-            }
-            IndentConfig::Space(n) => {
-                self.indentation.resize(self.indentation.len() + n, b' ');
-            }
-        }
+        self.indentation.extend_from_slice(&self.unit);
     }
 
     pub fn unindent(&mut self) {
-        match self.config {
-            IndentConfig::Tab => {
-                self.indentation.pop();
-            }
-            IndentConfig::Space(n) => {
-                self.indentation
-                    .truncate(self.indentation.len().saturating_sub(n));
-            }
-        }
+        self.indentation
+            .truncate(self.indentation.len().saturating_sub(self.unit.len()));
     }
 }
 
@@ -71,7 +68,7 @@ impl<T: Write> Write for IndentedWriter<T> {
             bytes_written += before_newline.len();
 
             if has_newline {
-                self.out.write_all(b"\n")?;
+                self.out.write_all(&self.newline)?;
                 bytes_written += 1;
                 self.at_begining_of_line = true;
             }
@@ -124,4 +121,21 @@ foo
 
         Ok(())
     }
+
+    #[test]
+    fn custom_formatting() -> Result<()> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        let mut out = IndentedWriter::with_formatting(&mut buffer, "\t", "\r\n");
+
+        writeln!(out, "foo")?;
+        out.indent();
+        writeln!(out, "bar\n")?;
+        out.unindent();
+        writeln!(out, "foo")?;
+
+        assert_eq!(buffer, b"foo\r\n\tbar\r\n\r\nfoo\r\n");
+
+        Ok(())
+    }
 }
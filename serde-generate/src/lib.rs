@@ -126,11 +126,16 @@
 //! cargo run -p serde-generate-bin -- --language python3 test.yaml > test.py
 //! ```
 //!
+//! Alternatively, the option `--output test.py` writes the file directly, while `--check test.py`
+//! verifies that an existing file is up to date (and prints a diff otherwise).
+//!
 //! To create a python module `test` and install the bincode runtime in a directory `$DEST`, you may run:
 //! ```bash
 //! cargo run -p serde-generate-bin -- --language python3 --with-runtimes serde bincode --module-name test --target-source-dir "$DEST" test.yaml
 //! ```
 //!
+//! The option `--install-dir` is an alias of `--target-source-dir`.
+//!
 //! See the help message of the tool with `--help` for more options.
 //!
 //! Note: Outside of this repository, you may install the tool with `cargo install serde-generate-bin` then use `$HOME/.cargo/bin/serdegen`.
@@ -2,7 +2,8 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    common::uppercase_first_letter,
+    analyzer,
+    common::{self, uppercase_first_letter},
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
@@ -44,6 +45,8 @@ impl<'a> CodeGenerator<'a> {
     }
 
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_fully_resolved(registry)?;
+        common::check_variant_indices(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -56,11 +59,30 @@ impl<'a> CodeGenerator<'a> {
             current_namespace,
         };
         emitter.output_preamble()?;
-        let n = registry.len();
-        for (i, (name, format)) in registry.iter().enumerate() {
-            let first = i == 0;
-            let last = i == n - 1;
-            emitter.output_container(name, format, first, last)?;
+        // Emit one group of (mutually recursive) type definitions per strongly connected
+        // component, in dependency order.
+        let dependencies = analyzer::get_dependency_map(registry)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error.to_string()))?;
+        let components = analyzer::get_strongly_connected_components(&dependencies)
+            .into_iter()
+            .map(|component| {
+                component
+                    .into_iter()
+                    .filter(|name| registry.contains_key(*name))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|component| !component.is_empty())
+            .collect::<Vec<_>>();
+        for (index, component) in components.iter().enumerate() {
+            if index > 0 {
+                writeln!(emitter.out)?;
+            }
+            let n = component.len();
+            for (i, name) in component.iter().enumerate() {
+                let first = i == 0;
+                let last = i == n - 1;
+                emitter.output_container(name, &registry[*name], first, last)?;
+            }
         }
         for (name, _) in registry.iter() {
             emitter.output_custom_code(name)?;
@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
-    CodeGeneratorConfig, Encoding,
+    CodeGeneratorConfig, DocComments, Encoding, ExternalDefinitions,
 };
+use heck::SnakeCase;
 use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
 use std::{
     collections::{BTreeMap, HashMap},
@@ -22,6 +24,41 @@ pub struct CodeGenerator<'a> {
     /// Assumes suitable imports (e.g. "from my_package import my_module").
     /// Derived from `config.external_definitions`.
     external_qualified_names: HashMap<String, String>,
+    /// Whether to postpone the evaluation of type annotations (PEP 563).
+    future_annotations: bool,
+    /// Whether to represent sequences as tuples so that generated classes are hashable.
+    hashable: bool,
+    /// Whether to emit fully annotated code for `mypy --strict`.
+    strict_typing: bool,
+    /// Whether optional fields, sequences, and maps have default values.
+    default_values: bool,
+    /// Whether enum variants are printed as `Enum.Variant(..)`.
+    variant_repr: bool,
+}
+
+/// Write container definitions in Python with the default options and return them as a string.
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_reflection::{Tracer, TracerConfig};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Test {
+///     a: u32,
+/// }
+///
+/// let mut tracer = Tracer::new(TracerConfig::default());
+/// tracer.trace_simple_type::<Test>().unwrap();
+/// let registry = tracer.registry().unwrap();
+///
+/// let config = serde_generate::CodeGeneratorConfig::new("testing".to_string());
+/// let source = serde_generate::python3::to_string(&registry, &config).unwrap();
+/// assert!(source.contains("class Test:\n    a: st.uint32\n"));
+/// ```
+pub fn to_string(registry: &Registry, config: &CodeGeneratorConfig) -> Result<String> {
+    let mut out = Vec::new();
+    CodeGenerator::new(config).output(&mut out, registry)?;
+    String::from_utf8(out)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
 }
 
 /// Shared state for the code generation of a Python source file.
@@ -32,14 +69,21 @@ struct PythonEmitter<'a, T> {
     generator: &'a CodeGenerator<'a>,
     /// Current namespace (e.g. vec!["my_package", "my_module", "MyClass"])
     current_namespace: Vec<String>,
+    /// Whether some field names are Python keywords and must be escaped.
+    escapes_field_names: bool,
 }
 
+/// Python keywords, which cannot be used as field names without escaping.
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
 impl<'a> CodeGenerator<'a> {
     /// Create a Python code generator for the given config.
     pub fn new(config: &'a CodeGeneratorConfig) -> Self {
-        if config.c_style_enums {
-            panic!("Python 3 does not support generating c-style enums");
-        }
         let mut external_qualified_names = HashMap::new();
         for (module_path, names) in &config.external_definitions {
             let module = {
@@ -58,6 +102,11 @@ impl<'a> CodeGenerator<'a> {
             config,
             serde_package_name: None,
             external_qualified_names,
+            future_annotations: false,
+            hashable: false,
+            strict_typing: false,
+            default_values: false,
+            variant_repr: false,
         }
     }
 
@@ -67,18 +116,101 @@ impl<'a> CodeGenerator<'a> {
         self
     }
 
+    /// Whether to emit `from __future__ import annotations` so that type annotations may refer
+    /// to classes defined later in the module without quotes.
+    pub fn with_future_annotations(mut self, future_annotations: bool) -> Self {
+        self.future_annotations = future_annotations;
+        self
+    }
+
+    /// Whether to represent sequences as variable-length tuples (`typing.Tuple[T, ...]`) instead
+    /// of lists. Generated dataclasses are always frozen, so this makes them hashable unless
+    /// they contain maps (Python dictionaries are never hashable).
+    pub fn with_hashable(mut self, hashable: bool) -> Self {
+        self.hashable = hashable;
+        self
+    }
+
+    /// Whether to emit code that passes `mypy --strict`. This implies `future_annotations`,
+    /// represents sequences as `typing.List` (unless `hashable` is set), and casts the values
+    /// returned by the deserialization runtimes to the expected class.
+    pub fn with_strict_typing(mut self, strict_typing: bool) -> Self {
+        self.strict_typing = strict_typing;
+        self
+    }
+
+    /// Whether to give default values to dataclass fields of optional, sequence, and map types
+    /// (respectively `None`, an empty sequence, and an empty dictionary). Python requires fields
+    /// with default values to come after the other ones: only the trailing fields of a class
+    /// receive default values, and the order of fields is never changed.
+    pub fn with_default_values(mut self, default_values: bool) -> Self {
+        self.default_values = default_values;
+        self
+    }
+
+    /// Whether to override the `__repr__` method of enum variants so that they print as
+    /// `Enum.Variant(..)` (e.g. `Shape.Circle(radius=1)`) instead of `Shape__Circle(radius=1)`,
+    /// similarly to the `Debug` output of Rust. Data of newtype and tuple variants are printed
+    /// as positional arguments.
+    pub fn with_variant_repr(mut self, variant_repr: bool) -> Self {
+        self.variant_repr = variant_repr;
+        self
+    }
+
+    /// Whether type annotations are postponed, so that they do not need quotes.
+    fn uses_future_annotations(&self) -> bool {
+        self.future_annotations || self.strict_typing
+    }
+
+    /// Reject variants of C-style enums that could not be told apart from escaped keywords
+    /// (e.g. `None_`).
+    fn check_c_style_variant_names(&self, registry: &Registry) -> Result<()> {
+        if !self.config.c_style_enums {
+            return Ok(());
+        }
+        for (name, format) in registry {
+            if let ContainerFormat::Enum(variants) = format {
+                if !variants.values().all(|v| v.value == VariantFormat::Unit) {
+                    continue;
+                }
+                for variant in variants.values() {
+                    let is_ambiguous = variant
+                        .name
+                        .strip_suffix('_')
+                        .map_or(false, |prefix| PYTHON_KEYWORDS.contains(&prefix));
+                    if is_ambiguous {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!(
+                                "Variant `{}` of C-style enum `{}` is reserved for escaping Python keywords",
+                                variant.name, name
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Write container definitions in Python.
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_fully_resolved(registry)?;
+        common::check_variant_indices(registry)?;
+        common::check_container_names(registry, "Python", is_python_identifier)?;
+        self.check_c_style_variant_names(registry)?;
         let current_namespace = self
             .config
             .module_name
             .split('.')
             .map(String::from)
             .collect();
+        let escapes_field_names = escapes_field_names(registry);
         let mut emitter = PythonEmitter {
             out: IndentedWriter::new(out, IndentConfig::Space(4)),
             generator: self,
             current_namespace,
+            escapes_field_names,
         };
         emitter.output_preamble()?;
         for (name, format) in registry {
@@ -86,6 +218,100 @@ impl<'a> CodeGenerator<'a> {
         }
         Ok(())
     }
+
+    /// Write each container definition in a separate Python module of a package. Return a map
+    /// from file names to contents, including an `__init__.py` file re-exporting all names.
+    fn output_container_files(&self, registry: &Registry) -> Result<BTreeMap<String, String>> {
+        common::check_fully_resolved(registry)?;
+        common::check_variant_indices(registry)?;
+        common::check_container_names(registry, "Python", is_python_identifier)?;
+        self.check_c_style_variant_names(registry)?;
+        let external_names = self.external_qualified_names.keys().cloned().collect();
+        let dependencies =
+            analyzer::get_dependency_map_with_external_dependencies(registry, &external_names)
+                .map_err(|error| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, error.to_string())
+                })?;
+        let mut module_names = BTreeMap::new();
+        let mut containers = BTreeMap::new();
+        for name in registry.keys() {
+            let module_name = quote_field_name(&name.to_snake_case());
+            if let Some(other) = containers.insert(module_name.clone(), name) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Containers `{}` and `{}` would be written to the same file `{}.py`",
+                        other, name, module_name
+                    ),
+                ));
+            }
+            module_names.insert(name.as_str(), module_name);
+        }
+        let current_namespace = self
+            .config
+            .module_name
+            .split('.')
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let escapes_field_names = escapes_field_names(registry);
+
+        let mut files = BTreeMap::new();
+        let mut init = "# pyre-strict\n".to_string();
+        let mut exported_names = Vec::new();
+        for name in analyzer::best_effort_topological_sort(&dependencies) {
+            let mut content = Vec::new();
+            {
+                let mut emitter = PythonEmitter {
+                    out: IndentedWriter::new(&mut content, IndentConfig::Space(4)),
+                    generator: self,
+                    current_namespace: current_namespace.clone(),
+                    escapes_field_names,
+                };
+                emitter.output_preamble()?;
+                emitter.output_container(name, &registry[name])?;
+                // Imported last to allow circular references between modules.
+                for dependency in &dependencies[name] {
+                    if *dependency != name && module_names.contains_key(dependency) {
+                        writeln!(
+                            emitter.out,
+                            "from .{} import {}",
+                            module_names[dependency], dependency
+                        )?;
+                    }
+                }
+            }
+            files.insert(
+                format!("{}.py", module_names[name]),
+                String::from_utf8_lossy(&content).to_string(),
+            );
+            init.push_str(&format!("from .{} import {}\n", module_names[name], name));
+            exported_names.push(format!("\"{}\"", name));
+        }
+        init.push_str(&format!("\n__all__ = [{}]\n", exported_names.join(", ")));
+        files.insert("__init__.py".to_string(), init);
+        Ok(files)
+    }
+}
+
+/// Whether some field names of the registry are Python keywords and must be escaped.
+fn escapes_field_names(registry: &Registry) -> bool {
+    registry.values().any(|format| {
+        let has_keyword = |fields: &[Named<Format>]| {
+            fields
+                .iter()
+                .any(|field| PYTHON_KEYWORDS.contains(&field.name.as_str()))
+        };
+        match format {
+            ContainerFormat::Struct(fields) => has_keyword(fields),
+            ContainerFormat::Enum(variants) => {
+                variants.values().any(|variant| match &variant.value {
+                    VariantFormat::Struct(fields) => has_keyword(fields),
+                    _ => false,
+                })
+            }
+            _ => false,
+        }
+    })
 }
 
 impl<'a, T> PythonEmitter<'a, T>
@@ -107,11 +333,21 @@ where
             None => "".to_string(),
             Some(name) => format!("from {} ", name),
         };
+        writeln!(self.out, "# pyre-strict")?;
+        if self.generator.uses_future_annotations() {
+            // Must come before any other import.
+            writeln!(self.out, "from __future__ import annotations")?;
+        }
+        writeln!(self.out, "from dataclasses import dataclass")?;
+        if self.generator.default_values || self.escapes_field_names {
+            writeln!(self.out, "import dataclasses")?;
+        }
+        if self.generator.config.c_style_enums {
+            writeln!(self.out, "import enum")?;
+        }
         writeln!(
             self.out,
-            r#"# pyre-strict
-from dataclasses import dataclass
-import typing
+            r#"import typing
 {}import serde_types as st"#,
             from_serde_package,
         )?;
@@ -132,8 +368,12 @@ import typing
             .get(name)
             .cloned()
             .unwrap_or_else(|| {
-                // Need quotes because of circular dependencies.
-                format!("\"{}\"", name)
+                if self.generator.uses_future_annotations() {
+                    name.to_string()
+                } else {
+                    // Need quotes because of circular dependencies.
+                    format!("\"{}\"", name)
+                }
             })
     }
 
@@ -160,7 +400,15 @@ import typing
             Bytes => "bytes".into(),
 
             Option(format) => format!("typing.Optional[{}]", self.quote_type(format)),
-            Seq(format) => format!("typing.Sequence[{}]", self.quote_type(format)),
+            Seq(format) => {
+                if self.generator.hashable {
+                    format!("typing.Tuple[{}, ...]", self.quote_type(format))
+                } else if self.generator.strict_typing {
+                    format!("typing.List[{}]", self.quote_type(format))
+                } else {
+                    format!("typing.Sequence[{}]", self.quote_type(format))
+                }
+            }
             Map { key, value } => format!(
                 "typing.Dict[{}, {}]",
                 self.quote_type(key),
@@ -219,17 +467,56 @@ import typing
             writeln!(self.out, "pass")?;
             return Ok(());
         }
-        for field in fields {
+        // Fields with default values must come last.
+        let num_required = if self.generator.default_values {
+            fields
+                .iter()
+                .rposition(|field| self.quote_default_value(&field.value).is_none())
+                .map_or(0, |position| position + 1)
+        } else {
+            fields.len()
+        };
+        for (index, field) in fields.iter().enumerate() {
+            let mut arguments = Vec::new();
+            if index >= num_required {
+                arguments.push(self.quote_default_value(&field.value).unwrap());
+            }
+            if PYTHON_KEYWORDS.contains(&field.name.as_str()) {
+                // Record the serialized name of the field for the runtimes.
+                arguments.push(format!("metadata={{\"serde_name\": \"{}\"}}", field.name));
+            }
+            let default = match arguments.as_slice() {
+                [] => String::new(),
+                [argument] if argument.starts_with("default=") => {
+                    format!(" = {}", &argument["default=".len()..])
+                }
+                _ => format!(" = dataclasses.field({})", arguments.join(", ")),
+            };
             writeln!(
                 self.out,
-                "{}: {}",
-                field.name,
-                self.quote_type(&field.value)
+                "{}: {}{}",
+                quote_field_name(&field.name),
+                self.quote_type(&field.value),
+                default
             )?;
+            // Attribute docstrings follow the attribute.
+            self.output_comment(&field.name)?;
         }
         Ok(())
     }
 
+    /// Quote the argument of `dataclasses.field` giving a default value to a field.
+    fn quote_default_value(&self, format: &Format) -> Option<String> {
+        use Format::*;
+        match format {
+            Option(_) => Some("default=None".into()),
+            Seq(_) if self.generator.hashable => Some("default=()".into()),
+            Seq(_) => Some("default_factory=list".into()),
+            Map { .. } => Some("default_factory=dict".into()),
+            _ => None,
+        }
+    }
+
     fn output_variant(
         &mut self,
         base: &str,
@@ -262,15 +549,52 @@ import typing
         self.output_comment(name)?;
         if self.generator.config.serialization {
             writeln!(self.out, "INDEX = {}  # type: int", index)?;
+            if let NewType(_) | Tuple(_) = variant {
+                writeln!(self.out, "NEWTYPE = True  # type: bool")?;
+            }
         }
         self.current_namespace.push(name.to_string());
         self.output_fields(&fields)?;
+        if self.generator.variant_repr {
+            self.output_variant_repr(base, name, variant)?;
+        }
         self.output_custom_code()?;
         self.current_namespace.pop();
         self.out.unindent();
         writeln!(self.out)
     }
 
+    fn output_variant_repr(
+        &mut self,
+        base: &str,
+        name: &str,
+        variant: &VariantFormat,
+    ) -> Result<()> {
+        use VariantFormat::*;
+        let arguments = match variant {
+            Unit => String::new(),
+            NewType(_) => "{self.value!r}".to_string(),
+            Tuple(formats) => (0..formats.len())
+                .map(|index| format!("{{self.value[{}]!r}}", index))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Struct(fields) => fields
+                .iter()
+                .map(|field| {
+                    let name = quote_field_name(&field.name);
+                    format!("{0}={{self.{0}!r}}", name)
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            Variable(_) => panic!("incorrect value"),
+        };
+        writeln!(self.out, "\ndef __repr__(self) -> str:")?;
+        self.out.indent();
+        writeln!(self.out, "return f\"{}.{}({})\"", base, name, arguments)?;
+        self.out.unindent();
+        Ok(())
+    }
+
     fn output_enum_container(
         &mut self,
         name: &str,
@@ -318,6 +642,32 @@ import typing
         Ok(())
     }
 
+    fn output_c_style_enum_container(
+        &mut self,
+        name: &str,
+        variants: &BTreeMap<u32, Named<VariantFormat>>,
+    ) -> Result<()> {
+        writeln!(self.out, "\nclass {}(enum.IntEnum):", name)?;
+        self.out.indent();
+        self.output_comment(name)?;
+        self.current_namespace.push(name.to_string());
+        for (index, variant) in variants {
+            self.output_comment(&variant.name)?;
+            // Variants named after keywords are escaped like fields (e.g. `None_`).
+            writeln!(self.out, "{} = {}", quote_field_name(&variant.name), index)?;
+        }
+        if self.generator.config.serialization {
+            for encoding in &self.generator.config.encodings {
+                self.output_serialize_method_for_encoding(name, *encoding)?;
+                self.output_deserialize_method_for_encoding(name, *encoding)?;
+            }
+        }
+        self.output_custom_code()?;
+        self.current_namespace.pop();
+        self.out.unindent();
+        writeln!(self.out)
+    }
+
     fn output_serialize_method_for_encoding(
         &mut self,
         name: &str,
@@ -338,6 +688,11 @@ def {0}_serialize(self) -> bytes:
         name: &str,
         encoding: Encoding,
     ) -> Result<()> {
+        let result = if self.generator.strict_typing {
+            format!("typing.cast({}, v)", name)
+        } else {
+            "v".to_string()
+        };
         writeln!(
             self.out,
             r#"
@@ -346,9 +701,10 @@ def {0}_deserialize(input: bytes) -> '{1}':
     v, buffer = {0}.deserialize(input, {1})
     if buffer:
         raise st.DeserializationError("Some input bytes were not read");
-    return v"#,
+    return {2}"#,
             encoding.name(),
-            name
+            name,
+            result
         )
     }
 
@@ -367,7 +723,13 @@ def {0}_deserialize(input: bytes) -> '{1}':
             Struct(fields) => fields.clone(),
             Enum(variants) => {
                 // Enum case.
-                self.output_enum_container(name, variants)?;
+                if self.generator.config.c_style_enums
+                    && variants.values().all(|v| v.value == VariantFormat::Unit)
+                {
+                    self.output_c_style_enum_container(name, variants)?;
+                } else {
+                    self.output_enum_container(name, variants)?;
+                }
                 return Ok(());
             }
         };
@@ -375,6 +737,12 @@ def {0}_deserialize(input: bytes) -> '{1}':
         writeln!(self.out, "\n@dataclass(frozen=True)\nclass {}:", name)?;
         self.out.indent();
         self.output_comment(name)?;
+        // Newtype and tuple structs are serialized as the content of their field `value`.
+        if self.generator.config.serialization
+            && matches!(format, NewTypeStruct(_) | TupleStruct(_))
+        {
+            writeln!(self.out, "NEWTYPE = True  # type: bool")?;
+        }
         self.current_namespace.push(name.to_string());
         self.output_fields(&fields)?;
         for encoding in &self.generator.config.encodings {
@@ -388,10 +756,41 @@ def {0}_deserialize(input: bytes) -> '{1}':
     }
 }
 
+/// Whether `name` can be used as a Python identifier.
+fn is_python_identifier(name: &str) -> bool {
+    common::is_identifier(name) && !PYTHON_KEYWORDS.contains(&name)
+}
+
+/// Escape field names that are Python keywords by appending an underscore.
+fn quote_field_name(name: &str) -> String {
+    if PYTHON_KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Write container definitions in Python for the given module, referring to the given
+/// external definitions (e.g. `from other import X`) and attaching docstrings to classes and
+/// fields. Other options take their default values.
+pub fn output_with_external_dependencies_and_comments(
+    out: &mut dyn Write,
+    registry: &Registry,
+    module_name: &str,
+    external_definitions: ExternalDefinitions,
+    comments: DocComments,
+) -> Result<()> {
+    let config = CodeGeneratorConfig::new(module_name.to_string())
+        .with_external_definitions(external_definitions)
+        .with_comments(comments);
+    CodeGenerator::new(&config).output(out, registry)
+}
+
 /// Installer for generated source files in Python.
 pub struct Installer {
     install_dir: PathBuf,
     serde_package_name: Option<String>,
+    file_per_container: bool,
 }
 
 impl Installer {
@@ -399,9 +798,19 @@ impl Installer {
         Installer {
             install_dir,
             serde_package_name,
+            file_per_container: false,
         }
     }
 
+    /// Whether to write each container definition to a separate module of the package (e.g.
+    /// `my_class.py` for `MyClass`). The `__init__.py` file of the package re-exports all
+    /// names in topological order. References to other containers use relative imports at
+    /// the end of each module, so that circular references remain possible.
+    pub fn with_file_per_container(mut self, file_per_container: bool) -> Self {
+        self.file_per_container = file_per_container;
+        self
+    }
+
     fn create_module_init_file(&self, name: &str) -> Result<std::fs::File> {
         let dir_path = self.install_dir.join(name);
         std::fs::create_dir_all(&dir_path)?;
@@ -432,9 +841,17 @@ impl crate::SourceInstaller for Installer {
         config: &crate::CodeGeneratorConfig,
         registry: &Registry,
     ) -> std::result::Result<(), Self::Error> {
-        let mut file = self.create_module_init_file(&config.module_name)?;
         let generator =
             CodeGenerator::new(config).with_serde_package_name(self.serde_package_name.clone());
+        if self.file_per_container {
+            let dir_path = self.install_dir.join(&config.module_name);
+            std::fs::create_dir_all(&dir_path)?;
+            for (file_name, content) in generator.output_container_files(registry)? {
+                std::fs::write(dir_path.join(file_name), content)?;
+            }
+            return Ok(());
+        }
+        let mut file = self.create_module_init_file(&config.module_name)?;
         generator.output(&mut file, registry)?;
         Ok(())
     }
@@ -452,6 +869,12 @@ impl crate::SourceInstaller for Installer {
             "{}",
             self.fix_serde_package(include_str!("../runtime/python/serde_binary/__init__.py"))
         )?;
+        let mut file = self.create_module_init_file("serde_json")?;
+        write!(
+            file,
+            "{}",
+            self.fix_serde_package(include_str!("../runtime/python/serde_json/__init__.py"))
+        )?;
         Ok(())
     }
 
@@ -2,16 +2,18 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
-    analyzer,
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
-    CodeGeneratorConfig,
+    CodeGeneratorConfig, Encoding,
 };
-use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
+use heck::{ShoutySnakeCase, SnakeCase};
+use serde_reflection::{ContainerFormat, Format, FormatHolder, Named, Registry, VariantFormat};
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     io::{Result, Write},
     path::PathBuf,
+    process::{Command, Stdio},
 };
 
 /// Main configuration object for code-generation in Rust.
@@ -22,8 +24,310 @@ pub struct CodeGenerator<'a> {
     derive_macros: Vec<String>,
     /// Additional block of text added before each new container definition.
     custom_derive_block: Option<String>,
-    /// Whether definitions and fields should be marked as `pub`.
-    track_visibility: bool,
+    /// Additional attributes of particular containers, indexed by container name.
+    container_attributes: BTreeMap<String, Vec<String>>,
+    /// Visibility of definitions and fields.
+    visibility: Visibility,
+    /// How to represent `Format::Map` in Rust.
+    map_representation: MapRepresentation,
+    /// How to represent `Format::Bytes` in Rust.
+    bytes_representation: BytesRepresentation,
+    /// How to represent `Format::Seq` in Rust.
+    seq_representation: SeqRepresentation,
+    /// Whether the generated code should only depend on `core` and `alloc`.
+    no_std: bool,
+    /// Whether enums should be marked as `#[non_exhaustive]`.
+    non_exhaustive_enums: bool,
+    /// Whether structs should be marked as `#[non_exhaustive]`.
+    non_exhaustive_structs: bool,
+    /// Whether enums without variant data should have explicit discriminants.
+    explicit_discriminants: bool,
+    /// Whether enums without variant data should be marked as `#[repr(u32)]`.
+    c_repr_enums: bool,
+    /// Whether optional fields should be skipped during serialization when empty.
+    skip_none_fields: bool,
+    /// Whether to implement `Default` when possible.
+    derive_default: bool,
+    /// Optional `#[serde(rename_all = "..")]` rule applied to all containers.
+    rename_all: Option<String>,
+    /// Optional path of the Serde crate, when it is not available as `serde`.
+    serde_crate_path: Option<String>,
+    /// Custom Rust types used in place of the default ones for primitive formats.
+    type_overrides: Vec<(Format, String)>,
+    /// Size above which array fields are serialized with `serde_big_array`.
+    array_impl_threshold: Option<usize>,
+    /// Custom `#[serde(with = "..")]` modules for particular fields.
+    serde_with_overrides: BTreeMap<Vec<String>, String>,
+    /// Serialized names of particular variants.
+    variant_renames: BTreeMap<Vec<String>, String>,
+    /// Custom `#[serde(default = "..")]` functions for particular fields.
+    field_defaults: BTreeMap<Vec<String>, String>,
+    /// Additional attributes of particular fields.
+    field_attributes: BTreeMap<Vec<String>, Vec<String>>,
+    /// Map fields marked with `#[serde(flatten)]`.
+    flattened_fields: BTreeSet<Vec<String>>,
+    /// Whether to sort struct fields and enum variants by name.
+    sort_members: bool,
+    /// Whether to generate conversions from and to `u32` for enums without variant data.
+    numeric_enum_conversions: bool,
+    /// Whether to implement `Display` for enums without variant data.
+    display_for_unit_enums: bool,
+    /// Whether to generate a module of constants with the variant indices of each enum.
+    emit_variant_tags: bool,
+    /// Whether to generate getters for the fields of structs when fields are not public.
+    generate_accessors: bool,
+    /// Local names of external definitions.
+    external_aliases: BTreeMap<String, String>,
+    /// Enums that should implement `Display` and `Error`.
+    error_impls_for: BTreeSet<String>,
+    /// Whether to serialize newtype structs as their inner value.
+    transparent_newtypes: bool,
+    /// Whether to represent `Seq(U8)` as `Bytes`.
+    bytes_as_seq_u8: bool,
+    /// Whether to compare containers with floating-point numbers using a total order.
+    total_float_eq: bool,
+    /// Whether to implement conversions into `serde_json::Value`.
+    json_value_conversions: bool,
+    /// Whether to derive `arbitrary::Arbitrary`.
+    derive_arbitrary: bool,
+    /// Whether to generate `proptest` strategies.
+    proptest_strategies: bool,
+    /// Optional name of a module wrapping all the generated definitions.
+    wrapper_module: Option<String>,
+    /// Lints allowed in the generated code.
+    lint_allows: Vec<String>,
+    /// Optional Cargo feature required to compile the generated definitions.
+    cfg_feature: Option<String>,
+    /// Whether to borrow strings and bytes from the input when deserializing.
+    zero_copy: bool,
+    /// How Serde should represent enums.
+    enum_representation: EnumRepresentation,
+    /// Indentation and line endings.
+    formatting: Formatting,
+    /// Whether to format the generated code with `rustfmt`.
+    run_rustfmt: bool,
+}
+
+/// Renaming rules accepted by `#[serde(rename_all = "..")]`.
+const RENAME_ALL_RULES: &[&str] = &[
+    "lowercase",
+    "UPPERCASE",
+    "PascalCase",
+    "camelCase",
+    "snake_case",
+    "SCREAMING_SNAKE_CASE",
+    "kebab-case",
+    "SCREAMING-KEBAB-CASE",
+];
+
+/// Rust keywords, which cannot be used as field names without escaping.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// Definitions of a Rust module, including nested modules.
+#[derive(Default)]
+struct ModuleDefinitions {
+    definitions: Vec<String>,
+    submodules: BTreeMap<String, ModuleDefinitions>,
+}
+
+/// How to represent `Format::Map` in the generated Rust code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapRepresentation {
+    /// Use `std::collections::BTreeMap` (default).
+    BTreeMap,
+    /// Use `std::collections::HashMap`. Since `HashMap` does not implement `PartialOrd`,
+    /// `Ord`, or `Hash`, these traits are removed from the derive macros.
+    HashMap,
+    /// Use the given map type (e.g. `indexmap::IndexMap`) in place of `Map`. No alias is
+    /// emitted: the type must be in scope, e.g. thanks to external definitions.
+    Custom(String),
+}
+
+/// How to represent `Format::Seq` in the generated Rust code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SeqRepresentation {
+    /// Use `Vec` (default).
+    Vec,
+    /// Use `std::collections::VecDeque`.
+    VecDeque,
+    /// Use the given generic type with a single parameter (e.g. `std::collections::LinkedList`)
+    /// in place of `Vec`. No import is emitted: the type must be in scope, e.g. thanks to
+    /// external definitions. The type must implement `Serialize` and `Deserialize` as a
+    /// sequence of its elements (like `Vec`) for the wire format to be preserved. Since
+    /// recursive definitions through sequences are never boxed, the type must also store its
+    /// elements on the heap rather than inline.
+    Custom(String),
+}
+
+/// How to represent `Format::Bytes` in the generated Rust code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BytesRepresentation {
+    /// Use `serde_bytes::ByteBuf` (default). Since it requires Serde and `std`, `Vec<u8>` is
+    /// used instead when serialization is disabled or in `no_std` mode.
+    ByteBuf,
+    /// Use `Vec<u8>`. Serde then treats bytes as sequences of integers. (For the Bincode and
+    /// BCS encodings, this does not change the wire format.)
+    VecU8,
+    /// Use the given type (e.g. `bytes::Bytes`) in place of `Bytes`. No alias is emitted: the
+    /// type must be in scope, e.g. thanks to external definitions.
+    Custom { import: String },
+}
+
+/// How Serde should represent enums in the generated Rust code. Representations other than the
+/// default require a self-describing format such as JSON, hence are not available for the
+/// Bincode and BCS encodings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EnumRepresentation {
+    /// Externally tagged enums (default), e.g. `{"Variant": {..}}`.
+    External,
+    /// Adjacently tagged enums: `#[serde(tag = "..", content = "..")]`.
+    Adjacent { tag: String, content: String },
+    /// Internally tagged enums: `#[serde(tag = "..")]`. Only available for enums made of
+    /// struct and unit variants.
+    Internal { tag: String },
+    /// Untagged enums: `#[serde(untagged)]`. Variants are tried in order, so no variant may
+    /// accept the values of a later variant (e.g. `A { x: u32 }` before `B { x: u32, y: u32 }`,
+    /// since unknown fields are ignored).
+    Untagged,
+}
+
+/// Indentation and line endings of the generated code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Formatting {
+    /// Text of one level of indentation (default: 4 spaces).
+    pub indent: String,
+    /// Line ending (default: `\n`).
+    pub newline: String,
+}
+
+impl Default for Formatting {
+    fn default() -> Self {
+        Self {
+            indent: "    ".to_string(),
+            newline: "\n".to_string(),
+        }
+    }
+}
+
+/// Visibility of the generated definitions and fields.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// Use `pub` (default).
+    Public,
+    /// Use `pub(crate)`.
+    Crate,
+    /// Do not add any visibility keyword.
+    Private,
+}
+
+impl Visibility {
+    /// The keyword to be written before an item, including a trailing space if needed.
+    fn prefix(self) -> &'static str {
+        match self {
+            Visibility::Public => "pub ",
+            Visibility::Crate => "pub(crate) ",
+            Visibility::Private => "",
+        }
+    }
+}
+
+/// Render a format as a Rust type with the default options, as in the generated code.
+/// When `known_sizes` is provided, type names that are not in the set are boxed. Fails if the
+/// format contains variables.
+/// ```
+/// use serde_reflection::Format;
+/// use std::collections::HashSet;
+///
+/// let format = Format::Seq(Box::new(Format::U64));
+/// assert_eq!(serde_generate::rust::format_to_rust(&format, None).unwrap(), "Vec<u64>");
+///
+/// let format = Format::Option(Box::new(Format::TypeName("Tree".to_string())));
+/// assert_eq!(serde_generate::rust::format_to_rust(&format, None).unwrap(), "Option<Tree>");
+/// assert_eq!(
+///     serde_generate::rust::format_to_rust(&format, Some(&HashSet::new())).unwrap(),
+///     "Option<Box<Tree>>"
+/// );
+/// ```
+pub fn format_to_rust(
+    format: &Format,
+    known_sizes: Option<&HashSet<&str>>,
+) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    let config = CodeGeneratorConfig::new(String::new());
+    CodeGenerator::new(&config).quote_type(format, known_sizes)
+}
+
+/// Write container definitions in Rust with the default options and return them as a string.
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_reflection::{Tracer, TracerConfig};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Test {
+///     a: u32,
+/// }
+///
+/// let mut tracer = Tracer::new(TracerConfig::default());
+/// tracer.trace_simple_type::<Test>().unwrap();
+/// let registry = tracer.registry().unwrap();
+///
+/// let config = serde_generate::CodeGeneratorConfig::new("testing".to_string());
+/// let source = serde_generate::rust::to_string(&registry, &config).unwrap();
+/// assert!(source.contains("pub struct Test {\n    pub a: u32,\n}"));
+/// ```
+pub fn to_string(
+    registry: &Registry,
+    config: &CodeGeneratorConfig,
+) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    let mut out = Vec::new();
+    CodeGenerator::new(config).output(&mut out, registry)?;
+    Ok(String::from_utf8(out)?)
+}
+
+/// Generate the imports and type aliases written at the top of the Rust code, with the
+/// default options. See `container` for an example.
+pub fn preamble(config: &CodeGeneratorConfig) -> String {
+    CodeGenerator::new(config).quote_preamble()
+}
+
+/// Generate the Rust definition of a single container with the default options. Type names
+/// that are not in `known_sizes` are boxed. Fails if the container contains variables.
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use serde_reflection::{Tracer, TracerConfig};
+/// use std::collections::HashSet;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Test {
+///     a: Vec<u8>,
+/// }
+///
+/// let mut tracer = Tracer::new(TracerConfig::default());
+/// tracer.trace_simple_type::<Test>().unwrap();
+/// let registry = tracer.registry().unwrap();
+///
+/// let config = serde_generate::CodeGeneratorConfig::new("testing".to_string());
+/// let mut source = serde_generate::rust::preamble(&config);
+/// source.push_str("// Hand-written code.\n\n");
+/// source.push_str(
+///     &serde_generate::rust::container("Test", &registry["Test"], &HashSet::new(), &config)
+///         .unwrap(),
+/// );
+/// assert!(source.contains("use serde::{Serialize, Deserialize};\n"));
+/// assert!(source.ends_with("pub struct Test {\n    pub a: Vec<u8>,\n}\n"));
+/// ```
+pub fn container(
+    name: &str,
+    format: &ContainerFormat,
+    known_sizes: &HashSet<&str>,
+    config: &CodeGeneratorConfig,
+) -> std::result::Result<String, Box<dyn std::error::Error>> {
+    CodeGenerator::new(config).quote_container(name, format, known_sizes)
 }
 
 /// Shared state for the code generation of a Rust source file.
@@ -36,6 +340,17 @@ struct RustEmitter<'a, T> {
     known_sizes: Cow<'a, HashSet<&'a str>>,
     /// Current namespace (e.g. vec!["my_package", "my_module", "MyClass"])
     current_namespace: Vec<String>,
+    /// Nested Rust module of the current container (e.g. vec!["proto", "messages"])
+    current_module: Vec<String>,
+    /// Track which definitions can implement `Default`.
+    defaultable: &'a HashSet<&'a str>,
+    /// Containers that transitively contain floats, when `total_float_eq` is enabled.
+    float_containers: &'a BTreeSet<&'a str>,
+    /// Containers whose size depends on the current container (and conversely), hence may
+    /// need to be boxed.
+    recursive_names: &'a HashSet<&'a str>,
+    /// Containers with a lifetime parameter, when `zero_copy` is enabled.
+    borrowing_containers: &'a BTreeSet<&'a str>,
 }
 
 impl<'a> CodeGenerator<'a> {
@@ -48,11 +363,53 @@ impl<'a> CodeGenerator<'a> {
                 .map(String::from)
                 .collect(),
             custom_derive_block: None,
-            track_visibility: true,
+            container_attributes: BTreeMap::new(),
+            visibility: Visibility::Public,
+            map_representation: MapRepresentation::BTreeMap,
+            bytes_representation: BytesRepresentation::ByteBuf,
+            seq_representation: SeqRepresentation::Vec,
+            no_std: false,
+            non_exhaustive_enums: false,
+            non_exhaustive_structs: false,
+            explicit_discriminants: false,
+            c_repr_enums: false,
+            skip_none_fields: false,
+            derive_default: false,
+            rename_all: None,
+            serde_crate_path: None,
+            type_overrides: Vec::new(),
+            array_impl_threshold: None,
+            serde_with_overrides: BTreeMap::new(),
+            variant_renames: BTreeMap::new(),
+            field_defaults: BTreeMap::new(),
+            field_attributes: BTreeMap::new(),
+            flattened_fields: BTreeSet::new(),
+            sort_members: false,
+            numeric_enum_conversions: false,
+            display_for_unit_enums: false,
+            emit_variant_tags: false,
+            generate_accessors: false,
+            external_aliases: BTreeMap::new(),
+            error_impls_for: BTreeSet::new(),
+            transparent_newtypes: false,
+            bytes_as_seq_u8: false,
+            total_float_eq: false,
+            json_value_conversions: false,
+            derive_arbitrary: false,
+            proptest_strategies: false,
+            wrapper_module: None,
+            lint_allows: vec!["unused_imports".to_string()],
+            cfg_feature: None,
+            zero_copy: false,
+            enum_representation: EnumRepresentation::External,
+            formatting: Formatting::default(),
+            run_rustfmt: false,
         }
     }
 
     /// Which derive macros should be added (independently from serialization).
+    /// When serialization is enabled, `Serialize` and `Deserialize` always come first and
+    /// duplicates are ignored.
     pub fn with_derive_macros(mut self, derive_macros: Vec<String>) -> Self {
         self.derive_macros = derive_macros;
         self
@@ -65,9 +422,371 @@ impl<'a> CodeGenerator<'a> {
         self
     }
 
+    /// Additional attributes (e.g. `#[repr(C)]`) of particular containers, indexed by
+    /// container name. Attributes are written in the given order, after the doc comments and
+    /// before the derive macros.
+    pub fn with_container_attributes(
+        mut self,
+        container_attributes: BTreeMap<String, Vec<String>>,
+    ) -> Self {
+        self.container_attributes = container_attributes;
+        self
+    }
+
     /// Whether definitions and fields should be marked as `pub`.
     pub fn with_track_visibility(mut self, track_visibility: bool) -> Self {
-        self.track_visibility = track_visibility;
+        self.visibility = if track_visibility {
+            Visibility::Public
+        } else {
+            Visibility::Private
+        };
+        self
+    }
+
+    /// Visibility of definitions and fields.
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Whether to generate a public getter `fn x(&self) -> &X` for each field of structs with
+    /// named fields, when the visibility of fields is not `Public`. Enums and tuple structs are
+    /// left unchanged.
+    pub fn with_generate_accessors(mut self, generate_accessors: bool) -> Self {
+        self.generate_accessors = generate_accessors;
+        self
+    }
+
+    /// Local names (aliases) of external definitions, e.g. `Uuid` => `UserId` to import
+    /// `use uuid::{Uuid as UserId};` and refer to `Format::TypeName("Uuid")` as `UserId`. Only
+    /// names imported from a (non-empty) module can be renamed.
+    pub fn with_external_aliases(mut self, external_aliases: BTreeMap<String, String>) -> Self {
+        self.external_aliases = external_aliases;
+        self
+    }
+
+    /// How to represent `Format::Map` in Rust. When the BCS encoding is selected in the
+    /// configuration, `HashMap` is replaced by `BTreeMap` so that maps have a canonical order.
+    pub fn with_map_representation(mut self, map_representation: MapRepresentation) -> Self {
+        self.map_representation = map_representation;
+        self
+    }
+
+    /// How to represent `Format::Seq` in Rust. (`Seq(U8)` still uses `Bytes` when
+    /// `bytes_as_seq_u8` is enabled.)
+    pub fn with_seq_representation(mut self, seq_representation: SeqRepresentation) -> Self {
+        self.seq_representation = seq_representation;
+        self
+    }
+
+    /// How to represent `Format::Bytes` in Rust. Unless a custom type is given, the generated
+    /// code defines `Bytes` as an alias (or an import) for the selected type.
+    pub fn with_bytes_representation(mut self, bytes_representation: BytesRepresentation) -> Self {
+        self.bytes_representation = bytes_representation;
+        self
+    }
+
+    /// Whether the generated code should only depend on `core` and `alloc` (i.e. `#![no_std]`).
+    /// In this mode, `Bytes` is always an alias for `Vec<u8>` because `serde_bytes::ByteBuf`
+    /// requires `std`. (For the Bincode and BCS encodings, this does not change the wire format.)
+    pub fn with_no_std(mut self, no_std: bool) -> Self {
+        self.no_std = no_std;
+        self
+    }
+
+    /// Whether enums should be marked as `#[non_exhaustive]`.
+    pub fn with_non_exhaustive_enums(mut self, non_exhaustive_enums: bool) -> Self {
+        self.non_exhaustive_enums = non_exhaustive_enums;
+        self
+    }
+
+    /// Whether structs should be marked as `#[non_exhaustive]`.
+    pub fn with_non_exhaustive_structs(mut self, non_exhaustive_structs: bool) -> Self {
+        self.non_exhaustive_structs = non_exhaustive_structs;
+        self
+    }
+
+    /// Whether enums without variant data should have explicit discriminants matching the
+    /// variant indices used by Serde (e.g. `A = 0,`). Enums with variant data are not affected.
+    pub fn with_explicit_discriminants(mut self, explicit_discriminants: bool) -> Self {
+        self.explicit_discriminants = explicit_discriminants;
+        self
+    }
+
+    /// Whether enums without variant data should be marked as `#[repr(u32)]` and use explicit
+    /// discriminants matching the variant indices used by Serde, so that they can be shared
+    /// with C code. Enums with variant data (or without variants) are not affected.
+    pub fn with_c_repr_enums(mut self, c_repr_enums: bool) -> Self {
+        self.c_repr_enums = c_repr_enums;
+        self
+    }
+
+    /// Whether optional fields should be marked with
+    /// `#[serde(skip_serializing_if = "Option::is_none", default)]`.
+    /// This is only meant for self-describing encodings (e.g. JSON): Bincode and BCS
+    /// require every field to be present on the wire.
+    pub fn with_skip_none_fields(mut self, skip_none_fields: bool) -> Self {
+        self.skip_none_fields = skip_none_fields;
+        self
+    }
+
+    /// Whether to implement `Default` for the containers where all fields have a default value.
+    /// Structs use `#[derive(Default)]`. Enums whose first variant is without data get a manual
+    /// implementation returning this variant. Fields of overridden types (see
+    /// `with_type_overrides`) are not assumed to have a default value.
+    pub fn with_derive_default(mut self, derive_default: bool) -> Self {
+        self.derive_default = derive_default;
+        self
+    }
+
+    /// Add `#[serde(rename_all = "..")]` to all containers when serialization is enabled.
+    /// The rule must be one of the values supported by Serde, e.g. "camelCase" or "kebab-case".
+    pub fn with_rename_all(mut self, rename_all: Option<String>) -> Self {
+        self.rename_all = rename_all;
+        self
+    }
+
+    /// Path of the Serde crate (e.g. "my_serde" or "my_crate::serde") when Serde is vendored or
+    /// re-exported under another name. When serialization is enabled, `Serialize` and
+    /// `Deserialize` are then derived from this path without being imported, and all
+    /// containers are annotated with `#[serde(crate = "..")]`.
+    pub fn with_serde_crate_path(mut self, serde_crate_path: Option<String>) -> Self {
+        self.serde_crate_path = serde_crate_path;
+        self
+    }
+
+    /// Use custom Rust types for some primitive formats, e.g. `(Format::U128, "my_crate::U128")`.
+    /// Only primitive formats (including `Str` and `Bytes`) can be overridden.
+    pub fn with_type_overrides(mut self, type_overrides: Vec<(Format, String)>) -> Self {
+        self.type_overrides = type_overrides;
+        self
+    }
+
+    /// Serialize array fields larger than the given threshold with
+    /// `#[serde(with = "serde_big_array::BigArray")]`.
+    /// Serde only implements `Serialize` and `Deserialize` for arrays of up to 32 elements,
+    /// whereas derived traits such as `Debug` and `PartialEq` support arrays of any size since
+    /// Rust 1.47. Generated code then depends on the `serde-big-array` crate, which the `Installer`
+    /// adds to `Cargo.toml`. Only the arrays used directly as the type of a named field are
+    /// supported. This is typically needed for byte arrays such as hashes and signatures.
+    pub fn with_array_impl_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.array_impl_threshold = threshold;
+        self
+    }
+
+    /// Add `#[serde(with = "..")]` to particular fields. Fields are identified by qualified
+    /// names, using the same scheme as doc comments (e.g. `["my_module", "Event", "at"]`).
+    pub fn with_serde_with_overrides(
+        mut self,
+        serde_with_overrides: BTreeMap<Vec<String>, String>,
+    ) -> Self {
+        self.serde_with_overrides = serde_with_overrides;
+        self
+    }
+
+    /// Add `#[serde(rename = "..")]` to particular variants when serialization is enabled, so
+    /// that they are serialized under another name (e.g. a short tag) while keeping a readable
+    /// name in Rust. Variants are identified by qualified names, as above (e.g.
+    /// `["my_module", "Event", "Started"]`). This only matters for encodings that serialize
+    /// variant names, such as JSON: Bincode and BCS use variant indices.
+    pub fn with_variant_renames(mut self, variant_renames: BTreeMap<Vec<String>, String>) -> Self {
+        self.variant_renames = variant_renames;
+        self
+    }
+
+    /// Add `#[serde(default = "..")]` to particular fields, so that they are deserialized with
+    /// the value returned by the given function when missing. Fields are identified by
+    /// qualified names, as above. The functions (e.g. `my_crate::default_port`) are not
+    /// generated and must be provided by the user.
+    pub fn with_field_defaults(mut self, field_defaults: BTreeMap<Vec<String>, String>) -> Self {
+        self.field_defaults = field_defaults;
+        self
+    }
+
+    /// Additional attributes (e.g. `#[serde(default)]`) of particular fields, including fields of
+    /// struct variants. Fields are identified by qualified names, as above. Attributes are
+    /// written in the given order, after the doc comments.
+    pub fn with_field_attributes(
+        mut self,
+        field_attributes: BTreeMap<Vec<String>, Vec<String>>,
+    ) -> Self {
+        self.field_attributes = field_attributes;
+        self
+    }
+
+    /// Add `#[serde(flatten)]` to particular map fields so that they absorb unknown keys during
+    /// deserialization. Fields are identified by qualified names, as above. This requires
+    /// serialization to be enabled and allows at most one flattened field per struct. Note that
+    /// flattened fields are only supported by self-describing formats (e.g. not Bincode).
+    pub fn with_flattened_fields(mut self, flattened_fields: BTreeSet<Vec<String>>) -> Self {
+        self.flattened_fields = flattened_fields;
+        self
+    }
+
+    /// Whether to sort struct fields (including fields of struct variants) and enum variants by
+    /// name, e.g. for review-friendly diffs. Serde encodes fields and variants according to
+    /// their order of declaration, regardless of explicit discriminants. Therefore, when
+    /// serialization is enabled, code generation fails if sorting would change the order of
+    /// any fields or variants. Otherwise, reordering the variants of enums without variant data
+    /// requires explicit discriminants so that the numeric value of each variant is preserved.
+    pub fn with_sort_members(mut self, sort_members: bool) -> Self {
+        self.sort_members = sort_members;
+        self
+    }
+
+    /// Whether to implement `TryFrom<u32>` and `From<&Enum> for u32` for enums whose variants
+    /// are all without data. Numbers are the variant indices used by the serialization formats.
+    /// Failed conversions return the error `()`.
+    pub fn with_numeric_enum_conversions(mut self, numeric_enum_conversions: bool) -> Self {
+        self.numeric_enum_conversions = numeric_enum_conversions;
+        self
+    }
+
+    /// Whether to implement `core::fmt::Display` for enums whose variants are all without data.
+    /// Each variant is printed as its serialized name when serialization is enabled, that is,
+    /// its explicit rename (see `with_variant_renames`) or else its name after applying the
+    /// `rename_all` rule (if any).
+    pub fn with_display_for_unit_enums(mut self, display_for_unit_enums: bool) -> Self {
+        self.display_for_unit_enums = display_for_unit_enums;
+        self
+    }
+
+    /// Whether to generate, after each enum `MyEnum`, a module `my_enum_tags` with one constant
+    /// `u32` per variant (e.g. `MY_VARIANT`) holding the index of the variant, that is, the
+    /// discriminant used by the Bincode and BCS encodings.
+    pub fn with_emit_variant_tags(mut self, emit_variant_tags: bool) -> Self {
+        self.emit_variant_tags = emit_variant_tags;
+        self
+    }
+
+    /// Enums (given by their qualified names) that should implement `core::fmt::Display` and
+    /// `std::error::Error` (not available in `no_std` mode). Values are displayed as
+    /// their variant name, as with `with_display_for_unit_enums`. The `source()` of a variant is
+    /// its first member whose type is also listed here, if any. Requires deriving `Debug`.
+    pub fn with_error_impls_for(mut self, error_impls_for: BTreeSet<String>) -> Self {
+        self.error_impls_for = error_impls_for;
+        self
+    }
+
+    /// Whether to implement `PartialEq` and `PartialOrd` manually (instead of deriving them) for
+    /// containers that transitively contain `f32` or `f64` values. Floating-point numbers are
+    /// then compared according to the IEEE 754 total order (as with `total_cmp`): `NaN` is
+    /// equal to itself and `-0.0` is strictly less than `0.0`, unlike with the derived
+    /// implementations. Fields are compared in order of declaration and variants by index.
+    /// Values without floats are compared with their own `PartialOrd` implementation.
+    /// This requires `PartialEq` and `PartialOrd` to be among the derive macros and maps to be
+    /// represented by `BTreeMap`.
+    pub fn with_total_float_eq(mut self, total_float_eq: bool) -> Self {
+        self.total_float_eq = total_float_eq;
+        self
+    }
+
+    /// Whether to implement `From<&T> for serde_json::Value` for each container `T`. The
+    /// conversion calls `serde_json::to_value`, hence relies on the derived `Serialize`
+    /// implementation and requires serialization to be enabled. It panics if a map has keys
+    /// that JSON cannot represent (e.g. structs). The generated crate must depend on `serde_json`.
+    pub fn with_json_value_conversions(mut self, json_value_conversions: bool) -> Self {
+        self.json_value_conversions = json_value_conversions;
+        self
+    }
+
+    /// Whether to derive `arbitrary::Arbitrary` (e.g. for fuzzing) in addition to the derive
+    /// macros above. The generated crate must depend on `arbitrary` with the `derive` feature.
+    /// This is not available in `no_std` mode, and `Bytes` values require a representation
+    /// other than `serde_bytes::ByteBuf` (see `with_bytes_representation`).
+    pub fn with_derive_arbitrary(mut self, derive_arbitrary: bool) -> Self {
+        self.derive_arbitrary = derive_arbitrary;
+        self
+    }
+
+    /// Whether to generate a function `{name}_strategy() -> BoxedStrategy<Name>` returning a
+    /// `proptest` strategy for each container `Name` (with `name` in snake case), e.g. to test
+    /// that values round-trip through an encoding. Sequences and maps have fewer than 3 elements.
+    /// References to other containers are resolved lazily so that recursive containers are
+    /// supported, although values of recursive containers are not bounded in depth.
+    /// Overridden types must implement `proptest::arbitrary::Arbitrary`. This requires `Debug`
+    /// to be among the derive macros and is not available in `no_std` mode. The generated crate
+    /// must depend on `proptest`.
+    pub fn with_proptest_strategies(mut self, proptest_strategies: bool) -> Self {
+        self.proptest_strategies = proptest_strategies;
+        self
+    }
+
+    /// Whether to add `#[serde(transparent)]` to newtype structs (and tuple structs with a
+    /// single field) when serialization is enabled.
+    pub fn with_transparent_newtypes(mut self, transparent_newtypes: bool) -> Self {
+        self.transparent_newtypes = transparent_newtypes;
+        self
+    }
+
+    /// Whether to represent `Seq(U8)` as `Bytes`, the same as `Format::Bytes`. When
+    /// serialization is enabled, named fields of format `Seq(U8)` are also marked with
+    /// `#[serde(with = "serde_bytes")]` (unless a custom `with` module was given above).
+    ///
+    /// This changes the Serde data model of such values from a sequence of `u8` to a byte
+    /// string. Bincode and BCS encode both in the same way, so the wire format is unchanged
+    /// and only (de)serialization gets faster. Self-describing formats (e.g. CBOR, MessagePack)
+    /// encode byte strings differently: messages are not compatible with implementations that
+    /// still use `Seq(U8)`. Tracing the generated types also yields `Bytes` instead of `Seq(U8)`.
+    pub fn with_bytes_as_seq_u8(mut self, bytes_as_seq_u8: bool) -> Self {
+        self.bytes_as_seq_u8 = bytes_as_seq_u8;
+        self
+    }
+
+    /// Optional name of a module `pub mod <name> { .. }` wrapping all the generated definitions
+    /// and their `use` statements. This is convenient to `include!` the generated code in a
+    /// larger crate. Crate-level items required by `no_std` mode are kept outside the module.
+    pub fn with_wrapper_module(mut self, wrapper_module: Option<String>) -> Self {
+        self.wrapper_module = wrapper_module;
+        self
+    }
+
+    /// Lints allowed by an inner attribute `#![allow(..)]` at the beginning of the generated
+    /// code (default: `unused_imports`). When a wrapper module is used, the attribute is scoped
+    /// inside this module. An empty list omits the attribute, which is needed when the generated
+    /// file is included with `include!` without a wrapper module.
+    pub fn with_lint_allows(mut self, lint_allows: Vec<String>) -> Self {
+        self.lint_allows = lint_allows;
+        self
+    }
+
+    /// Optional Cargo feature (e.g. `schema`) required to compile the generated code. When set,
+    /// every `use` statement, container, module, and implementation is marked with
+    /// `#[cfg(feature = "..")]`. Custom code is written as is.
+    pub fn with_cfg_feature(mut self, cfg_feature: Option<String>) -> Self {
+        self.cfg_feature = cfg_feature;
+        self
+    }
+
+    /// Render `Format::Str` as `Cow<'a, str>` and `Format::Bytes` as `Cow<'a, [u8]>` (regardless
+    /// of `with_bytes_representation`) for zero-copy deserialization. Containers that
+    /// (transitively) contain such values take a lifetime parameter `'a`, and the corresponding
+    /// fields are marked with `#[serde(borrow)]`. Only leaf string and byte fields are actually
+    /// borrowed: Serde deserializes nested values such as `Option<Cow<'a, str>>` into owned data.
+    pub fn with_zero_copy(mut self, zero_copy: bool) -> Self {
+        self.zero_copy = zero_copy;
+        self
+    }
+
+    /// How Serde should represent enums (default: externally tagged). This only matters when
+    /// serialization is enabled.
+    pub fn with_enum_representation(mut self, enum_representation: EnumRepresentation) -> Self {
+        self.enum_representation = enum_representation;
+        self
+    }
+
+    /// Indentation and line endings of the generated code (default: 4 spaces and `\n`).
+    /// Custom code is written as is.
+    pub fn with_formatting(mut self, formatting: Formatting) -> Self {
+        self.formatting = formatting;
+        self
+    }
+
+    /// Whether to pipe the generated code through `rustfmt` before writing it. This takes
+    /// precedence over the formatting options. Code generation fails if `rustfmt` is not found
+    /// on the `PATH`.
+    pub fn with_run_rustfmt(mut self, run_rustfmt: bool) -> Self {
+        self.run_rustfmt = run_rustfmt;
         self
     }
 
@@ -77,6 +796,7 @@ impl<'a> CodeGenerator<'a> {
         out: &mut dyn Write,
         registry: &Registry,
     ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        self.check_external_definitions(registry)?;
         let external_names = self
             .config
             .external_definitions
@@ -84,33 +804,114 @@ impl<'a> CodeGenerator<'a> {
             .flatten()
             .cloned()
             .collect();
-        let dependencies =
-            analyzer::get_dependency_map_with_external_dependencies(registry, &external_names)?;
-        let entries = analyzer::best_effort_topological_sort(&dependencies);
-
-        let known_sizes = external_names
-            .iter()
-            .map(<String as std::ops::Deref>::deref)
-            .collect::<HashSet<_>>();
+        let definitions = self.quote_definitions(registry, &external_names)?;
+        self.output_definitions(out, definitions)
+    }
 
-        let current_namespace = self
+    /// Same as `output` but write each container definition to a separate file (see
+    /// `container_file_name`), returned as a map from file names to contents. The main output pulls
+    /// these files in with `include!`.
+    fn output_with_container_files(
+        &self,
+        out: &mut dyn Write,
+        registry: &Registry,
+    ) -> std::result::Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
+        self.check_external_definitions(registry)?;
+        let external_names = self
             .config
-            .module_name
-            .split('.')
-            .map(String::from)
+            .external_definitions
+            .values()
+            .flatten()
+            .cloned()
             .collect();
-        let mut emitter = RustEmitter {
-            out: IndentedWriter::new(out, IndentConfig::Space(4)),
-            generator: self,
-            known_sizes: Cow::Owned(known_sizes),
-            current_namespace,
-        };
+        let definitions = self.quote_definitions(registry, &external_names)?;
+        let mut files = BTreeMap::new();
+        let mut includes = Vec::new();
+        for (name, definition) in definitions {
+            let file_name = container_file_name(name);
+            includes.push((name, format!("include!(\"{}\");\n\n", file_name)));
+            let content = definition.trim().to_string() + "\n";
+            let mut content = content.replace('\n', &self.formatting.newline);
+            if self.run_rustfmt {
+                content = String::from_utf8(run_rustfmt(content.into_bytes())?)?;
+            }
+            files.insert(file_name, content);
+        }
+        self.output_definitions(out, includes)?;
+        Ok(files)
+    }
 
-        emitter.output_preamble()?;
-        for name in entries {
-            let format = &registry[name];
-            emitter.output_container(name, format)?;
-            emitter.known_sizes.to_mut().insert(name);
+    fn output_definitions(
+        &self,
+        out: &mut dyn Write,
+        definitions: Vec<(&str, String)>,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // Group definitions by (nested) module while preserving the topological order.
+        let mut root = ModuleDefinitions::default();
+        for (name, definition) in definitions {
+            let mut path = split_qualified_name(name);
+            path.pop();
+            let mut module = &mut root;
+            for segment in path {
+                module = module.submodules.entry(segment.to_string()).or_default();
+            }
+            module.definitions.push(definition);
+        }
+
+        let mut content = Vec::new();
+        {
+            let empty_set = HashSet::new();
+            let mut emitter = RustEmitter {
+                out: self.fragment_writer(&mut content),
+                generator: self,
+                known_sizes: Cow::Owned(HashSet::new()),
+                current_namespace: Vec::new(),
+                current_module: Vec::new(),
+                defaultable: &empty_set,
+                float_containers: &BTreeSet::new(),
+                recursive_names: &empty_set,
+                borrowing_containers: &BTreeSet::new(),
+            };
+            emitter.output_preamble()?;
+            for definition in &root.definitions {
+                write!(emitter.out, "{}", definition)?;
+            }
+            for (name, module) in &root.submodules {
+                emitter.output_module(name, module)?;
+                writeln!(emitter.out)?;
+            }
+        }
+        let content = String::from_utf8_lossy(&content);
+
+        let mut output = Vec::new();
+        let mut writer = self.newline_writer(&mut output);
+        self.output_wrapper_module(&mut writer, &content)?;
+        if self.run_rustfmt {
+            output = run_rustfmt(output)?;
+        }
+        out.write_all(&output)?;
+        Ok(())
+    }
+
+    /// Write the generated content, within the wrapper module if any.
+    fn output_wrapper_module<T: Write>(
+        &self,
+        out: &mut IndentedWriter<T>,
+        content: &str,
+    ) -> Result<()> {
+        match &self.wrapper_module {
+            None => write!(out, "{}", content)?,
+            Some(name) => {
+                if self.no_std {
+                    writeln!(out, "#![no_std]\nextern crate alloc;\n")?;
+                }
+                write!(out, "{}", self.cfg_attribute())?;
+                writeln!(out, "pub mod {} {{", name)?;
+                out.indent();
+                writeln!(out, "{}", content.trim_end())?;
+                out.unindent();
+                writeln!(out, "}}")?;
+            }
         }
         Ok(())
     }
@@ -120,11 +921,167 @@ impl<'a> CodeGenerator<'a> {
         &self,
         registry: &Registry,
     ) -> std::result::Result<BTreeMap<String, String>, Box<dyn std::error::Error>> {
-        let dependencies = analyzer::get_dependency_map(registry)?;
+        let definitions = self.quote_definitions(registry, &BTreeSet::new())?;
+        Ok(definitions
+            .into_iter()
+            .map(|(name, definition)| {
+                let content = definition.trim().to_string() + "\n";
+                (
+                    name.to_string(),
+                    content.replace('\n', &self.formatting.newline),
+                )
+            })
+            .collect())
+    }
+
+    /// Generate the imports and type aliases written at the top of the generated code.
+    pub fn quote_preamble(&self) -> String {
+        let mut content = Vec::new();
+        {
+            let empty_set = HashSet::new();
+            let mut emitter = RustEmitter {
+                out: self.fragment_writer(&mut content),
+                generator: self,
+                known_sizes: Cow::Owned(HashSet::new()),
+                current_namespace: Vec::new(),
+                current_module: Vec::new(),
+                defaultable: &empty_set,
+                float_containers: &BTreeSet::new(),
+                recursive_names: &empty_set,
+                borrowing_containers: &BTreeSet::new(),
+            };
+            emitter
+                .output_preamble()
+                .expect("writing to a vector never fails");
+        }
+        String::from_utf8_lossy(&content).replace('\n', &self.formatting.newline)
+    }
+
+    /// Generate the Rust definition of a single container. Type names that are not in
+    /// `known_sizes` are boxed. Options that depend on other containers (e.g. `Default`
+    /// implementations and zero-copy lifetimes) only take the given container into account.
+    /// Options are not validated: use `output` or `quote_container_definitions` for that.
+    /// Fails if the container contains variables.
+    pub fn quote_container(
+        &self,
+        name: &str,
+        format: &ContainerFormat,
+        known_sizes: &HashSet<&str>,
+    ) -> std::result::Result<String, Box<dyn std::error::Error>> {
+        // Visiting formats fails on variables.
+        let mut names = HashSet::new();
+        format.visit(&mut |format| {
+            if let Format::TypeName(name) = format {
+                names.insert(name.as_str());
+            }
+            Ok(())
+        })?;
+        let registry = std::iter::once((name.to_string(), format.clone())).collect::<Registry>();
+        let defaultable = self.get_defaultable_containers(&registry);
+        let float_containers = if self.total_float_eq {
+            analyzer::containers_with_floats(&registry)
+        } else {
+            BTreeSet::new()
+        };
+        let borrowing_containers = self.get_borrowing_containers(&registry);
+        let mut content = Vec::new();
+        {
+            let mut emitter = RustEmitter {
+                out: self.fragment_writer(&mut content),
+                generator: self,
+                known_sizes: Cow::Borrowed(known_sizes),
+                current_namespace: self
+                    .config
+                    .module_name
+                    .split('.')
+                    .map(String::from)
+                    .collect(),
+                current_module: Vec::new(),
+                defaultable: &defaultable,
+                float_containers: &float_containers,
+                recursive_names: &names,
+                borrowing_containers: &borrowing_containers,
+            };
+            emitter.output_container(name, format)?;
+        }
+        let content = String::from_utf8_lossy(&content).trim().to_string() + "\n";
+        Ok(content.replace('\n', &self.formatting.newline))
+    }
+
+    /// Render a format as a Rust type, using the same mapping as the generated code
+    /// (e.g. type overrides and the `Map` and `Bytes` aliases). When `known_sizes` is
+    /// provided, type names that are not in the set are boxed. Fails if the format contains
+    /// variables.
+    pub fn quote_type(
+        &self,
+        format: &Format,
+        known_sizes: Option<&HashSet<&str>>,
+    ) -> std::result::Result<String, Box<dyn std::error::Error>> {
+        // Visiting formats fails on variables.
+        let mut names = HashSet::new();
+        format.visit(&mut |format| {
+            if let Format::TypeName(name) = format {
+                names.insert(name.as_str());
+            }
+            Ok(())
+        })?;
+        let empty_set = HashSet::new();
+        let emitter = RustEmitter {
+            out: self.fragment_writer(std::io::sink()),
+            generator: self,
+            known_sizes: Cow::Owned(HashSet::new()),
+            current_namespace: self
+                .config
+                .module_name
+                .split('.')
+                .map(String::from)
+                .collect(),
+            current_module: Vec::new(),
+            defaultable: &empty_set,
+            float_containers: &BTreeSet::new(),
+            recursive_names: &names,
+            borrowing_containers: &BTreeSet::new(),
+        };
+        Ok(emitter.quote_type(format, known_sizes))
+    }
+
+    /// Generate the Rust definition of each container, in topological order.
+    fn quote_definitions<'b>(
+        &self,
+        registry: &'b Registry,
+        external_names: &BTreeSet<String>,
+    ) -> std::result::Result<Vec<(&'b str, String)>, Box<dyn std::error::Error>> {
+        self.check_options()?;
+        analyzer::check_fully_resolved(registry)?;
+        analyzer::check_variant_indices(registry)?;
+        common::check_container_names(registry, "Rust", |name| {
+            split_qualified_name(name)
+                .iter()
+                .all(|segment| is_rust_identifier(segment))
+        })?;
+        self.check_flattened_fields(registry)?;
+        self.check_sorted_members(registry)?;
+        self.check_map_keys(registry, external_names)?;
+        self.check_arbitrary_bytes(registry)?;
+        self.check_internally_tagged_enums(registry)?;
+        self.check_untagged_enums(registry)?;
+        self.check_error_impls(registry)?;
+        let dependencies =
+            analyzer::get_dependency_map_with_external_dependencies(registry, external_names)?;
         let entries = analyzer::best_effort_topological_sort(&dependencies);
 
-        let mut result = BTreeMap::new();
-        let mut known_sizes = HashSet::new();
+        let mut result = Vec::new();
+        let mut known_sizes = external_names
+            .iter()
+            .map(<String as std::ops::Deref>::deref)
+            .collect::<HashSet<_>>();
+        let defaultable = self.get_defaultable_containers(registry);
+        let float_containers = if self.total_float_eq {
+            analyzer::containers_with_floats(registry)
+        } else {
+            BTreeSet::new()
+        };
+        let borrowing_containers = self.get_borrowing_containers(registry);
         let current_namespace = self
             .config
             .module_name
@@ -132,48 +1089,575 @@ impl<'a> CodeGenerator<'a> {
             .map(String::from)
             .collect::<Vec<_>>();
 
+        // Only containers that (indirectly) contain themselves without going through a vector
+        // or a map need boxing. Such containers form cycles in the graph of inline dependencies.
+        let inline_dependencies = registry
+            .iter()
+            .map(|(name, format)| {
+                let mut dependencies = BTreeSet::new();
+                collect_inline_dependencies(format, &mut dependencies);
+                dependencies.retain(|x| !external_names.contains(*x));
+                (name.as_str(), dependencies)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let mut recursive_components = Vec::new();
+        let mut component_indices = HashMap::new();
+        for component in analyzer::get_strongly_connected_components(&inline_dependencies) {
+            let is_recursive =
+                component.len() > 1 || inline_dependencies[component[0]].contains(component[0]);
+            if is_recursive {
+                for name in &component {
+                    component_indices.insert(*name, recursive_components.len());
+                }
+                recursive_components.push(component.into_iter().collect::<HashSet<_>>());
+            }
+        }
+        let empty_set = HashSet::new();
+
         for name in entries {
             let mut content = Vec::new();
             {
                 let mut emitter = RustEmitter {
-                    out: IndentedWriter::new(&mut content, IndentConfig::Space(4)),
+                    out: self.fragment_writer(&mut content),
                     generator: self,
                     known_sizes: Cow::Borrowed(&known_sizes),
                     current_namespace: current_namespace.clone(),
+                    current_module: Vec::new(),
+                    defaultable: &defaultable,
+                    float_containers: &float_containers,
+                    recursive_names: component_indices
+                        .get(name)
+                        .map_or(&empty_set, |index| &recursive_components[*index]),
+                    borrowing_containers: &borrowing_containers,
                 };
                 let format = &registry[name];
                 emitter.output_container(name, format)?;
             }
             known_sizes.insert(name);
-            result.insert(
-                name.to_string(),
-                String::from_utf8_lossy(&content).trim().to_string() + "\n",
-            );
+            result.push((name, String::from_utf8_lossy(&content).to_string()));
         }
         Ok(result)
     }
-}
 
-impl<'a, T> RustEmitter<'a, T>
-where
-    T: std::io::Write,
-{
-    fn output_comment(&mut self, name: &str) -> std::io::Result<()> {
-        let mut path = self.current_namespace.clone();
-        path.push(name.to_string());
-        if let Some(doc) = self.generator.config.comments.get(&path) {
-            let text = textwrap::indent(doc, "/// ").replace("\n\n", "\n///\n");
-            write!(self.out, "\n{}", text)?;
+    /// How `Format::Map` is actually represented, taking the selected encodings into account.
+    fn map_representation(&self) -> &MapRepresentation {
+        match &self.map_representation {
+            MapRepresentation::HashMap if self.config.encodings.contains(&Encoding::Bcs) => {
+                &MapRepresentation::BTreeMap
+            }
+            representation => representation,
         }
-        Ok(())
     }
 
-    fn output_custom_code(&mut self, name: &str) -> std::io::Result<()> {
-        let mut path = self.current_namespace.clone();
-        path.push(name.to_string());
-        if let Some(code) = self.generator.config.custom_code.get(&path) {
-            write!(self.out, "\n{}", code)?;
-        }
+    /// Writer for a fragment of generated code. Line endings are converted to
+    /// `formatting.newline` only once, when writing the final output (see `newline_writer`).
+    fn fragment_writer<T>(&self, out: T) -> IndentedWriter<T> {
+        IndentedWriter::with_formatting(out, &self.formatting.indent, "\n")
+    }
+
+    /// Writer for the final output.
+    fn newline_writer<T>(&self, out: T) -> IndentedWriter<T> {
+        IndentedWriter::with_formatting(out, &self.formatting.indent, &self.formatting.newline)
+    }
+
+    /// Attribute (followed by a newline) gating the generated items behind `cfg_feature`, if any.
+    fn cfg_attribute(&self) -> String {
+        match &self.cfg_feature {
+            Some(feature) => format!("#[cfg(feature = \"{}\")]\n", feature),
+            None => String::new(),
+        }
+    }
+
+    /// Whether the generated code uses the `serde_bytes` crate to represent `Format::Bytes`.
+    fn uses_serde_bytes(&self) -> bool {
+        self.config.serialization
+            && !self.no_std
+            && self.bytes_representation == BytesRepresentation::ByteBuf
+            && !self
+                .config
+                .external_definitions
+                .values()
+                .any(|names| names.iter().any(|name| name == "Bytes"))
+    }
+
+    /// Whether the generated code uses `#[serde(with = "serde_bytes")]` for `Seq(U8)` fields.
+    fn uses_serde_bytes_attributes(&self) -> bool {
+        self.config.serialization && self.bytes_as_seq_u8
+    }
+
+    /// Whether the generated code may use `serde_big_array` for large array fields.
+    fn uses_serde_big_array(&self) -> bool {
+        self.config.serialization && self.array_impl_threshold.is_some()
+    }
+
+    /// Reject external definitions that would collide with a container of the registry.
+    fn check_external_definitions(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let collisions = self
+            .config
+            .external_definitions
+            .iter()
+            .flat_map(|(module, names)| {
+                names
+                    .iter()
+                    .filter(|name| registry.contains_key(*name))
+                    .map(move |name| format!("`{}` (from module `{}`)", name, module))
+            })
+            .collect::<Vec<_>>();
+        if !collisions.is_empty() {
+            return Err(format!(
+                "External definitions collide with containers of the registry: {}",
+                collisions.join(", ")
+            )
+            .into());
+        }
+        for name in self.external_aliases.keys() {
+            let is_imported = self
+                .config
+                .external_definitions
+                .iter()
+                .any(|(module, names)| !module.is_empty() && names.contains(name));
+            if !is_imported {
+                return Err(format!(
+                    "Cannot rename `{}`, which is not imported from an external module",
+                    name
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    fn check_options(&self) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if self.no_std && self.map_representation() == &MapRepresentation::HashMap {
+            return Err("`HashMap` is not available in `no_std` mode".into());
+        }
+        if let Some(rule) = &self.rename_all {
+            if !RENAME_ALL_RULES.contains(&rule.as_str()) {
+                return Err(format!("Unknown `rename_all` rule: {}", rule).into());
+            }
+        }
+        for (format, _) in &self.type_overrides {
+            if !is_primitive(format) {
+                return Err(format!("Cannot override the Rust type of {:?}", format).into());
+            }
+        }
+        if self.derive_arbitrary && self.no_std {
+            return Err("Deriving `Arbitrary` is not available in `no_std` mode".into());
+        }
+        if self.zero_copy && self.derive_arbitrary {
+            return Err("Deriving `Arbitrary` is not available with zero-copy types".into());
+        }
+        if self.zero_copy && self.proptest_strategies {
+            return Err("Proptest strategies are not available with zero-copy types".into());
+        }
+        if self.proptest_strategies {
+            if self.no_std {
+                return Err("Proptest strategies are not available in `no_std` mode".into());
+            }
+            if !self.derive_macros.iter().any(|x| x == "Debug") {
+                return Err("Proptest strategies require deriving `Debug`".into());
+            }
+        }
+        if !self.config.encodings.is_empty() {
+            match self.enum_representation {
+                EnumRepresentation::External => (),
+                EnumRepresentation::Untagged => {
+                    return Err(
+                        "Untagged enums are not supported by the Bincode and BCS encodings".into(),
+                    )
+                }
+                _ => {
+                    return Err(
+                        "Tagged enum representations are not supported by the Bincode and BCS encodings"
+                            .into(),
+                    )
+                }
+            }
+        }
+        if self.json_value_conversions && !self.config.serialization {
+            return Err("JSON value conversions require serialization to be enabled".into());
+        }
+        if self.total_float_eq {
+            for derive in &["PartialEq", "PartialOrd"] {
+                if !self.derive_macros.iter().any(|x| x == derive) {
+                    return Err(format!(
+                        "Comparing floats with a total order requires deriving `{}`",
+                        derive
+                    )
+                    .into());
+                }
+            }
+            if self.map_representation() == &MapRepresentation::HashMap {
+                return Err(
+                    "Comparing floats with a total order is not available for `HashMap`".into(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject `BTreeMap` keys that do not implement `Ord`, e.g. floats or containers that do not
+    /// derive `Ord`. (External definitions are assumed to implement `Ord`.)
+    fn check_map_keys(
+        &self,
+        registry: &Registry,
+        external_names: &BTreeSet<String>,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if self.map_representation() != &MapRepresentation::BTreeMap {
+            return Ok(());
+        }
+        let mut orderable = external_names
+            .iter()
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+        if self.derive_macros.iter().any(|x| x == "Ord") {
+            // Start from all containers and remove the ones with unordered values until we
+            // reach a fixpoint.
+            orderable.extend(registry.keys().map(String::as_str));
+            loop {
+                let previous = orderable.clone();
+                orderable.retain(|name| match registry.get(*name) {
+                    Some(format) => is_orderable_container(format, &previous),
+                    None => true,
+                });
+                if orderable.len() == previous.len() {
+                    break;
+                }
+            }
+        }
+        for (name, format) in registry {
+            let mut error = None;
+            format.visit(&mut |format| {
+                if let Format::Map { key, .. } = format {
+                    if error.is_none() && !analyzer::format_is_orderable(key, &orderable) {
+                        error = Some(format!(
+                            "Map keys of type {:?} in container `{}` do not implement `Ord`, as required by `BTreeMap`",
+                            key, name
+                        ));
+                    }
+                }
+                Ok(())
+            })?;
+            if let Some(error) = error {
+                return Err(error.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject `Bytes` values represented by `serde_bytes::ByteBuf` when deriving `Arbitrary`,
+    /// since `ByteBuf` does not implement it.
+    fn check_arbitrary_bytes(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if !self.derive_arbitrary || !self.uses_serde_bytes() {
+            return Ok(());
+        }
+        for (name, format) in registry {
+            let mut uses_bytes = false;
+            format.visit(&mut |format| {
+                match format {
+                    Format::Bytes => uses_bytes = true,
+                    Format::Seq(content) if self.bytes_as_seq_u8 && **content == Format::U8 => {
+                        uses_bytes = true
+                    }
+                    _ => (),
+                }
+                Ok(())
+            })?;
+            if uses_bytes {
+                return Err(format!(
+                    "Container `{}` uses `serde_bytes::ByteBuf`, which does not implement `Arbitrary`",
+                    name
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject error implementations for containers that are not enums, as well as options that
+    /// are not compatible with error implementations (e.g. `no_std`).
+    fn check_error_impls(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if self.error_impls_for.is_empty() {
+            return Ok(());
+        }
+        if !self.derive_macros.iter().any(|x| x == "Debug") {
+            return Err("Error implementations require deriving `Debug`".into());
+        }
+        if self.zero_copy {
+            return Err("Error implementations are not available with zero-copy types".into());
+        }
+        if self.no_std {
+            // `core::error::Error` requires Rust 1.81.
+            return Err("Error implementations are not available in `no_std` mode".into());
+        }
+        for name in &self.error_impls_for {
+            if !matches!(registry.get(name), Some(ContainerFormat::Enum(_))) {
+                return Err(format!(
+                    "Cannot implement `Error` for `{}`, which is not an enum of the registry",
+                    name
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject newtype and tuple variants in internally tagged enums.
+    fn check_internally_tagged_enums(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if !matches!(
+            self.enum_representation,
+            EnumRepresentation::Internal { .. }
+        ) {
+            return Ok(());
+        }
+        for (name, format) in registry {
+            if let ContainerFormat::Enum(variants) = format {
+                for variant in variants.values() {
+                    if let VariantFormat::NewType(_) | VariantFormat::Tuple(_) = variant.value {
+                        return Err(format!(
+                            "Enum `{}` cannot be internally tagged because variant `{}` is not a struct or unit variant",
+                            name, variant.name
+                        )
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject untagged enums where the values of a variant may be deserialized as an earlier
+    /// variant.
+    fn check_untagged_enums(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if self.enum_representation != EnumRepresentation::Untagged {
+            return Ok(());
+        }
+        let values = UntaggedValues { registry };
+        for (name, format) in registry {
+            if let ContainerFormat::Enum(variants) = format {
+                // Serde tries variants in order.
+                let variants = variants.values().collect::<Vec<_>>();
+                for (index, first) in variants.iter().enumerate() {
+                    for second in &variants[index + 1..] {
+                        if values.variant_accepts(&first.value, &second.value) {
+                            return Err(format!(
+                                "Enum `{}` cannot be untagged because variant `{}` may be deserialized as variant `{}`",
+                                name, second.name, first.name
+                            )
+                            .into());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reject reorderings of fields and variants that would change the serialization format or
+    /// the numeric values of variants.
+    fn check_sorted_members(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if !self.sort_members {
+            return Ok(());
+        }
+        fn is_sorted<T>(members: &[&Named<T>]) -> bool {
+            members.windows(2).all(|pair| pair[0].name <= pair[1].name)
+        }
+        for (name, format) in registry {
+            let mut structs = Vec::new();
+            match format {
+                ContainerFormat::Struct(fields) => structs.push((name.clone(), fields)),
+                ContainerFormat::Enum(variants) => {
+                    let members = variants.values().collect::<Vec<_>>();
+                    if !is_sorted(&members) {
+                        if self.config.serialization {
+                            return Err(format!(
+                                "Sorting the variants of `{}` would change its serialization format",
+                                name
+                            )
+                            .into());
+                        }
+                        if is_unit_enum(variants)
+                            && !self.explicit_discriminants
+                            && !self.c_repr_enums
+                        {
+                            return Err(format!(
+                                "Sorting the variants of `{}` requires explicit discriminants",
+                                name
+                            )
+                            .into());
+                        }
+                    }
+                    for variant in variants.values() {
+                        if let VariantFormat::Struct(fields) = &variant.value {
+                            structs.push((format!("{}::{}", name, variant.name), fields));
+                        }
+                    }
+                }
+                _ => (),
+            }
+            if !self.config.serialization {
+                continue;
+            }
+            for (name, fields) in structs {
+                if !is_sorted(&fields.iter().collect::<Vec<_>>()) {
+                    return Err(format!(
+                        "Sorting the fields of `{}` would change its serialization format",
+                        name
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_flattened_fields(
+        &self,
+        registry: &Registry,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if self.flattened_fields.is_empty() {
+            return Ok(());
+        }
+        if !self.config.serialization {
+            return Err("Flattened fields require serialization to be enabled".into());
+        }
+        for (name, format) in registry {
+            let fields = match format {
+                ContainerFormat::Struct(fields) => fields,
+                _ => continue,
+            };
+            let mut path = self
+                .config
+                .module_name
+                .split('.')
+                .map(String::from)
+                .collect::<Vec<_>>();
+            path.push(name.clone());
+            let mut flattened = Vec::new();
+            for field in fields {
+                path.push(field.name.clone());
+                if self.flattened_fields.contains(&path) {
+                    if !matches!(field.value, Format::Map { .. }) {
+                        return Err(format!(
+                            "Cannot flatten field `{}` of `{}`: only maps can be flattened",
+                            field.name, name
+                        )
+                        .into());
+                    }
+                    flattened.push(field.name.as_str());
+                }
+                path.pop();
+            }
+            if flattened.len() > 1 {
+                return Err(format!(
+                    "Cannot flatten more than one field of `{}`: {}",
+                    name,
+                    flattened.join(", ")
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Compute the containers that can implement `Default` (if requested).
+    fn get_defaultable_containers<'b>(&self, registry: &'b Registry) -> HashSet<&'b str> {
+        if !self.derive_default {
+            return HashSet::new();
+        }
+        // Start from all containers and remove the ones with non-default fields until we
+        // reach a fixpoint.
+        let mut result = registry.keys().map(String::as_str).collect::<HashSet<_>>();
+        loop {
+            let previous = result.clone();
+            result.retain(|name| {
+                is_defaultable_container(&registry[*name], &previous, &self.type_overrides)
+            });
+            if result.len() == previous.len() {
+                return result;
+            }
+        }
+    }
+
+    /// Compute the containers that need a lifetime parameter (if zero-copy types are requested).
+    fn get_borrowing_containers<'b>(&self, registry: &'b Registry) -> BTreeSet<&'b str> {
+        let mut result = BTreeSet::new();
+        if !self.zero_copy {
+            return result;
+        }
+        // Iterate until we reach a fixpoint.
+        loop {
+            let previous_len = result.len();
+            for (name, format) in registry {
+                let mut borrows = false;
+                format
+                    .visit(&mut |format| {
+                        borrows |= self.format_borrows(format, &result);
+                        Ok(())
+                    })
+                    .expect("visiting formats never fails");
+                if borrows {
+                    result.insert(name.as_str());
+                }
+            }
+            if result.len() == previous_len {
+                return result;
+            }
+        }
+    }
+
+    /// Whether a format (without looking at nested formats) is rendered with a lifetime,
+    /// assuming that the given containers are.
+    fn format_borrows(&self, format: &Format, borrowing: &BTreeSet<&str>) -> bool {
+        if !self.zero_copy || self.type_overrides.iter().any(|(key, _)| key == format) {
+            return false;
+        }
+        match format {
+            Format::Str | Format::Bytes => true,
+            Format::TypeName(name) => borrowing.contains(name.as_str()),
+            _ => false,
+        }
+    }
+}
+
+impl<'a, T> RustEmitter<'a, T>
+where
+    T: std::io::Write,
+{
+    fn output_comment(&mut self, name: &str) -> std::io::Result<()> {
+        let mut path = self.current_namespace.clone();
+        path.push(name.to_string());
+        if let Some(doc) = self.generator.config.comments.get(&path) {
+            let text = textwrap::indent(doc, "/// ").replace("\n\n", "\n///\n");
+            write!(self.out, "\n{}", text)?;
+        }
+        Ok(())
+    }
+
+    fn output_custom_code(&mut self, name: &str) -> std::io::Result<()> {
+        let mut path = self.current_namespace.clone();
+        path.push(name.to_string());
+        if let Some(code) = self.generator.config.custom_code.get(&path) {
+            write!(self.out, "\n{}", code)?;
+        }
         Ok(())
     }
 
@@ -186,45 +1670,155 @@ where
             .flatten()
             .cloned()
             .collect::<HashSet<_>>();
-        writeln!(self.out, "#![allow(unused_imports)]")?;
+        // Crate-level items are written separately when using a wrapper module.
+        let is_crate_root = self.generator.wrapper_module.is_none();
+        let collections = if self.generator.no_std {
+            if is_crate_root {
+                writeln!(self.out, "#![no_std]")?;
+            }
+            "alloc::collections"
+        } else {
+            "std::collections"
+        };
+        if !self.generator.lint_allows.is_empty() {
+            writeln!(
+                self.out,
+                "#![allow({})]",
+                self.generator.lint_allows.join(", ")
+            )?;
+        }
+        let cfg = self.generator.cfg_attribute();
+        if self.generator.no_std {
+            if is_crate_root {
+                writeln!(self.out, "extern crate alloc;")?;
+            }
+            for item in &["boxed::Box", "string::String", "vec::Vec"] {
+                writeln!(self.out, "{}use alloc::{};", cfg, item)?;
+            }
+        }
         if !external_names.contains("Map") {
-            writeln!(self.out, "use std::collections::BTreeMap as Map;")?;
+            match self.generator.map_representation() {
+                MapRepresentation::BTreeMap => {
+                    writeln!(self.out, "{}use {}::BTreeMap as Map;", cfg, collections)?
+                }
+                MapRepresentation::HashMap => {
+                    writeln!(self.out, "{}use std::collections::HashMap as Map;", cfg)?
+                }
+                MapRepresentation::Custom(_) => (),
+            }
         }
-        if self.generator.config.serialization {
-            writeln!(self.out, "use serde::{{Serialize, Deserialize}};")?;
+        if self.generator.zero_copy {
+            let borrow = if self.generator.no_std {
+                "alloc::borrow"
+            } else {
+                "std::borrow"
+            };
+            writeln!(self.out, "{}use {}::Cow;", cfg, borrow)?;
         }
-        if self.generator.config.serialization && !external_names.contains("Bytes") {
-            writeln!(self.out, "use serde_bytes::ByteBuf as Bytes;")?;
+        if self.generator.seq_representation == SeqRepresentation::VecDeque
+            && !external_names.contains("VecDeque")
+        {
+            writeln!(self.out, "{}use {}::VecDeque;", cfg, collections)?;
+        }
+        // Derive macros are fully qualified when a Serde crate path is given.
+        if self.generator.config.serialization && self.generator.serde_crate_path.is_none() {
+            writeln!(self.out, "{}use serde::{{Serialize, Deserialize}};", cfg)?;
+        }
+        if self.generator.uses_serde_bytes() {
+            writeln!(self.out, "{}use serde_bytes::ByteBuf as Bytes;", cfg)?;
+        }
+        if self.generator.derive_arbitrary {
+            writeln!(self.out, "{}use arbitrary::Arbitrary;", cfg)?;
         }
         for (module, definitions) in &self.generator.config.external_definitions {
             // Skip the empty module name.
             if !module.is_empty() {
+                let definitions = definitions
+                    .iter()
+                    .map(|name| match self.generator.external_aliases.get(name) {
+                        Some(alias) => format!("{} as {}", name, alias),
+                        None => name.clone(),
+                    })
+                    .collect::<Vec<_>>();
                 writeln!(
                     self.out,
-                    "use {}::{{{}}};",
+                    "{}use {}::{{{}}};",
+                    cfg,
                     module,
-                    definitions.to_vec().join(", "),
+                    definitions.join(", "),
                 )?;
             }
         }
         writeln!(self.out)?;
-        if !self.generator.config.serialization && !external_names.contains("Bytes") {
-            // If we are not going to use Serde derive macros, use plain vectors.
-            writeln!(self.out, "type Bytes = Vec<u8>;\n")?;
+        let is_custom = matches!(
+            self.generator.bytes_representation,
+            BytesRepresentation::Custom { .. }
+        );
+        if !self.generator.uses_serde_bytes() && !is_custom && !external_names.contains("Bytes") {
+            // If we are not going to use Serde derive macros (or `std`), use plain vectors.
+            writeln!(self.out, "{}type Bytes = Vec<u8>;\n", cfg)?;
         }
         Ok(())
     }
 
-    fn quote_type(format: &Format, known_sizes: Option<&HashSet<&str>>) -> String {
+    fn output_module(&mut self, name: &str, module: &ModuleDefinitions) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "pub mod {} {{", name)?;
+        self.out.indent();
+        writeln!(self.out, "use super::*;")?;
+        for definition in &module.definitions {
+            write!(self.out, "\n{}\n", definition.trim())?;
+        }
+        for (name, submodule) in &module.submodules {
+            writeln!(self.out)?;
+            self.output_module(name, submodule)?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")
+    }
+
+    /// Quote a (possibly qualified) container name relatively to the current module.
+    fn quote_qualified_name(&self, name: &str) -> String {
+        let mut path = split_qualified_name(name);
+        let ident = path.pop().unwrap_or(name);
+        let common = self
+            .current_module
+            .iter()
+            .zip(&path)
+            .take_while(|(x, y)| x == y)
+            .count();
+        let mut result = "super::".repeat(self.current_module.len() - common);
+        for segment in &path[common..] {
+            result.push_str(segment);
+            result.push_str("::");
+        }
+        result.push_str(ident);
+        result
+    }
+
+    fn quote_type(&self, format: &Format, known_sizes: Option<&HashSet<&str>>) -> String {
+        if let Some((_, name)) = self
+            .generator
+            .type_overrides
+            .iter()
+            .find(|(key, _)| key == format)
+        {
+            return name.clone();
+        }
         use Format::*;
         match format {
+            TypeName(x) if self.generator.external_aliases.contains_key(x) => {
+                self.generator.external_aliases[x].clone()
+            }
             TypeName(x) => {
+                let name = self.quote_qualified_name(x) + self.quote_lifetime(x);
+                // This is the only place where `Box` is added, hence at most once per type.
                 if let Some(set) = known_sizes {
-                    if !set.contains(x.as_str()) {
-                        return format!("Box<{}>", x);
+                    if self.recursive_names.contains(x.as_str()) && !set.contains(x.as_str()) {
+                        return format!("Box<{}>", name);
                     }
                 }
-                x.to_string()
+                name
             }
             Unit => "()".into(),
             Bool => "bool".into(),
@@ -241,74 +1835,231 @@ where
             F32 => "f32".into(),
             F64 => "f64".into(),
             Char => "char".into(),
+            Str if self.generator.zero_copy => "Cow<'a, str>".into(),
             Str => "String".into(),
-            Bytes => "Bytes".into(),
+            Bytes if self.generator.zero_copy => "Cow<'a, [u8]>".into(),
+            Bytes => self.quote_bytes_type().into(),
 
-            Option(format) => format!("Option<{}>", Self::quote_type(format, known_sizes)),
-            Seq(format) => format!("Vec<{}>", Self::quote_type(format, None)),
+            Option(format) => format!("Option<{}>", self.quote_type(format, known_sizes)),
+            Seq(format) if self.generator.bytes_as_seq_u8 && **format == U8 => {
+                self.quote_bytes_type().into()
+            }
+            Seq(format) => format!(
+                "{}<{}>",
+                self.quote_seq_type(),
+                self.quote_type(format, None)
+            ),
             Map { key, value } => format!(
-                "Map<{}, {}>",
-                Self::quote_type(key, None),
-                Self::quote_type(value, None)
+                "{}<{}, {}>",
+                self.quote_map_type(),
+                self.quote_type(key, None),
+                self.quote_type(value, None)
             ),
-            Tuple(formats) => format!("({})", Self::quote_types(formats, known_sizes)),
+            Tuple(formats) => format!("({})", self.quote_types(formats, known_sizes)),
             TupleArray { content, size } => {
-                format!("[{}; {}]", Self::quote_type(content, known_sizes), *size)
+                format!("[{}; {}]", self.quote_type(content, known_sizes), *size)
             }
 
             Variable(_) => panic!("unexpected value"),
         }
     }
 
-    fn quote_types(formats: &[Format], known_sizes: Option<&HashSet<&str>>) -> String {
+    fn quote_seq_type(&self) -> &str {
+        match &self.generator.seq_representation {
+            SeqRepresentation::Vec => "Vec",
+            SeqRepresentation::VecDeque => "VecDeque",
+            SeqRepresentation::Custom(name) => name.as_str(),
+        }
+    }
+
+    fn quote_map_type(&self) -> &str {
+        match self.generator.map_representation() {
+            MapRepresentation::Custom(name) => name.as_str(),
+            MapRepresentation::BTreeMap | MapRepresentation::HashMap => "Map",
+        }
+    }
+
+    fn quote_bytes_type(&self) -> &str {
+        match &self.generator.bytes_representation {
+            BytesRepresentation::Custom { import } => import.as_str(),
+            BytesRepresentation::ByteBuf | BytesRepresentation::VecU8 => "Bytes",
+        }
+    }
+
+    fn quote_types(&self, formats: &[Format], known_sizes: Option<&HashSet<&str>>) -> String {
+        formats
+            .iter()
+            .map(|x| self.quote_type(x, known_sizes))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Same as `quote_types` for the members of tuple structs and variants, which may need to
+    /// be marked with `#[serde(borrow)]`.
+    fn quote_member_types(
+        &self,
+        formats: &[Format],
+        known_sizes: Option<&HashSet<&str>>,
+    ) -> String {
         formats
             .iter()
-            .map(|x| Self::quote_type(x, known_sizes))
+            .map(|x| self.quote_borrow_attribute(x) + &self.quote_type(x, known_sizes))
             .collect::<Vec<_>>()
             .join(", ")
     }
 
+    /// Lifetime parameters of a container, if any.
+    fn quote_lifetime(&self, name: &str) -> &'static str {
+        if self.borrowing_containers.contains(name) {
+            "<'a>"
+        } else {
+            ""
+        }
+    }
+
+    /// Whether a format is rendered with a lifetime.
+    fn needs_lifetime(&self, format: &Format) -> bool {
+        let mut result = false;
+        format
+            .visit(&mut |format| {
+                result |= self
+                    .generator
+                    .format_borrows(format, self.borrowing_containers);
+                Ok(())
+            })
+            .expect("visiting formats never fails");
+        result
+    }
+
+    /// Inline `#[serde(borrow)]` attribute (followed by a space) for members with a lifetime.
+    fn quote_borrow_attribute(&self, format: &Format) -> String {
+        if self.generator.config.serialization && self.needs_lifetime(format) {
+            "#[serde(borrow)] ".to_string()
+        } else {
+            String::new()
+        }
+    }
+
     fn output_fields(&mut self, base: &[&str], fields: &[Named<Format>]) -> Result<()> {
         // Do not add 'pub' within variants.
-        let prefix = if base.len() <= 1 && self.generator.track_visibility {
-            "pub "
+        let prefix = if base.len() <= 1 {
+            self.generator.visibility.prefix()
         } else {
             ""
         };
+        let mut fields = fields.iter().collect::<Vec<_>>();
+        if self.generator.sort_members {
+            fields.sort_by(|a, b| a.name.cmp(&b.name));
+        }
         for field in fields {
             self.output_comment(&field.name)?;
+            let mut path = self.current_namespace.clone();
+            path.push(field.name.clone());
+            if let Some(attributes) = self.generator.field_attributes.get(&path) {
+                for attribute in attributes {
+                    writeln!(self.out, "{}", attribute)?;
+                }
+            }
+            self.output_field_attributes(field)?;
             writeln!(
                 self.out,
                 "{}{}: {},",
                 prefix,
-                field.name,
-                Self::quote_type(&field.value, Some(&self.known_sizes)),
+                quote_field_name(&field.name),
+                self.quote_type(&field.value, Some(&self.known_sizes)),
             )?;
         }
         Ok(())
     }
 
-    fn output_variant(&mut self, base: &str, name: &str, variant: &VariantFormat) -> Result<()> {
-        self.output_comment(name)?;
-        use VariantFormat::*;
-        match variant {
-            Unit => writeln!(self.out, "{},", name),
-            NewType(format) => writeln!(
-                self.out,
-                "{}({}),",
-                name,
-                Self::quote_type(format, Some(&self.known_sizes))
-            ),
-            Tuple(formats) => writeln!(
-                self.out,
-                "{}({}),",
-                name,
-                Self::quote_types(formats, Some(&self.known_sizes))
-            ),
-            Struct(fields) => {
-                writeln!(self.out, "{} {{", name)?;
-                self.current_namespace.push(name.to_string());
-                self.out.indent();
+    fn output_field_attributes(&mut self, field: &Named<Format>) -> Result<()> {
+        if !self.generator.config.serialization {
+            return Ok(());
+        }
+        if RUST_KEYWORDS.contains(&field.name.as_str()) {
+            writeln!(self.out, "#[serde(rename = \"{}\")]", field.name)?;
+        }
+        let mut path = self.current_namespace.clone();
+        path.push(field.name.clone());
+        let default = self.generator.field_defaults.get(&path);
+        if self.generator.skip_none_fields {
+            if let Format::Option(_) = field.value {
+                if default.is_some() {
+                    writeln!(
+                        self.out,
+                        "#[serde(skip_serializing_if = \"Option::is_none\")]"
+                    )?;
+                } else {
+                    writeln!(
+                        self.out,
+                        "#[serde(skip_serializing_if = \"Option::is_none\", default)]"
+                    )?;
+                }
+            }
+        }
+        if let Some(function) = default {
+            writeln!(self.out, "#[serde(default = \"{}\")]", function)?;
+        }
+        if let (Some(threshold), Format::TupleArray { size, .. }) =
+            (self.generator.array_impl_threshold, &field.value)
+        {
+            if *size > threshold {
+                writeln!(self.out, "#[serde(with = \"serde_big_array::BigArray\")]")?;
+            }
+        }
+        if let Some(module) = self.generator.serde_with_overrides.get(&path) {
+            writeln!(self.out, "#[serde(with = \"{}\")]", module)?;
+        } else if self.generator.bytes_as_seq_u8 && field.value == Format::Seq(Box::new(Format::U8))
+        {
+            writeln!(self.out, "#[serde(with = \"serde_bytes\")]")?;
+        }
+        if self.generator.flattened_fields.contains(&path) {
+            writeln!(self.out, "#[serde(flatten)]")?;
+        }
+        if self.needs_lifetime(&field.value) {
+            writeln!(self.out, "#[serde(borrow)]")?;
+        }
+        Ok(())
+    }
+
+    fn output_variant(
+        &mut self,
+        base: &str,
+        name: &str,
+        discriminant: Option<u32>,
+        variant: &VariantFormat,
+    ) -> Result<()> {
+        self.output_comment(name)?;
+        if self.generator.config.serialization {
+            let mut path = self.current_namespace.clone();
+            path.push(name.to_string());
+            if let Some(rename) = self.generator.variant_renames.get(&path) {
+                writeln!(self.out, "#[serde(rename = \"{}\")]", rename)?;
+            }
+        }
+        use VariantFormat::*;
+        match variant {
+            Unit => match discriminant {
+                Some(index) => writeln!(self.out, "{} = {},", name, index),
+                None => writeln!(self.out, "{},", name),
+            },
+            NewType(format) => writeln!(
+                self.out,
+                "{}({}{}),",
+                name,
+                self.quote_borrow_attribute(format),
+                self.quote_type(format, Some(&self.known_sizes))
+            ),
+            Tuple(formats) => writeln!(
+                self.out,
+                "{}({}),",
+                name,
+                self.quote_member_types(formats, Some(&self.known_sizes))
+            ),
+            Struct(fields) => {
+                writeln!(self.out, "{} {{", name)?;
+                self.current_namespace.push(name.to_string());
+                self.out.indent();
                 self.output_fields(&[base, name], fields)?;
                 self.out.unindent();
                 self.current_namespace.pop();
@@ -323,112 +2074,1358 @@ where
         base: &str,
         variants: &BTreeMap<u32, Named<VariantFormat>>,
     ) -> Result<()> {
-        for (expected_index, (index, variant)) in variants.iter().enumerate() {
-            assert_eq!(*index, expected_index as u32);
-            self.output_variant(base, &variant.name, &variant.value)?;
+        let explicit_discriminants = (self.generator.explicit_discriminants
+            || self.generator.c_repr_enums)
+            && is_unit_enum(variants);
+        let mut variants = variants.iter().collect::<Vec<_>>();
+        if self.generator.sort_members {
+            variants.sort_by(|a, b| a.1.name.cmp(&b.1.name));
+        }
+        for (index, variant) in variants {
+            let discriminant = if explicit_discriminants {
+                Some(*index)
+            } else {
+                None
+            };
+            self.output_variant(base, &variant.name, discriminant, &variant.value)?;
         }
         Ok(())
     }
 
     fn output_container(&mut self, name: &str, format: &ContainerFormat) -> Result<()> {
         self.output_comment(name)?;
-        let mut derive_macros = self.generator.derive_macros.clone();
+        // Qualified names are declared in nested modules.
+        let mut path = split_qualified_name(name);
+        let ident = path.pop().unwrap_or(name);
+        self.current_module = path.into_iter().map(String::from).collect();
+        let mut derive_macros = Vec::new();
         if self.generator.config.serialization {
-            derive_macros.push("Serialize".to_string());
-            derive_macros.push("Deserialize".to_string());
+            match &self.generator.serde_crate_path {
+                None => {
+                    derive_macros.push("Serialize".to_string());
+                    derive_macros.push("Deserialize".to_string());
+                }
+                Some(path) => {
+                    derive_macros.push(format!("{}::Serialize", path));
+                    derive_macros.push(format!("{}::Deserialize", path));
+                }
+            }
+        }
+        for derive in &self.generator.derive_macros {
+            if !derive_macros.contains(derive) {
+                derive_macros.push(derive.clone());
+            }
+        }
+        if self.generator.derive_arbitrary && !derive_macros.iter().any(|x| x == "Arbitrary") {
+            derive_macros.push("Arbitrary".to_string());
+        }
+        let is_enum = matches!(format, ContainerFormat::Enum(_));
+        let implements_default = self.defaultable.contains(name);
+        if implements_default && !is_enum && !derive_macros.iter().any(|x| x == "Default") {
+            derive_macros.push("Default".to_string());
+        }
+        if self.generator.map_representation() == &MapRepresentation::HashMap {
+            // `HashMap` does not implement these traits.
+            derive_macros.retain(|x| x != "PartialOrd" && x != "Ord" && x != "Hash");
+        }
+        let has_total_float_eq = self.float_containers.contains(name);
+        if has_total_float_eq {
+            // These traits are implemented manually below.
+            derive_macros.retain(|x| x != "PartialEq" && x != "PartialOrd");
+        }
+        let mut prefix = self.generator.cfg_attribute();
+        if let Some(attributes) = self.generator.container_attributes.get(name) {
+            for attribute in attributes {
+                prefix.push_str(attribute);
+                prefix.push('\n');
+            }
         }
-        let mut prefix = String::new();
         if !derive_macros.is_empty() {
             prefix.push_str(&format!("#[derive({})]\n", derive_macros.join(", ")));
         }
+        if let Some(path) = &self.generator.serde_crate_path {
+            if self.generator.config.serialization {
+                prefix.push_str(&format!("#[serde(crate = \"{}\")]\n", path));
+            }
+        }
+        if let Some(rule) = &self.generator.rename_all {
+            if self.generator.config.serialization {
+                prefix.push_str(&format!("#[serde(rename_all = \"{}\")]\n", rule));
+            }
+        }
+        if is_enum && self.generator.config.serialization {
+            match &self.generator.enum_representation {
+                EnumRepresentation::External => (),
+                EnumRepresentation::Adjacent { tag, content } => prefix.push_str(&format!(
+                    "#[serde(tag = \"{}\", content = \"{}\")]\n",
+                    tag, content
+                )),
+                EnumRepresentation::Internal { tag } => {
+                    prefix.push_str(&format!("#[serde(tag = \"{}\")]\n", tag))
+                }
+                EnumRepresentation::Untagged => prefix.push_str("#[serde(untagged)]\n"),
+            }
+        }
+        let is_newtype = match format {
+            ContainerFormat::NewTypeStruct(_) => true,
+            ContainerFormat::TupleStruct(formats) => formats.len() == 1,
+            _ => false,
+        };
+        if self.generator.transparent_newtypes && self.generator.config.serialization && is_newtype
+        {
+            prefix.push_str("#[serde(transparent)]\n");
+        }
         if let Some(text) = &self.generator.custom_derive_block {
             prefix.push_str(text);
             prefix.push('\n');
         }
-        if self.generator.track_visibility {
-            prefix.push_str("pub ");
+        if let ContainerFormat::Enum(variants) = format {
+            if self.generator.c_repr_enums && !variants.is_empty() && is_unit_enum(variants) {
+                prefix.push_str("#[repr(u32)]\n");
+            }
+        }
+        let non_exhaustive = match format {
+            ContainerFormat::Enum(_) => self.generator.non_exhaustive_enums,
+            _ => self.generator.non_exhaustive_structs,
+        };
+        if non_exhaustive {
+            prefix.push_str("#[non_exhaustive]\n");
         }
+        prefix.push_str(self.generator.visibility.prefix());
+        let lifetime = self.quote_lifetime(name);
 
         use ContainerFormat::*;
         match format {
-            UnitStruct => writeln!(self.out, "{}struct {};\n", prefix, name)?,
+            UnitStruct => writeln!(self.out, "{}struct {};\n", prefix, ident)?,
             NewTypeStruct(format) => writeln!(
                 self.out,
-                "{}struct {}({}{});\n",
+                "{}struct {}{}({}{}{});\n",
                 prefix,
-                name,
-                if self.generator.track_visibility {
-                    "pub "
-                } else {
-                    ""
-                },
-                Self::quote_type(format, Some(&self.known_sizes))
+                ident,
+                lifetime,
+                self.quote_borrow_attribute(format),
+                self.generator.visibility.prefix(),
+                self.quote_type(format, Some(&self.known_sizes))
             )?,
             TupleStruct(formats) => writeln!(
                 self.out,
-                "{}struct {}({});\n",
+                "{}struct {}{}({});\n",
                 prefix,
-                name,
-                Self::quote_types(formats, Some(&self.known_sizes))
+                ident,
+                lifetime,
+                self.quote_member_types(formats, Some(&self.known_sizes))
             )?,
             Struct(fields) => {
-                writeln!(self.out, "{}struct {} {{", prefix, name)?;
+                writeln!(self.out, "{}struct {}{} {{", prefix, ident, lifetime)?;
                 self.current_namespace.push(name.to_string());
                 self.out.indent();
                 self.output_fields(&[name], fields)?;
                 self.out.unindent();
                 self.current_namespace.pop();
                 writeln!(self.out, "}}\n")?;
+                if self.generator.generate_accessors
+                    && self.generator.visibility != Visibility::Public
+                {
+                    self.output_accessors(ident, lifetime, fields)?;
+                }
             }
             Enum(variants) => {
-                writeln!(self.out, "{}enum {} {{", prefix, name)?;
+                writeln!(self.out, "{}enum {}{} {{", prefix, ident, lifetime)?;
                 self.current_namespace.push(name.to_string());
                 self.out.indent();
                 self.output_variants(name, variants)?;
                 self.out.unindent();
                 self.current_namespace.pop();
                 writeln!(self.out, "}}\n")?;
+                if implements_default {
+                    self.output_enum_default(ident, lifetime, &variants[&0].name)?;
+                }
+                if self.generator.numeric_enum_conversions && is_unit_enum(variants) {
+                    self.output_numeric_conversions(ident, variants)?;
+                }
+                let implements_error = self.generator.error_impls_for.contains(name);
+                if implements_error
+                    || (self.generator.display_for_unit_enums && is_unit_enum(variants))
+                {
+                    self.output_enum_display(name, ident, variants)?;
+                }
+                if implements_error {
+                    self.output_enum_error(ident, variants)?;
+                }
+                if self.generator.emit_variant_tags {
+                    self.output_variant_tags(ident, variants)?;
+                }
             }
         }
+        if has_total_float_eq {
+            self.output_total_float_comparisons(ident, lifetime, format)?;
+        }
+        if self.generator.json_value_conversions {
+            self.output_json_value_conversion(ident, lifetime)?;
+        }
+        if self.generator.proptest_strategies {
+            self.output_proptest_strategy(ident, format)?;
+        }
         self.output_custom_code(name)
     }
+
+    fn output_proptest_strategy(&mut self, name: &str, format: &ContainerFormat) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(
+            self.out,
+            "{}fn {}() -> proptest::strategy::BoxedStrategy<{}> {{",
+            self.generator.visibility.prefix(),
+            quote_strategy_name(name),
+            name
+        )?;
+        self.out.indent();
+        writeln!(self.out, "use proptest::strategy::Strategy;")?;
+        use ContainerFormat::*;
+        let value = match format {
+            UnitStruct => VariantFormat::Unit,
+            NewTypeStruct(format) => VariantFormat::NewType(format.clone()),
+            TupleStruct(formats) => VariantFormat::Tuple(formats.clone()),
+            Struct(fields) => VariantFormat::Struct(fields.clone()),
+            Enum(variants) => {
+                writeln!(self.out, "proptest::prop_oneof![")?;
+                self.out.indent();
+                for variant in variants.values() {
+                    let strategy = self.quote_members_strategy(
+                        &format!("{}::{}", name, variant.name),
+                        &variant.value,
+                    );
+                    writeln!(self.out, "{}.boxed(),", strategy)?;
+                }
+                self.out.unindent();
+                writeln!(self.out, "]\n.boxed()")?;
+                self.out.unindent();
+                return writeln!(self.out, "}}\n");
+            }
+        };
+        let strategy = self.quote_members_strategy(name, &value);
+        writeln!(self.out, "{}", strategy)?;
+        self.out.indent();
+        writeln!(self.out, ".boxed()")?;
+        self.out.unindent();
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    /// Quote a strategy building the value `{constructor}{members}` (e.g. `Point { x, y }`) from
+    /// strategies of each member.
+    fn quote_members_strategy(&self, constructor: &str, value: &VariantFormat) -> String {
+        let formats = match value {
+            VariantFormat::Unit => Vec::new(),
+            VariantFormat::NewType(format) => vec![format.as_ref().clone()],
+            VariantFormat::Tuple(formats) => formats.clone(),
+            VariantFormat::Struct(fields) => fields.iter().map(|x| x.value.clone()).collect(),
+            VariantFormat::Variable(_) => panic!("incorrect value"),
+        };
+        let value = format!("{}{}", constructor, quote_member_bindings(value, "a"));
+        let strategies = formats
+            .iter()
+            .map(|format| self.quote_strategy(format, Some(&self.known_sizes)))
+            .collect::<Vec<_>>();
+        quote_tuple_strategy(&strategies, &value)
+    }
+
+    fn quote_strategy(&self, format: &Format, known_sizes: Option<&HashSet<&str>>) -> String {
+        let any = |format: &Format| {
+            format!(
+                "proptest::arbitrary::any::<{}>()",
+                self.quote_type(format, known_sizes)
+            )
+        };
+        if is_primitive(format)
+            && self
+                .generator
+                .type_overrides
+                .iter()
+                .any(|(key, _)| key == format)
+        {
+            return any(format);
+        }
+        let bytes = || {
+            format!(
+                "proptest::collection::vec(proptest::arbitrary::any::<u8>(), 0..3).prop_map(<{}>::from)",
+                self.quote_bytes_type()
+            )
+        };
+        use Format::*;
+        match format {
+            TypeName(x) => {
+                let strategy = format!(
+                    "proptest::strategy::Just(()).prop_flat_map(|_| {}())",
+                    quote_strategy_name(&self.quote_qualified_name(x))
+                );
+                if self.quote_type(format, known_sizes).starts_with("Box<") {
+                    format!("{}.prop_map(Box::new)", strategy)
+                } else {
+                    strategy
+                }
+            }
+            Unit => "proptest::strategy::Just(())".into(),
+            Bytes => bytes(),
+            Option(format) => format!(
+                "proptest::option::of({})",
+                self.quote_strategy(format, known_sizes)
+            ),
+            Seq(format) if self.generator.bytes_as_seq_u8 && **format == U8 => bytes(),
+            Seq(format) => {
+                let content = self.quote_strategy(format, None);
+                match &self.generator.seq_representation {
+                    SeqRepresentation::Vec => {
+                        format!("proptest::collection::vec({}, 0..3)", content)
+                    }
+                    SeqRepresentation::VecDeque => {
+                        format!("proptest::collection::vec_deque({}, 0..3)", content)
+                    }
+                    SeqRepresentation::Custom(_) => format!(
+                        "proptest::collection::vec({}, 0..3).prop_map(|seq| seq.into_iter().collect())",
+                        content
+                    ),
+                }
+            }
+            Map { key, value } => {
+                let key = self.quote_strategy(key, None);
+                let value = self.quote_strategy(value, None);
+                match self.generator.map_representation() {
+                    MapRepresentation::BTreeMap => {
+                        format!("proptest::collection::btree_map({}, {}, 0..3)", key, value)
+                    }
+                    MapRepresentation::HashMap => {
+                        format!("proptest::collection::hash_map({}, {}, 0..3)", key, value)
+                    }
+                    MapRepresentation::Custom(_) => format!(
+                        "proptest::collection::btree_map({}, {}, 0..3).prop_map(|map| map.into_iter().collect())",
+                        key, value
+                    ),
+                }
+            }
+            Tuple(formats) => {
+                let strategies = formats
+                    .iter()
+                    .map(|format| self.quote_strategy(format, known_sizes))
+                    .collect::<Vec<_>>();
+                let bindings = (0..formats.len())
+                    .map(|index| format!("a{}", index))
+                    .collect::<Vec<_>>();
+                quote_tuple_strategy(&strategies, &format!("({})", bindings.join(", ")))
+            }
+            TupleArray { content, size } => {
+                let content_type = self.quote_type(content, known_sizes);
+                format!(
+                    "proptest::collection::vec({}, {}).prop_map(|values| match <[{}; {}] as core::convert::TryFrom<Vec<{}>>>::try_from(values) {{ Ok(array) => array, Err(_) => unreachable!() }})",
+                    self.quote_strategy(content, known_sizes),
+                    size,
+                    content_type,
+                    size,
+                    content_type
+                )
+            }
+            _ => any(format),
+        }
+    }
+
+    fn output_json_value_conversion(&mut self, name: &str, lifetime: &str) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(
+            self.out,
+            "impl{1} core::convert::From<&{0}{1}> for serde_json::Value {{",
+            name, lifetime
+        )?;
+        self.out.indent();
+        writeln!(self.out, "fn from(value: &{}{}) -> Self {{", name, lifetime)?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "serde_json::to_value(value).expect(\"map keys must be representable in JSON\")"
+        )?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    fn output_numeric_conversions(
+        &mut self,
+        name: &str,
+        variants: &BTreeMap<u32, Named<VariantFormat>>,
+    ) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl core::convert::TryFrom<u32> for {} {{", name)?;
+        self.out.indent();
+        writeln!(self.out, "type Error = ();\n")?;
+        writeln!(
+            self.out,
+            "fn try_from(value: u32) -> core::result::Result<Self, Self::Error> {{"
+        )?;
+        self.out.indent();
+        writeln!(self.out, "match value {{")?;
+        self.out.indent();
+        for (index, variant) in variants {
+            writeln!(self.out, "{} => Ok(Self::{}),", index, variant.name)?;
+        }
+        writeln!(self.out, "_ => Err(()),")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")?;
+
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl core::convert::From<&{}> for u32 {{", name)?;
+        self.out.indent();
+        writeln!(self.out, "fn from(value: &{}) -> Self {{", name)?;
+        self.out.indent();
+        writeln!(self.out, "match value {{")?;
+        self.out.indent();
+        for (index, variant) in variants {
+            writeln!(self.out, "{}::{} => {},", name, variant.name, index)?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    fn output_accessors(
+        &mut self,
+        name: &str,
+        lifetime: &str,
+        fields: &[Named<Format>],
+    ) -> Result<()> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+        let mut fields = fields.iter().collect::<Vec<_>>();
+        if self.generator.sort_members {
+            fields.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl{1} {0}{1} {{", name, lifetime)?;
+        self.out.indent();
+        for (index, field) in fields.iter().enumerate() {
+            if index > 0 {
+                writeln!(self.out)?;
+            }
+            let field_name = quote_field_name(&field.name);
+            writeln!(
+                self.out,
+                "pub fn {}(&self) -> &{} {{",
+                field_name,
+                self.quote_type(&field.value, Some(&self.known_sizes))
+            )?;
+            self.out.indent();
+            writeln!(self.out, "&self.{}", field_name)?;
+            self.out.unindent();
+            writeln!(self.out, "}}")?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    fn output_variant_tags(
+        &mut self,
+        name: &str,
+        variants: &BTreeMap<u32, Named<VariantFormat>>,
+    ) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(
+            self.out,
+            "{}mod {}_tags {{",
+            self.generator.visibility.prefix(),
+            name.to_snake_case()
+        )?;
+        self.out.indent();
+        // The visibility of the constants is bounded by the one of the module.
+        for (index, variant) in variants {
+            writeln!(
+                self.out,
+                "pub const {}: u32 = {};",
+                variant.name.to_shouty_snake_case(),
+                index
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    fn output_enum_display(
+        &mut self,
+        name: &str,
+        ident: &str,
+        variants: &BTreeMap<u32, Named<VariantFormat>>,
+    ) -> Result<()> {
+        let serialization = self.generator.config.serialization;
+        let rule = match &self.generator.rename_all {
+            Some(rule) if serialization => Some(rule.as_str()),
+            _ => None,
+        };
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl core::fmt::Display for {} {{", ident)?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {{"
+        )?;
+        self.out.indent();
+        writeln!(self.out, "match self {{")?;
+        self.out.indent();
+        for variant in variants.values() {
+            // Explicit renames take precedence over `rename_all`, as in Serde.
+            let mut path = self.current_namespace.clone();
+            path.push(name.to_string());
+            path.push(variant.name.clone());
+            let text = match (self.generator.variant_renames.get(&path), rule) {
+                (Some(rename), _) if serialization => rename.clone(),
+                (_, Some(rule)) => rename_variant(rule, &variant.name),
+                _ => variant.name.clone(),
+            };
+            writeln!(
+                self.out,
+                "Self::{}{} => f.write_str({:?}),",
+                variant.name,
+                quote_variant_wildcard(&variant.value),
+                text
+            )?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    fn output_enum_error(
+        &mut self,
+        name: &str,
+        variants: &BTreeMap<u32, Named<VariantFormat>>,
+    ) -> Result<()> {
+        let mut sources = Vec::new();
+        for variant in variants.values() {
+            if let Some(pattern) = self.quote_source_pattern(&variant.value) {
+                sources.push(format!("Self::{}{}", variant.name, pattern));
+            }
+        }
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        if sources.is_empty() {
+            return writeln!(self.out, "impl std::error::Error for {} {{}}\n", name);
+        }
+        writeln!(self.out, "impl std::error::Error for {} {{", name)?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {{"
+        )?;
+        self.out.indent();
+        writeln!(self.out, "match self {{")?;
+        self.out.indent();
+        for pattern in &sources {
+            writeln!(self.out, "{} => Some(source),", pattern)?;
+        }
+        if sources.len() < variants.len() {
+            writeln!(self.out, "_ => None,")?;
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    /// Pattern binding `source` to the first member of a variant that is an error, if any.
+    fn quote_source_pattern(&self, variant: &VariantFormat) -> Option<String> {
+        let is_error = |format: &Format| match format {
+            Format::TypeName(name) => self.generator.error_impls_for.contains(name),
+            _ => false,
+        };
+        match variant {
+            VariantFormat::NewType(format) if is_error(format) => Some("(source)".to_string()),
+            VariantFormat::Tuple(formats) => {
+                let index = formats.iter().position(is_error)?;
+                Some(format!("({}source, ..)", "_, ".repeat(index)))
+            }
+            VariantFormat::Struct(fields) => {
+                let field = fields.iter().find(|field| is_error(&field.value))?;
+                Some(format!(
+                    " {{ {}: source, .. }}",
+                    quote_field_name(&field.name)
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn output_total_float_comparisons(
+        &mut self,
+        name: &str,
+        lifetime: &str,
+        format: &ContainerFormat,
+    ) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl{1} PartialEq for {0}{1} {{", name, lifetime)?;
+        self.out.indent();
+        writeln!(self.out, "fn eq(&self, other: &Self) -> bool {{")?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "self.partial_cmp(other) == Some(core::cmp::Ordering::Equal)"
+        )?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")?;
+
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl{1} PartialOrd for {0}{1} {{", name, lifetime)?;
+        self.out.indent();
+        writeln!(
+            self.out,
+            "fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {{"
+        )?;
+        self.out.indent();
+        use ContainerFormat::*;
+        match format {
+            UnitStruct => writeln!(self.out, "Some(core::cmp::Ordering::Equal)")?,
+            NewTypeStruct(format) => {
+                let value = VariantFormat::NewType(format.clone());
+                self.output_struct_total_cmp(&value)?;
+            }
+            TupleStruct(formats) => {
+                let value = VariantFormat::Tuple(formats.clone());
+                self.output_struct_total_cmp(&value)?;
+            }
+            Struct(fields) => {
+                let value = VariantFormat::Struct(fields.clone());
+                self.output_struct_total_cmp(&value)?;
+            }
+            Enum(variants) => {
+                // Variants are compared by index first, as with derived implementations.
+                let has_several_variants = variants.len() > 1;
+                if has_several_variants {
+                    writeln!(self.out, "let index = |value: &Self| match value {{")?;
+                    self.out.indent();
+                    for (index, variant) in variants {
+                        let pattern = match &variant.value {
+                            VariantFormat::Unit => "",
+                            VariantFormat::NewType(_) | VariantFormat::Tuple(_) => "(..)",
+                            VariantFormat::Struct(_) => " { .. }",
+                            VariantFormat::Variable(_) => panic!("incorrect value"),
+                        };
+                        writeln!(self.out, "Self::{}{} => {},", variant.name, pattern, index)?;
+                    }
+                    self.out.unindent();
+                    writeln!(self.out, "}};")?;
+                }
+                writeln!(self.out, "match (self, other) {{")?;
+                self.out.indent();
+                for variant in variants.values() {
+                    if variant.value == VariantFormat::Unit {
+                        continue;
+                    }
+                    writeln!(
+                        self.out,
+                        "(Self::{}{}, Self::{}{}) => {{",
+                        variant.name,
+                        quote_member_bindings(&variant.value, "a"),
+                        variant.name,
+                        quote_member_bindings(&variant.value, "b"),
+                    )?;
+                    self.out.indent();
+                    self.output_members_total_cmp(&variant.value)?;
+                    self.out.unindent();
+                    writeln!(self.out, "}}")?;
+                }
+                if has_several_variants {
+                    writeln!(self.out, "_ => index(self).partial_cmp(&index(other)),")?;
+                }
+                self.out.unindent();
+                writeln!(self.out, "}}")?;
+            }
+        }
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+
+    /// Compare the members of a struct, after binding them to the variables `a0`, `b0`, etc.
+    fn output_struct_total_cmp(&mut self, value: &VariantFormat) -> Result<()> {
+        writeln!(
+            self.out,
+            "let Self{} = self;",
+            quote_member_bindings(value, "a")
+        )?;
+        writeln!(
+            self.out,
+            "let Self{} = other;",
+            quote_member_bindings(value, "b")
+        )?;
+        self.output_members_total_cmp(value)
+    }
+
+    /// Compare the members bound by `quote_member_bindings` in order, returning early from
+    /// `partial_cmp` as soon as they differ.
+    fn output_members_total_cmp(&mut self, value: &VariantFormat) -> Result<()> {
+        let mut comparisons = self.quote_members_total_cmp(value);
+        let last = match comparisons.pop() {
+            None => return writeln!(self.out, "Some(core::cmp::Ordering::Equal)"),
+            Some(comparison) => comparison,
+        };
+        for comparison in comparisons {
+            writeln!(self.out, "match {} {{", comparison)?;
+            self.out.indent();
+            writeln!(self.out, "Some(core::cmp::Ordering::Equal) => (),")?;
+            writeln!(self.out, "ordering => return ordering,")?;
+            self.out.unindent();
+            writeln!(self.out, "}}")?;
+        }
+        writeln!(self.out, "{}", last)
+    }
+
+    /// Quote the comparisons of the members bound by `quote_member_bindings`.
+    fn quote_members_total_cmp(&self, value: &VariantFormat) -> Vec<String> {
+        let formats = match value {
+            VariantFormat::Unit => Vec::new(),
+            VariantFormat::NewType(format) => vec![format.as_ref()],
+            VariantFormat::Tuple(formats) => formats.iter().collect(),
+            VariantFormat::Struct(fields) => fields.iter().map(|field| &field.value).collect(),
+            VariantFormat::Variable(_) => panic!("incorrect value"),
+        };
+        formats
+            .into_iter()
+            .enumerate()
+            .map(|(index, format)| {
+                let a = format!("a{}", index);
+                let b = format!("b{}", index);
+                self.quote_total_cmp(format, &a, &b, 0)
+            })
+            .collect()
+    }
+
+    /// Quote an expression of type `Option<Ordering>` comparing the references `a` and `b` to
+    /// values of the given format, using the total order of floats. Values without floats are
+    /// compared with their own `PartialOrd` implementation. `depth` is used to name nested
+    /// variables.
+    fn quote_total_cmp(&self, format: &Format, a: &str, b: &str, depth: usize) -> String {
+        use Format::*;
+        let is_overridden = self
+            .generator
+            .type_overrides
+            .iter()
+            .any(|(x, _)| x == format);
+        if is_overridden || !analyzer::format_contains_float(format, self.float_containers) {
+            return format!("{}.partial_cmp({})", a, b);
+        }
+        let x = format!("x{}", depth);
+        let y = format!("y{}", depth);
+        let compare_sequences = |comparison: String| {
+            format!(
+                "{a}.iter().zip({b}.iter()).map({comparison}).find(|ordering| *ordering != Some(core::cmp::Ordering::Equal)).unwrap_or_else(|| {a}.len().partial_cmp(&{b}.len()))",
+                a = a,
+                b = b,
+                comparison = comparison,
+            )
+        };
+        match format {
+            // Same as `total_cmp`, which requires Rust 1.62: flip the bits of negative numbers
+            // (except the sign) so that the integer order matches the IEEE 754 total order.
+            F32 => format!(
+                "{{ let (x, y) = ({}.to_bits() as i32, {}.to_bits() as i32); Some((x ^ (((x >> 31) as u32) >> 1) as i32).cmp(&(y ^ (((y >> 31) as u32) >> 1) as i32))) }}",
+                a, b
+            ),
+            F64 => format!(
+                "{{ let (x, y) = ({}.to_bits() as i64, {}.to_bits() as i64); Some((x ^ (((x >> 63) as u64) >> 1) as i64).cmp(&(y ^ (((y >> 63) as u64) >> 1) as i64))) }}",
+                a, b
+            ),
+            Option(format) => format!(
+                "match ({a}, {b}) {{ (Some({x}), Some({y})) => {cmp}, ({x}, {y}) => {x}.is_some().partial_cmp(&{y}.is_some()) }}",
+                a = a,
+                b = b,
+                x = x,
+                y = y,
+                cmp = self.quote_total_cmp(format, &x, &y, depth + 1),
+            ),
+            Seq(format) | TupleArray {
+                content: format, ..
+            } => compare_sequences(format!(
+                "|({}, {})| {}",
+                x,
+                y,
+                self.quote_total_cmp(format, &x, &y, depth + 1)
+            )),
+            Map { key, value } => {
+                let k = format!("k{}", depth);
+                let l = format!("l{}", depth);
+                compare_sequences(format!(
+                    "|(({k}, {x}), ({l}, {y}))| {}",
+                    quote_then_cmp(vec![
+                        self.quote_total_cmp(key, &k, &l, depth + 1),
+                        self.quote_total_cmp(value, &x, &y, depth + 1),
+                    ]),
+                    k = k,
+                    l = l,
+                    x = x,
+                    y = y,
+                ))
+            }
+            Tuple(formats) => {
+                let quote_bindings = |prefix: &str| {
+                    let bindings = (0..formats.len())
+                        .map(|index| format!("{}_{}", prefix, index))
+                        .collect::<Vec<_>>();
+                    format!("({})", bindings.join(", "))
+                };
+                let comparisons = formats
+                    .iter()
+                    .enumerate()
+                    .map(|(index, format)| {
+                        let a = format!("{}_{}", x, index);
+                        let b = format!("{}_{}", y, index);
+                        self.quote_total_cmp(format, &a, &b, depth + 1)
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "{{ let {} = {}; let {} = {}; {} }}",
+                    quote_bindings(&x),
+                    a,
+                    quote_bindings(&y),
+                    b,
+                    quote_then_cmp(comparisons)
+                )
+            }
+            _ => format!("{}.partial_cmp({})", a, b),
+        }
+    }
+
+    fn output_enum_default(&mut self, name: &str, lifetime: &str, variant: &str) -> Result<()> {
+        write!(self.out, "{}", self.generator.cfg_attribute())?;
+        writeln!(self.out, "impl{1} Default for {0}{1} {{", name, lifetime)?;
+        self.out.indent();
+        writeln!(self.out, "fn default() -> Self {{")?;
+        self.out.indent();
+        writeln!(self.out, "Self::{}", variant)?;
+        self.out.unindent();
+        writeln!(self.out, "}}")?;
+        self.out.unindent();
+        writeln!(self.out, "}}\n")
+    }
+}
+
+/// Pipe Rust source code through `rustfmt`.
+fn run_rustfmt(source: Vec<u8>) -> std::result::Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let child = Command::new("rustfmt")
+        .arg("--edition")
+        .arg("2018")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Err("`rustfmt` was not found on the `PATH`".into());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    // Write the input from another thread to avoid blocking on a full output pipe.
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(&source));
+    let output = child.wait_with_output()?;
+    writer.join().expect("writing to rustfmt does not panic")?;
+    if !output.status.success() {
+        return Err(format!(
+            "rustfmt failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+/// Pattern matching any value of a variant, after the variant name.
+fn quote_variant_wildcard(variant: &VariantFormat) -> &'static str {
+    match variant {
+        VariantFormat::NewType(_) | VariantFormat::Tuple(_) => "(..)",
+        VariantFormat::Struct(_) => " { .. }",
+        VariantFormat::Unit | VariantFormat::Variable(_) => "",
+    }
+}
+
+/// Escape field names that are Rust keywords, using raw identifiers when possible.
+fn quote_field_name(name: &str) -> String {
+    match name {
+        // These keywords cannot be raw identifiers.
+        "crate" | "self" | "Self" | "super" => format!("{}_", name),
+        _ if RUST_KEYWORDS.contains(&name) => format!("r#{}", name),
+        _ => name.to_string(),
+    }
+}
+
+/// Whether `name` can be used as a Rust identifier without escaping.
+fn is_rust_identifier(name: &str) -> bool {
+    common::is_identifier(name) && name != "_" && !RUST_KEYWORDS.contains(&name)
+}
+
+/// Split a container name such as `proto.messages.Header` or `proto::messages::Header` into
+/// module names followed by the Rust identifier.
+fn split_qualified_name(name: &str) -> Vec<&str> {
+    name.split("::").flat_map(|x| x.split('.')).collect()
+}
+
+/// Apply a `rename_all` rule to a variant name, following the implementation of Serde.
+fn rename_variant(rule: &str, variant: &str) -> String {
+    let snake_case = || {
+        let mut result = String::new();
+        for (index, c) in variant.char_indices() {
+            if index > 0 && c.is_uppercase() {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        }
+        result
+    };
+    match rule {
+        "lowercase" => variant.to_ascii_lowercase(),
+        "UPPERCASE" => variant.to_ascii_uppercase(),
+        "camelCase" => {
+            let mut chars = variant.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        }
+        "snake_case" => snake_case(),
+        "SCREAMING_SNAKE_CASE" => snake_case().to_ascii_uppercase(),
+        "kebab-case" => snake_case().replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => snake_case().to_ascii_uppercase().replace('_', "-"),
+        _ => variant.to_string(),
+    }
+}
+
+/// Whether a format is primitive, i.e. neither a container name nor a composite format.
+fn is_primitive(format: &Format) -> bool {
+    use Format::*;
+    !matches!(
+        format,
+        Variable(_) | TypeName(_) | Option(_) | Seq(_) | Map { .. } | Tuple(_) | TupleArray { .. }
+    )
+}
+
+/// Collect the container names used by a container without indirection through a vector or a
+/// map. These are the dependencies that determine the size of the generated Rust type.
+fn collect_inline_dependencies<'b>(format: &'b ContainerFormat, result: &mut BTreeSet<&'b str>) {
+    use ContainerFormat::*;
+    match format {
+        UnitStruct => (),
+        NewTypeStruct(format) => collect_inline_type_names(format, result),
+        TupleStruct(formats) => formats
+            .iter()
+            .for_each(|x| collect_inline_type_names(x, result)),
+        Struct(fields) => fields
+            .iter()
+            .for_each(|x| collect_inline_type_names(&x.value, result)),
+        Enum(variants) => {
+            for variant in variants.values() {
+                match &variant.value {
+                    VariantFormat::NewType(format) => collect_inline_type_names(format, result),
+                    VariantFormat::Tuple(formats) => formats
+                        .iter()
+                        .for_each(|x| collect_inline_type_names(x, result)),
+                    VariantFormat::Struct(fields) => fields
+                        .iter()
+                        .for_each(|x| collect_inline_type_names(&x.value, result)),
+                    VariantFormat::Unit | VariantFormat::Variable(_) => (),
+                }
+            }
+        }
+    }
+}
+
+fn collect_inline_type_names<'b>(format: &'b Format, result: &mut BTreeSet<&'b str>) {
+    use Format::*;
+    match format {
+        TypeName(x) => {
+            result.insert(x.as_str());
+        }
+        Option(format) => collect_inline_type_names(format, result),
+        Tuple(formats) => formats
+            .iter()
+            .for_each(|x| collect_inline_type_names(x, result)),
+        TupleArray { content, .. } => collect_inline_type_names(content, result),
+        _ => (),
+    }
+}
+
+/// Kinds of serialized values, as seen by Serde when deserializing untagged enums from a
+/// self-describing format such as JSON.
+enum ValueKind<'b> {
+    Null,
+    Bool,
+    Integer,
+    Float,
+    Char,
+    Str,
+    Bytes,
+    /// Arrays of any length.
+    Seq(&'b Format),
+    /// Arrays of a fixed length.
+    Tuple(Vec<&'b Format>),
+    /// Objects with arbitrary keys, given the format of values.
+    Map(&'b Format),
+    /// Objects with the given fields.
+    Object(&'b [Named<Format>]),
+    /// Values of an external definition.
+    External(&'b str),
+}
+
+/// Decide whether the values of a variant may be deserialized as another variant when enums
+/// are untagged. This is a conservative approximation: any value of the second variant that
+/// could be accepted by the first one counts (e.g. small integers, one-character strings, or
+/// objects with additional fields). Nested enums are untagged as well.
+struct UntaggedValues<'b> {
+    registry: &'b Registry,
+}
+
+impl<'b> UntaggedValues<'b> {
+    /// Recursive types are only explored up to this depth.
+    const MAX_DEPTH: usize = 8;
+
+    fn variant_accepts(&self, accepting: &'b VariantFormat, value: &'b VariantFormat) -> bool {
+        self.kinds_accept(
+            &self.variant_kinds(accepting, 0),
+            &self.variant_kinds(value, 0),
+            0,
+        )
+    }
+
+    fn format_accepts(&self, accepting: &Format, value: &Format, depth: usize) -> bool {
+        self.kinds_accept(
+            &self.format_kinds(accepting, depth),
+            &self.format_kinds(value, depth),
+            depth,
+        )
+    }
+
+    fn kinds_accept(&self, accepting: &[ValueKind], values: &[ValueKind], depth: usize) -> bool {
+        accepting.iter().any(|accepting| {
+            values
+                .iter()
+                .any(|value| self.kind_accepts(accepting, value, depth + 1))
+        })
+    }
+
+    fn kind_accepts(&self, accepting: &ValueKind, value: &ValueKind, depth: usize) -> bool {
+        use ValueKind::*;
+        if depth > Self::MAX_DEPTH {
+            return false;
+        }
+        let u8_format = Format::U8;
+        match (accepting, value) {
+            (Null, Null) | (Bool, Bool) | (Bytes, Bytes) | (Seq(_), Seq(_)) | (Map(_), Map(_)) => {
+                true
+            }
+            // Floats also accept integers, but not conversely.
+            (Integer, Integer) | (Float, Integer) | (Float, Float) => true,
+            (Char, Char) | (Char, Str) | (Str, Char) | (Str, Str) => true,
+            (Seq(x), Tuple(ys)) => ys.iter().all(|y| self.format_accepts(x, y, depth)),
+            (Seq(x), Bytes) => self.format_accepts(x, &u8_format, depth),
+            (Tuple(xs), Seq(y)) => xs.iter().all(|x| self.format_accepts(x, y, depth)),
+            (Tuple(xs), Bytes) => xs.iter().all(|x| self.format_accepts(x, &u8_format, depth)),
+            (Tuple(xs), Tuple(ys)) => {
+                xs.len() == ys.len()
+                    && xs
+                        .iter()
+                        .zip(ys)
+                        .all(|(x, y)| self.format_accepts(x, y, depth))
+            }
+            (Bytes, Seq(y)) => self.format_accepts(&u8_format, y, depth),
+            (Bytes, Tuple(ys)) => ys.iter().all(|y| self.format_accepts(&u8_format, y, depth)),
+            (Map(x), Object(fields)) => fields
+                .iter()
+                .all(|field| self.format_accepts(x, &field.value, depth)),
+            // Unknown fields are ignored and missing optional fields are `None`.
+            (Object(fields), Map(y)) => fields.iter().all(|field| {
+                matches!(field.value, Format::Option(_))
+                    || self.format_accepts(&field.value, y, depth)
+            }),
+            (Object(xs), Object(ys)) => {
+                xs.iter()
+                    .all(|x| match ys.iter().find(|y| y.name == x.name) {
+                        Some(y) => self.format_accepts(&x.value, &y.value, depth),
+                        None => matches!(x.value, Format::Option(_)),
+                    })
+            }
+            (External(x), External(y)) => x == y,
+            _ => false,
+        }
+    }
+
+    fn variant_kinds(&self, variant: &'b VariantFormat, depth: usize) -> Vec<ValueKind<'b>> {
+        match variant {
+            VariantFormat::Unit => vec![ValueKind::Null],
+            VariantFormat::NewType(format) => self.format_kinds(format, depth),
+            VariantFormat::Tuple(formats) => vec![ValueKind::Tuple(formats.iter().collect())],
+            VariantFormat::Struct(fields) => vec![ValueKind::Object(fields)],
+            VariantFormat::Variable(_) => panic!("incorrect value"),
+        }
+    }
+
+    fn format_kinds(&self, format: &'b Format, depth: usize) -> Vec<ValueKind<'b>> {
+        use Format::*;
+        if depth > Self::MAX_DEPTH {
+            return Vec::new();
+        }
+        match format {
+            TypeName(name) => match self.registry.get(name) {
+                None => vec![ValueKind::External(name)],
+                Some(ContainerFormat::UnitStruct) => vec![ValueKind::Null],
+                Some(ContainerFormat::NewTypeStruct(format)) => {
+                    self.format_kinds(format, depth + 1)
+                }
+                Some(ContainerFormat::TupleStruct(formats)) => {
+                    vec![ValueKind::Tuple(formats.iter().collect())]
+                }
+                Some(ContainerFormat::Struct(fields)) => vec![ValueKind::Object(fields)],
+                Some(ContainerFormat::Enum(variants)) => variants
+                    .values()
+                    .flat_map(|variant| self.variant_kinds(&variant.value, depth + 1))
+                    .collect(),
+            },
+            Unit => vec![ValueKind::Null],
+            Bool => vec![ValueKind::Bool],
+            I8 | I16 | I32 | I64 | I128 | U8 | U16 | U32 | U64 | U128 => vec![ValueKind::Integer],
+            F32 | F64 => vec![ValueKind::Float],
+            Char => vec![ValueKind::Char],
+            Str => vec![ValueKind::Str],
+            Bytes => vec![ValueKind::Bytes],
+            Option(format) => {
+                let mut kinds = vec![ValueKind::Null];
+                kinds.extend(self.format_kinds(format, depth + 1));
+                kinds
+            }
+            Seq(format) => vec![ValueKind::Seq(format)],
+            Map { value, .. } => vec![ValueKind::Map(value)],
+            Tuple(formats) => vec![ValueKind::Tuple(formats.iter().collect())],
+            TupleArray { content, size } => vec![ValueKind::Tuple(vec![content.as_ref(); *size])],
+            Variable(_) => panic!("incorrect value"),
+        }
+    }
+}
+
+/// Whether all the variants of an enum are without data.
+fn is_unit_enum(variants: &BTreeMap<u32, Named<VariantFormat>>) -> bool {
+    variants
+        .values()
+        .all(|variant| variant.value == VariantFormat::Unit)
+}
+
+/// Name of the function returning a `proptest` strategy for a (possibly qualified) container
+/// name, e.g. `super::proto::my_message_strategy` for `super::proto::MyMessage`.
+fn quote_strategy_name(name: &str) -> String {
+    match name.rsplit_once("::") {
+        Some((path, ident)) => format!("{}::{}_strategy", path, ident.to_snake_case()),
+        None => format!("{}_strategy", name.to_snake_case()),
+    }
+}
+
+/// Quote a strategy for the expression `value`, which uses the variables `a0`, `a1`, etc.
+/// generated by the given strategies. Proptest implements `Strategy` for tuples of up to 12
+/// strategies, hence larger tuples are nested.
+fn quote_tuple_strategy(strategies: &[String], value: &str) -> String {
+    if strategies.is_empty() {
+        return format!("proptest::strategy::LazyJust::new(|| {})", value);
+    }
+    let bindings = (0..strategies.len())
+        .map(|index| format!("a{}", index))
+        .collect::<Vec<_>>();
+    let (strategy, pattern) = if strategies.len() <= 12 {
+        (strategies.join(", "), bindings.join(", "))
+    } else {
+        let chunks = |items: &[String]| {
+            items
+                .chunks(12)
+                .map(|chunk| format!("({},)", chunk.join(", ")))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        (chunks(strategies), chunks(&bindings))
+    };
+    // Single-element tuples need a trailing comma.
+    let comma = if strategies.len() == 1 { "," } else { "" };
+    format!(
+        "({}{}).prop_map(|({}{})| {})",
+        strategy, comma, pattern, comma, value
+    )
+}
+
+/// Quote a pattern binding the members of a struct or a variant to the variables `{prefix}0`,
+/// `{prefix}1`, etc.
+fn quote_member_bindings(value: &VariantFormat, prefix: &str) -> String {
+    match value {
+        VariantFormat::Unit => String::new(),
+        VariantFormat::NewType(_) => format!("({}0)", prefix),
+        VariantFormat::Tuple(formats) => {
+            let bindings = (0..formats.len())
+                .map(|index| format!("{}{}", prefix, index))
+                .collect::<Vec<_>>();
+            format!("({})", bindings.join(", "))
+        }
+        VariantFormat::Struct(fields) => {
+            let bindings = fields
+                .iter()
+                .enumerate()
+                .map(|(index, field)| {
+                    format!("{}: {}{}", quote_field_name(&field.name), prefix, index)
+                })
+                .collect::<Vec<_>>();
+            format!(" {{ {} }}", bindings.join(", "))
+        }
+        VariantFormat::Variable(_) => panic!("incorrect value"),
+    }
+}
+
+/// Chain comparisons of type `Option<Ordering>`: the first one that is not `Some(Equal)` wins.
+fn quote_then_cmp(comparisons: Vec<String>) -> String {
+    comparisons
+        .into_iter()
+        .rev()
+        .reduce(|result, comparison| {
+            format!(
+                "match {} {{ Some(core::cmp::Ordering::Equal) => {}, ordering => ordering }}",
+                comparison, result
+            )
+        })
+        .unwrap_or_else(|| "Some(core::cmp::Ordering::Equal)".to_string())
+}
+
+/// Whether a container can implement `Default`, assuming that the given containers do.
+fn is_defaultable_container(
+    format: &ContainerFormat,
+    defaultable: &HashSet<&str>,
+    type_overrides: &[(Format, String)],
+) -> bool {
+    use ContainerFormat::*;
+    match format {
+        UnitStruct => true,
+        NewTypeStruct(format) => is_defaultable(format, defaultable, type_overrides),
+        TupleStruct(formats) => formats
+            .iter()
+            .all(|x| is_defaultable(x, defaultable, type_overrides)),
+        Struct(fields) => fields
+            .iter()
+            .all(|field| is_defaultable(&field.value, defaultable, type_overrides)),
+        Enum(variants) => matches!(
+            variants.get(&0),
+            Some(Named {
+                value: VariantFormat::Unit,
+                ..
+            })
+        ),
+    }
+}
+
+/// Whether a container can implement `Ord`, assuming that the given containers do.
+fn is_orderable_container(format: &ContainerFormat, orderable: &BTreeSet<&str>) -> bool {
+    use ContainerFormat::*;
+    match format {
+        UnitStruct => true,
+        NewTypeStruct(format) => analyzer::format_is_orderable(format, orderable),
+        TupleStruct(formats) => formats
+            .iter()
+            .all(|x| analyzer::format_is_orderable(x, orderable)),
+        Struct(fields) => fields
+            .iter()
+            .all(|field| analyzer::format_is_orderable(&field.value, orderable)),
+        Enum(variants) => variants.values().all(|variant| match &variant.value {
+            VariantFormat::Unit => true,
+            VariantFormat::NewType(format) => analyzer::format_is_orderable(format, orderable),
+            VariantFormat::Tuple(formats) => formats
+                .iter()
+                .all(|x| analyzer::format_is_orderable(x, orderable)),
+            VariantFormat::Struct(fields) => fields
+                .iter()
+                .all(|field| analyzer::format_is_orderable(&field.value, orderable)),
+            VariantFormat::Variable(_) => false,
+        }),
+    }
+}
+
+/// Whether a Rust type implements `Default`, assuming that the given containers do. Overridden
+/// types are not assumed to implement `Default`.
+fn is_defaultable(
+    format: &Format,
+    defaultable: &HashSet<&str>,
+    type_overrides: &[(Format, String)],
+) -> bool {
+    use Format::*;
+    if type_overrides.iter().any(|(x, _)| x == format) {
+        return false;
+    }
+    match format {
+        TypeName(x) => defaultable.contains(x.as_str()),
+        // The standard library implements `Default` for tuples of up to 12 elements and arrays
+        // of up to 32 elements.
+        Tuple(formats) => {
+            formats.len() <= 12
+                && formats
+                    .iter()
+                    .all(|x| is_defaultable(x, defaultable, type_overrides))
+        }
+        TupleArray { content, size } => {
+            *size <= 32 && is_defaultable(content, defaultable, type_overrides)
+        }
+        Variable(_) => false,
+        _ => true,
+    }
 }
 
 /// Installer for generated source files in Rust.
 pub struct Installer {
     install_dir: PathBuf,
+    file_per_container: bool,
 }
 
 impl Installer {
     pub fn new(install_dir: PathBuf) -> Self {
-        Installer { install_dir }
+        Installer {
+            install_dir,
+            file_per_container: false,
+        }
+    }
+
+    /// Whether to write each container definition to a separate file in `src/`.
+    /// Files whose content is unchanged are never rewritten, so that re-installing a large
+    /// schema only touches the definitions that actually changed. Container files included by
+    /// the previously installed `src/lib.rs` that are no longer generated (e.g. definitions of
+    /// removed containers) are deleted. Other files are left untouched.
+    pub fn with_file_per_container(mut self, file_per_container: bool) -> Self {
+        self.file_per_container = file_per_container;
+        self
     }
 
     fn runtime_installation_message(name: &str) {
         eprintln!("Not installing sources for published crate {}", name);
     }
-}
-
-impl crate::SourceInstaller for Installer {
-    type Error = Box<dyn std::error::Error>;
 
-    fn install_module(
+    /// Same as `install_module` but use a pre-configured code generator, e.g. to control
+    /// derive macros or the representation of maps.
+    pub fn install_module_with_generator(
         &self,
-        config: &CodeGeneratorConfig,
+        generator: &CodeGenerator,
         registry: &Registry,
-    ) -> std::result::Result<(), Self::Error> {
-        let generator = CodeGenerator::new(config);
-        let (name, version) = {
-            let parts = config.module_name.splitn(2, ':').collect::<Vec<_>>();
-            if parts.len() >= 2 {
-                (parts[0].to_string(), parts[1].to_string())
-            } else {
-                (parts[0].to_string(), "0.1.0".to_string())
-            }
-        };
-        let dir_path = self.install_dir.join(&name);
-        std::fs::create_dir_all(&dir_path)?;
-        let mut cargo = std::fs::File::create(&dir_path.join("Cargo.toml"))?;
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let config = generator.config;
+        let (name, version) = split_public_name(&config.module_name);
+        let dir_path = self.install_dir.join(name);
+        let mut contents = HashMap::new();
+        let mut cargo = Vec::new();
         write!(
             cargo,
             r#"[package]
@@ -438,14 +3435,220 @@ edition = "2018"
 
 [dependencies]
 serde = {{ version = "1.0", features = ["derive"] }}
-serde_bytes = "0.11"
 "#,
             name, version,
         )?;
-        std::fs::create_dir(dir_path.join("src"))?;
-        let source_path = dir_path.join("src/lib.rs");
-        let mut source = std::fs::File::create(&source_path)?;
-        generator.output(&mut source, registry)
+        if generator.uses_serde_bytes() || generator.uses_serde_bytes_attributes() {
+            writeln!(cargo, "serde_bytes = \"0.11\"")?;
+        }
+        if generator.uses_serde_big_array() {
+            writeln!(cargo, "serde-big-array = \"0.5\"")?;
+        }
+        if generator.json_value_conversions {
+            writeln!(cargo, "serde_json = \"1.0\"")?;
+        }
+        if generator.proptest_strategies {
+            writeln!(cargo, "proptest = \"1\"")?;
+        }
+        if generator.derive_arbitrary {
+            writeln!(
+                cargo,
+                "arbitrary = {{ version = \"1\", features = [\"derive\"] }}"
+            )?;
+        }
+        for encoding in &config.encodings {
+            writeln!(cargo, "{}", Self::encoding_dependency(*encoding))?;
+        }
+        contents.insert(dir_path.join("Cargo.toml"), cargo);
+
+        let source_dir = dir_path.join("src");
+        // Read the files included by a previous installation before overwriting `lib.rs`.
+        let previous_files = if self.file_per_container {
+            included_container_files(&source_dir)
+        } else {
+            Vec::new()
+        };
+        let mut source = Vec::new();
+        if self.file_per_container {
+            let files = generator.output_with_container_files(&mut source, registry)?;
+            for (file_name, content) in files {
+                contents.insert(source_dir.join(file_name), content.into_bytes());
+            }
+        } else {
+            generator.output(&mut source, registry)?;
+        }
+        if !config.encodings.is_empty() {
+            Self::output_runtime_module(&mut source, &config.encodings)?;
+        }
+        contents.insert(source_dir.join("lib.rs"), source);
+
+        for path in self.plan(&config.module_name, registry) {
+            std::fs::create_dir_all(path.parent().expect("planned paths have a parent"))?;
+            let content = contents
+                .get(&path)
+                .ok_or_else(|| format!("No content was generated for `{}`", path.display()))?;
+            write_if_changed(&path, content)?;
+        }
+        for path in previous_files {
+            if !contents.contains_key(&path) {
+                remove_stale_file(&source_dir, &path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// List the files that `install_module` would write for the given module name (e.g.
+    /// `my_crate` or `my_crate:0.2.0`) and registry, without touching the filesystem.
+    pub fn plan(&self, public_name: &str, registry: &Registry) -> Vec<PathBuf> {
+        let (name, _) = split_public_name(public_name);
+        let dir_path = self.install_dir.join(name);
+        let mut paths = vec![dir_path.join("Cargo.toml"), dir_path.join("src/lib.rs")];
+        if self.file_per_container {
+            for name in registry.keys() {
+                paths.push(dir_path.join("src").join(container_file_name(name)));
+            }
+        }
+        paths
+    }
+
+    /// Dependency line to add to `Cargo.toml` for the given encoding.
+    fn encoding_dependency(encoding: Encoding) -> &'static str {
+        match encoding {
+            Encoding::Bincode => "bincode = \"1.3\"",
+            Encoding::Bcs => "bcs = \"0.1\"",
+        }
+    }
+
+    /// Write a module `runtime` re-exporting `serialize` and `deserialize` for each encoding.
+    fn output_runtime_module(
+        out: &mut dyn Write,
+        encodings: &BTreeSet<Encoding>,
+    ) -> std::io::Result<()> {
+        let mut out = IndentedWriter::new(out, IndentConfig::Space(4));
+        writeln!(out, "\npub mod runtime {{")?;
+        out.indent();
+        for encoding in encodings {
+            writeln!(out, "pub mod {} {{", encoding.name())?;
+            out.indent();
+            match encoding {
+                Encoding::Bincode => {
+                    writeln!(out, "pub use ::bincode::{{deserialize, serialize}};")?
+                }
+                Encoding::Bcs => writeln!(
+                    out,
+                    "pub use ::bcs::{{from_bytes as deserialize, to_bytes as serialize}};"
+                )?,
+            }
+            out.unindent();
+            writeln!(out, "}}")?;
+        }
+        out.unindent();
+        writeln!(out, "}}")
+    }
+}
+
+/// Split the name of a crate from its version, if any (e.g. `my_crate:0.2.0`).
+fn split_public_name(public_name: &str) -> (&str, &str) {
+    match public_name.split_once(':') {
+        Some((name, version)) => (name, version),
+        None => (public_name, "0.1.0"),
+    }
+}
+
+/// Path of the file defining a container relative to `src/`, when definitions are written in
+/// separate files. Qualified names are mapped to nested directories (e.g. `a/b/C.rs` for
+/// `a::b::C` or `a.b.C`) and characters that are not valid in identifiers are replaced by `_`.
+fn container_file_name(name: &str) -> String {
+    let segments = split_qualified_name(name)
+        .into_iter()
+        .map(|segment| {
+            let segment = segment
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>();
+            if segment.is_empty() {
+                "_".to_string()
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{}.rs", segments.join("/"))
+}
+
+/// Write `content` to `path` unless the file already exists with the same content.
+fn write_if_changed(path: &std::path::Path, content: &[u8]) -> std::io::Result<()> {
+    if let Ok(existing) = std::fs::read(path) {
+        if existing == content {
+            return Ok(());
+        }
+    }
+    std::fs::write(path, content)
+}
+
+/// Container files included by the `lib.rs` file found in `source_dir`, if any. Only
+/// relative paths of the form written by `output_with_container_files` are considered.
+fn included_container_files(source_dir: &std::path::Path) -> Vec<PathBuf> {
+    let lib = match std::fs::read_to_string(source_dir.join("lib.rs")) {
+        Ok(lib) => lib,
+        Err(_) => return Vec::new(),
+    };
+    lib.lines()
+        .filter_map(|line| {
+            let file_name = line
+                .trim()
+                .strip_prefix("include!(\"")?
+                .strip_suffix("\");")?;
+            let path = std::path::Path::new(file_name);
+            let is_relative = path
+                .components()
+                .all(|component| matches!(component, std::path::Component::Normal(_)));
+            if is_relative && path.extension() == Some("rs".as_ref()) {
+                Some(source_dir.join(path))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Remove a file under `source_dir`, as well as the directories left empty.
+fn remove_stale_file(source_dir: &std::path::Path, path: &std::path::Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error),
+        _ => (),
+    }
+    let mut dir = path.parent();
+    while let Some(path) = dir {
+        if path == source_dir {
+            break;
+        }
+        let is_empty =
+            std::fs::read_dir(path).map_or(false, |mut entries| entries.next().is_none());
+        if !is_empty {
+            break;
+        }
+        std::fs::remove_dir(path)?;
+        dir = path.parent();
+    }
+    Ok(())
+}
+
+impl crate::SourceInstaller for Installer {
+    type Error = Box<dyn std::error::Error>;
+
+    fn install_module(
+        &self,
+        config: &CodeGeneratorConfig,
+        registry: &Registry,
+    ) -> std::result::Result<(), Self::Error> {
+        self.install_module_with_generator(&CodeGenerator::new(config), registry)
     }
 
     fn install_serde_runtime(&self) -> std::result::Result<(), Self::Error> {
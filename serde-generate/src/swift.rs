@@ -4,15 +4,15 @@
 #![allow(dead_code)]
 
 use crate::{
-    common,
+    analyzer, common,
     indent::{IndentConfig, IndentedWriter},
     CodeGeneratorConfig, Encoding,
 };
 use heck::CamelCase;
 use include_dir::include_dir as include_directory;
-use serde_reflection::{ContainerFormat, Format, FormatHolder, Named, Registry, VariantFormat};
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     io::{Result, Write},
     path::PathBuf,
 };
@@ -34,6 +34,8 @@ struct SwiftEmitter<'a, T> {
     generator: &'a CodeGenerator<'a>,
     /// Current namespace (e.g. vec!["Package", "MyClass"])
     current_namespace: Vec<String>,
+    /// Containers that (indirectly) depend on themselves.
+    recursive_names: HashSet<String>,
 }
 
 impl<'a> CodeGenerator<'a> {
@@ -65,6 +67,8 @@ impl<'a> CodeGenerator<'a> {
 
     /// Output class definitions for `registry`.
     pub fn output(&self, out: &mut dyn Write, registry: &Registry) -> Result<()> {
+        common::check_fully_resolved(registry)?;
+        common::check_variant_indices(registry)?;
         let current_namespace = self
             .config
             .module_name
@@ -72,10 +76,21 @@ impl<'a> CodeGenerator<'a> {
             .map(String::from)
             .collect::<Vec<_>>();
 
+        // Only enums that belong to a cycle of dependencies need to be `indirect`.
+        let dependencies = analyzer::get_dependency_map(registry)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error.to_string()))?;
+        let mut recursive_names = HashSet::new();
+        for component in analyzer::get_strongly_connected_components(&dependencies) {
+            if component.len() > 1 || dependencies[component[0]].contains(component[0]) {
+                recursive_names.extend(component.into_iter().map(String::from));
+            }
+        }
+
         let mut emitter = SwiftEmitter {
             out: IndentedWriter::new(out, IndentConfig::Space(4)),
             generator: self,
             current_namespace,
+            recursive_names,
         };
 
         emitter.output_preamble()?;
@@ -191,16 +206,12 @@ where
 
     fn output_trait_helpers(&mut self, registry: &Registry) -> Result<()> {
         let mut subtypes = BTreeMap::new();
-        for format in registry.values() {
-            format
-                .visit(&mut |f| {
-                    if Self::needs_helper(f) {
-                        subtypes.insert(common::mangle_type(f), f.clone());
-                    }
-                    Ok(())
-                })
-                .unwrap();
-        }
+        analyzer::visit_formats(registry, &mut |f| {
+            if Self::needs_helper(f) {
+                subtypes.insert(common::mangle_type(f), f.clone());
+            }
+        })
+        .unwrap();
         for (mangled_name, subtype) in &subtypes {
             self.output_serialization_helper(mangled_name, subtype)?;
             self.output_deserialization_helper(mangled_name, subtype)?;
@@ -666,7 +677,12 @@ public static func {1}Deserialize(input: [UInt8]) throws -> {0} {{
     ) -> Result<()> {
         writeln!(self.out)?;
         self.output_comment(name)?;
-        writeln!(self.out, "indirect public enum {}: Hashable {{", name)?;
+        let prefix = if self.recursive_names.contains(name) {
+            "indirect "
+        } else {
+            ""
+        };
+        writeln!(self.out, "{}public enum {}: Hashable {{", prefix, name)?;
         self.current_namespace.push(name.to_string());
         self.out.indent();
         for variant in variants.values() {
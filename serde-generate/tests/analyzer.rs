@@ -4,6 +4,7 @@
 use crate::test_utils;
 use maplit::{btreemap, btreeset};
 use serde_generate::analyzer;
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
 
 #[test]
 fn test_topological_sort() {
@@ -96,3 +97,557 @@ fn test_on_larger_registry() {
         ]
     );
 }
+
+#[test]
+fn test_topological_sort_is_deterministic() {
+    use analyzer::best_effort_topological_sort as tsort;
+    let children = btreemap! {
+        "A" => btreeset!["B", "C"],
+        "B" => btreeset!["A", "D"],
+        "C" => btreeset!["D"],
+        "D" => btreeset!["C", "E"],
+        "E" => btreeset![],
+    };
+    let first = tsort(&children);
+    let second = tsort(&children);
+    assert_eq!(first, second);
+    assert_eq!(first, vec!["C", "E", "D", "B", "A"]);
+}
+
+#[test]
+fn test_strongly_connected_components() {
+    use analyzer::get_strongly_connected_components as scc;
+    // Two-node cycle.
+    assert_eq!(
+        scc(&btreemap! {
+            1 => btreeset![2],
+            2 => btreeset![1],
+            3 => btreeset![1],
+        }),
+        vec![vec![1, 2], vec![3]]
+    );
+    // Larger cluster.
+    assert_eq!(
+        scc(&btreemap! {
+            1 => btreeset![2],
+            2 => btreeset![3, 5],
+            3 => btreeset![4],
+            4 => btreeset![2],
+            5 => btreeset![5, 6],
+            6 => btreeset![],
+        }),
+        vec![vec![6], vec![5], vec![2, 3, 4], vec![1]]
+    );
+
+    let registry = test_utils::get_registry().unwrap();
+    let components = analyzer::strongly_connected_components(&registry).unwrap();
+    assert!(components.contains(&vec![
+        "List".to_string(),
+        "SerdeData".to_string(),
+        "Tree".to_string()
+    ]));
+    assert!(components.contains(&vec!["SimpleList".to_string()]));
+}
+
+#[test]
+fn test_unreferenced_containers() {
+    let newtype = |name: &str| {
+        ContainerFormat::NewTypeStruct(Box::new(Format::Seq(Box::new(Format::TypeName(
+            name.to_string(),
+        )))))
+    };
+    let mut registry = Registry::new();
+    registry.insert("Root".to_string(), newtype("Reachable"));
+    registry.insert("Reachable".to_string(), ContainerFormat::UnitStruct);
+    registry.insert("Dead".to_string(), newtype("AlsoDead"));
+    registry.insert("AlsoDead".to_string(), newtype("Reachable"));
+
+    assert_eq!(
+        analyzer::unreferenced_containers(&registry, &["Root"]).unwrap(),
+        vec!["AlsoDead".to_string(), "Dead".to_string()]
+    );
+    assert_eq!(
+        analyzer::unreferenced_containers(&registry, &["Root", "Dead"]).unwrap(),
+        Vec::<String>::new()
+    );
+
+    let registry = test_utils::get_registry().unwrap();
+    assert_eq!(
+        analyzer::unreferenced_containers(&registry, &["SerdeData"]).unwrap(),
+        Vec::<String>::new()
+    );
+}
+
+#[test]
+fn test_reachable_subregistry() {
+    let registry = test_utils::get_registry().unwrap();
+    let subregistry = analyzer::reachable_subregistry(&registry, &["OtherTypes"]).unwrap();
+    assert_eq!(
+        subregistry.keys().collect::<Vec<_>>(),
+        vec!["OtherTypes", "Struct"]
+    );
+    assert_eq!(subregistry["OtherTypes"], registry["OtherTypes"]);
+}
+
+#[test]
+fn test_check_fully_resolved() {
+    use serde_reflection::{Named, VariantFormat};
+
+    let registry = test_utils::get_registry().unwrap();
+    assert!(analyzer::check_fully_resolved(&registry).is_ok());
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Foo".to_string(),
+        ContainerFormat::Struct(vec![Named {
+            name: "bar".to_string(),
+            value: Format::Seq(Box::new(Format::unknown())),
+        }]),
+    );
+    let error = analyzer::check_fully_resolved(&registry).unwrap_err();
+    assert_eq!(error.to_string(), "Unresolved format variable at Foo.bar");
+
+    let mut variants = std::collections::BTreeMap::new();
+    variants.insert(
+        0,
+        Named {
+            name: "A".to_string(),
+            value: VariantFormat::unknown(),
+        },
+    );
+    let mut registry = Registry::new();
+    registry.insert("Choice".to_string(), ContainerFormat::Enum(variants));
+    let error = analyzer::check_fully_resolved(&registry).unwrap_err();
+    assert_eq!(error.to_string(), "Unresolved format variable at Choice::A");
+}
+
+#[test]
+fn test_merge_registries() {
+    let registry = test_utils::get_registry().unwrap();
+    let mut first = Registry::new();
+    let mut second = Registry::new();
+    for (index, (name, format)) in registry.iter().enumerate() {
+        if index % 2 == 0 {
+            first.insert(name.clone(), format.clone());
+        } else {
+            second.insert(name.clone(), format.clone());
+        }
+    }
+    // Identical definitions may appear several times.
+    second.insert("Struct".to_string(), registry["Struct"].clone());
+    let merged = analyzer::merge_registries(vec![first, second.clone()]).unwrap();
+    assert_eq!(merged, registry);
+
+    let mut conflicting = Registry::new();
+    conflicting.insert("Struct".to_string(), ContainerFormat::UnitStruct);
+    let error = analyzer::merge_registries(vec![second, conflicting]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Conflicting definitions for container `Struct`"
+    );
+}
+
+#[test]
+fn test_container_kinds() {
+    use analyzer::ContainerKind;
+
+    let registry = test_utils::get_registry().unwrap();
+    let kinds = analyzer::container_kinds(&registry);
+    assert_eq!(kinds.len(), registry.len());
+    assert_eq!(kinds["SerdeData"], ContainerKind::Enum);
+    assert_eq!(kinds["List"], ContainerKind::Enum);
+    assert_eq!(kinds["Struct"], ContainerKind::Struct);
+    assert_eq!(kinds["OtherTypes"], ContainerKind::Struct);
+    assert_eq!(kinds["NewTypeStruct"], ContainerKind::NewType);
+    assert_eq!(kinds["SimpleList"], ContainerKind::NewType);
+    assert_eq!(kinds["TupleStruct"], ContainerKind::Tuple);
+    assert_eq!(kinds["UnitStruct"], ContainerKind::Unit);
+}
+
+#[test]
+fn test_containers_with_floats() {
+    let registry = test_utils::get_registry().unwrap();
+    let containers = analyzer::containers_with_floats(&registry);
+    assert_eq!(
+        containers,
+        btreeset!["List", "PrimitiveTypes", "SerdeData", "Tree"]
+    );
+
+    let float_containers = btreeset!["PrimitiveTypes"];
+    assert!(analyzer::format_contains_float(
+        &Format::Seq(Box::new(Format::Tuple(vec![Format::U8, Format::F32]))),
+        &float_containers
+    ));
+    assert!(analyzer::format_contains_float(
+        &Format::TypeName("PrimitiveTypes".to_string()),
+        &float_containers
+    ));
+    assert!(!analyzer::format_contains_float(
+        &Format::TypeName("Struct".to_string()),
+        &float_containers
+    ));
+}
+
+#[test]
+fn test_topological_iter() {
+    let mut registries = vec![test_utils::get_registry().unwrap(), Registry::new()];
+    // A cycle of containers, with a reference to itself.
+    let mut registry = Registry::new();
+    registry.insert(
+        "A".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("B".to_string()))),
+    );
+    registry.insert(
+        "B".to_string(),
+        ContainerFormat::TupleStruct(vec![
+            Format::TypeName("C".to_string()),
+            Format::Seq(Box::new(Format::TypeName("B".to_string()))),
+        ]),
+    );
+    registry.insert(
+        "C".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Option(Box::new(Format::TypeName(
+            "A".to_string(),
+        ))))),
+    );
+    registry.insert("D".to_string(), ContainerFormat::UnitStruct);
+    registries.push(registry);
+
+    for registry in &registries {
+        let dependencies = analyzer::get_dependency_map(registry).unwrap();
+        let expected = analyzer::best_effort_topological_sort(&dependencies);
+        let entries = analyzer::topological_iter(registry).unwrap();
+        assert_eq!(entries.children(), &dependencies);
+        assert_eq!(entries.collect::<Vec<_>>(), expected);
+    }
+
+    // Generic graphs are supported as well.
+    let children = btreemap! {
+        1 => btreeset![2, 3],
+        2 => btreeset![1],
+        3 => btreeset![],
+    };
+    assert_eq!(
+        analyzer::TopologicalIter::new(children.clone()).collect::<Vec<_>>(),
+        analyzer::best_effort_topological_sort(&children)
+    );
+}
+
+#[test]
+fn test_check_variant_indices() {
+    use serde_reflection::{Named, VariantFormat};
+
+    let registry = test_utils::get_registry().unwrap();
+    assert!(analyzer::check_variant_indices(&registry).is_ok());
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Choice".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "A".to_string(), value: VariantFormat::Unit },
+            2 => Named { name: "B".to_string(), value: VariantFormat::Unit },
+        }),
+    );
+    let message = "Non-contiguous variant indices in enum `Choice`: expected 1, found 2";
+    let error = analyzer::check_variant_indices(&registry).unwrap_err();
+    assert_eq!(error.to_string(), message);
+}
+
+#[test]
+fn test_to_dot() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "List".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Option(Box::new(Format::Tuple(vec![
+            Format::TypeName("Item".to_string()),
+            Format::TypeName("List".to_string()),
+        ]))))),
+    );
+    registry.insert(
+        "Item".to_string(),
+        ContainerFormat::TupleStruct(vec![Format::U32, Format::TypeName("Label".to_string())]),
+    );
+    registry.insert(
+        "Label".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+    assert_eq!(
+        analyzer::to_dot(&registry).unwrap(),
+        r#"digraph {
+    "Item";
+    "Label";
+    "List" [color=red];
+    "Item" -> "Label";
+    "List" -> "Item";
+    "List" -> "List";
+}
+"#
+    );
+}
+
+#[test]
+fn test_analyze() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Tree".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Seq(Box::new(Format::TypeName(
+            "Node".to_string(),
+        ))))),
+    );
+    registry.insert(
+        "Node".to_string(),
+        ContainerFormat::TupleStruct(vec![
+            Format::TypeName("Label".to_string()),
+            Format::TypeName("Tree".to_string()),
+        ]),
+    );
+    registry.insert(
+        "Label".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+    let analysis = analyzer::analyze(&registry).unwrap();
+    assert_eq!(
+        analysis.dependencies,
+        btreemap! {
+            "Label" => btreeset![],
+            "Node" => btreeset!["Label", "Tree"],
+            "Tree" => btreeset!["Node"],
+        }
+    );
+    assert_eq!(analysis.sorted_names, vec!["Label", "Tree", "Node"]);
+    assert_eq!(analysis.recursive_names, btreeset!["Node", "Tree"]);
+}
+
+#[test]
+fn test_diff_registries() {
+    let old = test_utils::get_registry().unwrap();
+    assert!(analyzer::diff_registries(&old, &old).is_empty());
+
+    // An added field and an added container.
+    let mut new = old.clone();
+    if let Some(ContainerFormat::Struct(fields)) = new.get_mut("Struct") {
+        fields.push(Named {
+            name: "z".to_string(),
+            value: Format::U8,
+        });
+    }
+    new.insert("Extra".to_string(), ContainerFormat::UnitStruct);
+    let diff = analyzer::diff_registries(&old, &new);
+    assert_eq!(diff.added_containers, vec!["Extra".to_string()]);
+    assert!(diff.removed_containers.is_empty());
+    assert_eq!(
+        diff.changed_containers,
+        btreemap! {
+            "Struct".to_string() => analyzer::ContainerDiff {
+                added_fields: vec!["z".to_string()],
+                ..Default::default()
+            },
+        }
+    );
+    // Containers that reference `Struct` are incompatible as well.
+    assert_eq!(
+        diff.bincode_incompatible_containers
+            .iter()
+            .collect::<Vec<_>>(),
+        vec!["List", "OtherTypes", "SerdeData", "Struct", "Tree"]
+    );
+
+    // A removed variant.
+    let mut new = old.clone();
+    if let Some(ContainerFormat::Enum(variants)) = new.get_mut("CStyleEnum") {
+        variants.remove(&4);
+    }
+    let diff = analyzer::diff_registries(&old, &new);
+    assert_eq!(
+        diff.changed_containers["CStyleEnum"].removed_variants,
+        vec!["E".to_string()]
+    );
+    assert_eq!(
+        diff.bincode_incompatible_containers
+            .iter()
+            .collect::<Vec<_>>(),
+        vec!["CStyleEnum", "List", "SerdeData", "Tree"]
+    );
+
+    // Conversely, adding a variant at the end is compatible.
+    let diff = analyzer::diff_registries(&new, &old);
+    assert_eq!(
+        diff.changed_containers["CStyleEnum"].added_variants,
+        vec!["E".to_string()]
+    );
+    assert!(diff.bincode_incompatible_containers.is_empty());
+}
+
+#[test]
+fn test_diff_registries_with_renamed_members() {
+    let old = test_utils::get_registry().unwrap();
+    let mut new = old.clone();
+    if let Some(ContainerFormat::Struct(fields)) = new.get_mut("Struct") {
+        fields[0].name = "w".to_string();
+    }
+    if let Some(ContainerFormat::Enum(variants)) = new.get_mut("CStyleEnum") {
+        variants.get_mut(&0).unwrap().name = "Z".to_string();
+    }
+    let diff = analyzer::diff_registries(&old, &new);
+    assert_eq!(
+        diff.changed_containers,
+        btreemap! {
+            "CStyleEnum".to_string() => analyzer::ContainerDiff {
+                added_variants: vec!["Z".to_string()],
+                removed_variants: vec!["A".to_string()],
+                ..Default::default()
+            },
+            "Struct".to_string() => analyzer::ContainerDiff {
+                added_fields: vec!["w".to_string()],
+                removed_fields: vec!["x".to_string()],
+                ..Default::default()
+            },
+        }
+    );
+    // Fields and variants are encoded by position and index.
+    assert!(diff.bincode_incompatible_containers.is_empty());
+
+    // Changing the format of a newtype struct is only reported for the struct, but breaks the
+    // containers that reference it.
+    let mut new = old.clone();
+    new.insert(
+        "NewTypeStruct".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::U32)),
+    );
+    let diff = analyzer::diff_registries(&old, &new);
+    assert_eq!(
+        diff.changed_containers.keys().collect::<Vec<_>>(),
+        vec!["NewTypeStruct"]
+    );
+    assert_eq!(
+        diff.bincode_incompatible_containers
+            .iter()
+            .collect::<Vec<_>>(),
+        vec!["List", "NewTypeStruct", "SerdeData", "Tree"]
+    );
+}
+
+#[test]
+fn test_diff_registries_with_reordered_members() {
+    let old = test_utils::get_registry().unwrap();
+    let mut new = old.clone();
+    if let Some(ContainerFormat::Enum(variants)) = new.get_mut("CStyleEnum") {
+        let a = variants.remove(&0).unwrap();
+        let b = variants.remove(&1).unwrap();
+        variants.insert(0, b);
+        variants.insert(1, a);
+    }
+    if let Some(ContainerFormat::Struct(fields)) = new.get_mut("Struct") {
+        fields.reverse();
+    }
+    let diff = analyzer::diff_registries(&old, &new);
+    assert_eq!(
+        diff.changed_containers,
+        btreemap! {
+            "CStyleEnum".to_string() => analyzer::ContainerDiff {
+                reindexed_variants: vec!["A".to_string(), "B".to_string()],
+                ..Default::default()
+            },
+            "Struct".to_string() => analyzer::ContainerDiff {
+                reordered_fields: true,
+                ..Default::default()
+            },
+        }
+    );
+    assert_eq!(
+        diff.bincode_incompatible_containers
+            .iter()
+            .collect::<Vec<_>>(),
+        vec![
+            "CStyleEnum",
+            "List",
+            "OtherTypes",
+            "SerdeData",
+            "Struct",
+            "Tree"
+        ]
+    );
+
+    // Changing the kind of a container.
+    new.insert(
+        "Struct".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "X".to_string(),
+                value: VariantFormat::Unit,
+            },
+        }),
+    );
+    let diff = analyzer::diff_registries(&old, &new);
+    assert!(diff.changed_containers["Struct"].kind_changed);
+}
+
+#[test]
+fn test_visit_formats_and_containers() {
+    let registry = test_utils::get_registry().unwrap();
+    let mut count = 0;
+    analyzer::visit_formats(&registry, &mut |format| {
+        if format == &Format::U64 {
+            count += 1;
+        }
+    })
+    .unwrap();
+    // `TupleVariant`, `f_u64`, `f_intset`, `NewTypeStruct`, `TupleStruct`, and `Struct`.
+    assert_eq!(count, 6);
+
+    let mut names = Vec::new();
+    analyzer::visit_containers(&registry, &mut |name, format| {
+        if let ContainerFormat::Enum(_) = format {
+            names.push(name);
+        }
+    });
+    assert_eq!(names, vec!["CStyleEnum", "List", "SerdeData"]);
+}
+
+#[test]
+fn test_to_schema_json() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let schema = analyzer::to_schema_json(&registry).unwrap();
+    assert_eq!(
+        schema,
+        serde_json::json!({
+            "version": 1,
+            "containers": [
+                {
+                    "name": "Choice",
+                    "kind": "enum",
+                    "variants": [
+                        {"name": "A", "index": 0, "kind": "unit"},
+                        {"name": "B", "index": 1, "kind": "newtype", "type": {"kind": "u64"}},
+                        {
+                            "name": "C",
+                            "index": 2,
+                            "kind": "struct",
+                            "fields": [{"name": "x", "type": {"kind": "u8"}}],
+                        },
+                    ],
+                },
+                {
+                    "name": "Test",
+                    "kind": "struct",
+                    "fields": [
+                        {"name": "a", "type": {"kind": "seq", "type": {"kind": "u32"}}},
+                        {
+                            "name": "b",
+                            "type": {"kind": "tuple", "types": [{"kind": "i64"}, {"kind": "u64"}]},
+                        },
+                        {"name": "c", "type": {"kind": "ref", "name": "Choice"}},
+                    ],
+                },
+            ],
+        })
+    );
+
+    // All containers of a larger registry are described.
+    let registry = test_utils::get_registry().unwrap();
+    let schema = analyzer::to_schema_json(&registry).unwrap();
+    assert_eq!(
+        schema["containers"].as_array().unwrap().len(),
+        registry.len()
+    );
+}
@@ -230,3 +230,72 @@ int main() {{
         .unwrap();
     assert!(status.success());
 }
+
+#[test]
+fn test_cpp_code_type_mapping() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = cpp::CodeGenerator::new(&config);
+    let mut header = Vec::new();
+    generator.output(&mut header, &registry).unwrap();
+    let content = String::from_utf8_lossy(&header);
+
+    assert!(content.contains("std::optional<testing::Struct> f_option;"));
+    assert!(content.contains("std::map<std::string, uint32_t> f_stringmap;"));
+    assert!(content.contains("std::array<uint32_t, 3> value;"));
+    assert!(content.contains("std::variant<Empty, Node> value;"));
+    // Recursive types are boxed with a copyable smart pointer.
+    assert!(content.contains(
+        "std::tuple<serde::value_ptr<testing::SerdeData>, serde::value_ptr<testing::List>> value;"
+    ));
+}
+
+#[test]
+fn test_that_cpp_code_compiles_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.cpp");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bcs]);
+    cpp::CodeGenerator::new(&config)
+        .output(&mut source, &registry)
+        .unwrap();
+
+    let status = Command::new("clang++")
+        .arg("--std=c++17")
+        .arg("-c")
+        .arg("-o")
+        .arg(dir.path().join("test.o"))
+        .arg("-I")
+        .arg("runtime/cpp")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_cpp_code_with_non_contiguous_variant_indices() {
+    use serde_reflection::{ContainerFormat, Named, Registry, VariantFormat};
+
+    let mut registry = Registry::new();
+    let variant = |name: &str| Named {
+        name: name.to_string(),
+        value: VariantFormat::Unit,
+    };
+    registry.insert(
+        "Choice".to_string(),
+        ContainerFormat::Enum(BTreeMap::from([(0, variant("A")), (2, variant("B"))])),
+    );
+    // The error is returned instead of panicking.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let error = cpp::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Non-contiguous variant indices in enum `Choice`: expected 1, found 2"
+    );
+}
@@ -91,7 +91,42 @@ fn test_that_csharp_code_compiles_with_comments() {
 
     let (_dir, path) = test_that_csharp_code_compiles_with_config(&config);
     let content = std::fs::read_to_string(path.join("SerdeData.cs")).unwrap();
-    assert!(content.contains("/// Some\n    /// comments"));
+    assert!(content.contains("/// <summary>\n    /// Some\n    /// comments\n    /// </summary>\n"));
+}
+
+#[test]
+fn test_csharp_code_with_xml_doc_comments() {
+    use serde_generate::SourceInstaller;
+
+    let registry = test_utils::get_registry().unwrap();
+    let comments = vec![
+        (
+            vec!["Generated".to_string(), "SerdeData".to_string()],
+            "Some data.\n\nSee <List> & <Tree>.".to_string(),
+        ),
+        (
+            vec![
+                "Generated".to_string(),
+                "Struct".to_string(),
+                "x".to_string(),
+            ],
+            "A field.".to_string(),
+        ),
+    ]
+    .into_iter()
+    .collect();
+    let config = CodeGeneratorConfig::new("Generated".to_string()).with_comments(comments);
+    let dir = tempdir().unwrap();
+    let installer = csharp::Installer::new(dir.path().to_path_buf());
+    installer.install_module(&config, &registry).unwrap();
+
+    let path = dir.path().join("Generated");
+    let content = std::fs::read_to_string(path.join("SerdeData.cs")).unwrap();
+    assert!(content.contains(
+        "/// <summary>\n    /// Some data.\n    ///\n    /// See &lt;List&gt; &amp; &lt;Tree&gt;.\n    /// </summary>\n"
+    ));
+    let content = std::fs::read_to_string(path.join("Struct.cs")).unwrap();
+    assert!(content.contains("/// <summary>\n        /// A field.\n        /// </summary>\n"));
 }
 
 #[test]
@@ -114,3 +149,16 @@ fn test_csharp_code_with_external_definitions() {
     let content = std::fs::read_to_string(dir.path().join("Generated/SerdeData.cs")).unwrap();
     assert!(content.contains("foo.TraitHelpers."));
 }
+
+#[test]
+fn test_csharp_code_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("Generated".to_string());
+    csharp::CodeGenerator::new(&config)
+        .write_source_files(dir.path().to_path_buf(), &registry)
+        .unwrap();
+
+    let content = std::fs::read_to_string(dir.path().join("Generated/TraitHelpers.cs")).unwrap();
+    assert!(content.contains("namespace Generated {"));
+}
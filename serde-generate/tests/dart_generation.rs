@@ -123,3 +123,15 @@ fn test_dart_code_compiles_class_enums_for_complex_enums() {
     assert!(generated_c_style.contains("enum CStyleEnum {"));
     assert!(generated_class_style.contains("abstract class List_ {"));
 }
+
+#[test]
+fn test_dart_code_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let installer = dart::Installer::new(dir.path().to_path_buf());
+    installer.install_module(&config, &registry).unwrap();
+
+    let content = read_to_string(dir.path().join("lib/src/testing/testing.dart")).unwrap();
+    assert!(content.contains("library testing_types;"));
+}
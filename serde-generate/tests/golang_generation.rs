@@ -186,3 +186,43 @@ fn test_that_golang_code_compiles_with_custom_code() {
     assert!(content.contains("// custom1"));
     assert!(content.contains("// custom2"));
 }
+
+#[test]
+fn test_golang_code_type_mapping() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = golang::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+
+    assert!(content.contains("\tFOption *Struct\n"));
+    assert!(content.contains("\tFSeq []Struct\n"));
+    assert!(content.contains("\tFStringmap map[string]uint32\n"));
+    assert!(content.contains("type SerdeData__TupleArray [3]uint32\n"));
+    // Enums are interfaces implemented by one struct per variant.
+    assert!(content.contains("type List interface {\n\tisList()\n"));
+    assert!(content.contains("func (*List__Node) isList() {}\n"));
+}
+
+#[test]
+fn test_golang_code_with_empty_registry() {
+    let config = CodeGeneratorConfig::new("main".to_string()).with_encodings(vec![Encoding::Bcs]);
+    let generator = golang::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator
+        .output(&mut source, &get_empty_registry().unwrap())
+        .unwrap();
+    let content = String::from_utf8(source).unwrap();
+    // Unused imports would not compile.
+    assert_eq!(content.trim(), "package main");
+}
+
+#[test]
+fn test_that_golang_code_compiles_with_empty_registry() {
+    let config = CodeGeneratorConfig::new("main".to_string()).with_encodings(vec![Encoding::Bcs]);
+    test_that_golang_code_compiles_with_config_and_registry(
+        &config,
+        &get_empty_registry().unwrap(),
+    );
+}
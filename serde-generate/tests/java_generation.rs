@@ -123,3 +123,56 @@ fn test_that_java_code_compiles_with_custom_code() {
     let content = std::fs::read_to_string(path.join("SerdeData.java")).unwrap();
     assert!(content.contains("me()"));
 }
+
+#[test]
+fn test_java_code_type_mapping() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = java::CodeGenerator::new(&config);
+    generator
+        .write_source_files(dir.path().to_path_buf(), &registry)
+        .unwrap();
+
+    // One file per container.
+    for name in registry.keys() {
+        assert!(dir.path().join(format!("testing/{}.java", name)).exists());
+    }
+    let other_types = std::fs::read_to_string(dir.path().join("testing/OtherTypes.java")).unwrap();
+    assert!(other_types.contains("public final class OtherTypes {"));
+    assert!(other_types.contains("public final java.util.Optional<Struct> f_option;"));
+    assert!(other_types.contains("public final java.util.List<Struct> f_seq;"));
+    assert!(other_types.contains(
+        "public final java.util.Map<String, @com.novi.serde.Unsigned Integer> f_stringmap;"
+    ));
+    let list = std::fs::read_to_string(dir.path().join("testing/List.java")).unwrap();
+    assert!(list.contains("public abstract class List {"));
+    assert!(list.contains("public static final class Node extends List {"));
+    let serde_data = std::fs::read_to_string(dir.path().join("testing/SerdeData.java")).unwrap();
+    assert!(serde_data.contains("@com.novi.serde.ArrayLen(length=3) List<"));
+}
+
+#[test]
+fn test_that_java_code_compiles_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let dir = tempdir().unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_encodings(vec![Encoding::Bincode, Encoding::Bcs]);
+    java::CodeGenerator::new(&config)
+        .write_source_files(dir.path().to_path_buf(), &registry)
+        .unwrap();
+
+    let paths = std::iter::empty()
+        .chain(std::fs::read_dir("runtime/java/com/novi/serde").unwrap())
+        .chain(std::fs::read_dir(dir.path().join("testing")).unwrap())
+        .map(|e| e.unwrap().path());
+    let status = Command::new("javac")
+        .arg("-Xlint")
+        .arg("-d")
+        .arg(dir.path())
+        .args(paths)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
@@ -29,6 +29,8 @@ mod python_generation;
 mod python_runtime;
 #[cfg(feature = "rust")]
 mod rust_generation;
+#[cfg(feature = "rust-roundtrip-tests")]
+mod rust_roundtrip;
 #[cfg(feature = "rust")]
 mod rust_runtime;
 #[cfg(feature = "swift")]
@@ -160,3 +160,40 @@ fn test_that_ocaml_code_compiles_with_custom_code() {
     let content = std::fs::read_to_string(&source_path).unwrap();
     assert!(content.contains("serde_data_to_string"));
 }
+
+#[test]
+fn test_ocaml_code_with_recursive_type_groups() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = ocaml::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+
+    // Mutually recursive types are grouped with `and`.
+    assert_eq!(content.matches("\nand ").count(), 2);
+    assert!(content.contains("\ntype list_ =\n"));
+    assert!(content.contains("\nand serde_data =\n"));
+    assert!(content.contains("\nand tree = {\n"));
+    // Other types are defined before the types that use them.
+    assert!(
+        content.find("type struct_ = {").unwrap() < content.find("type other_types = {").unwrap()
+    );
+    assert!(content.find("type other_types = {").unwrap() < content.find("type list_ =").unwrap());
+    assert_eq!(
+        content.matches("[@@deriving serde]").count(),
+        registry.len() - 2
+    );
+}
+
+#[test]
+fn test_ocaml_code_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let mut source = Vec::new();
+    ocaml::CodeGenerator::new(&config)
+        .output(&mut source, &registry)
+        .unwrap();
+    // An empty OCaml module is valid.
+    assert!(String::from_utf8(source).unwrap().trim().is_empty());
+}
@@ -119,6 +119,57 @@ fn test_python_code_with_external_definitions() {
     assert!(!content.contains("value: Tree"));
 }
 
+#[test]
+fn test_python_code_with_external_dependencies_and_comments() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let mut definitions = BTreeMap::new();
+    definitions.insert("pkg.foo".to_string(), vec!["Tree".to_string()]);
+    let mut comments = BTreeMap::new();
+    comments.insert(
+        vec!["testing".to_string(), "Struct".to_string()],
+        "A struct".to_string(),
+    );
+    comments.insert(
+        vec!["testing".to_string(), "Struct".to_string(), "x".to_string()],
+        "The first field".to_string(),
+    );
+    python3::output_with_external_dependencies_and_comments(
+        &mut source,
+        &registry,
+        "testing",
+        definitions,
+        comments,
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("from pkg import foo"));
+    assert!(content.contains("value: foo.Tree"));
+    assert!(content.contains(
+        r#"class Struct:
+    """A struct
+    """
+    x: st.uint32
+    """The first field
+    """
+    y: st.uint64
+"#
+    ));
+
+    let status = Command::new("python3")
+        .arg("-m")
+        .arg("py_compile")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
 #[test]
 fn test_that_python_code_parses_with_custom_code() {
     let custom_code = vec![
@@ -219,3 +270,512 @@ fn test_python_autotest() {
         .unwrap();
     assert!(status.success());
 }
+
+#[test]
+fn test_that_python_code_parses_with_future_annotations() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config).with_future_annotations(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.starts_with("# pyre-strict\nfrom __future__ import annotations\n"));
+    assert!(content.contains("value: typing.Optional[SimpleList]\n"));
+
+    let python_path = format!(
+        "{}:runtime/python",
+        std::env::var("PYTHONPATH").unwrap_or_default()
+    );
+    let status = Command::new("python3")
+        .arg(&source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_with_strict_typing_runs() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let source_path = dir.path().join("testing.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bcs]);
+    let generator = python3::CodeGenerator::new(&config).with_strict_typing(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.starts_with("# pyre-strict\nfrom __future__ import annotations\n"));
+    assert!(content.contains("f_nested_seq: typing.List[typing.List[Struct]]\n"));
+    assert!(content.contains("return typing.cast(SerdeData, v)\n"));
+
+    let python_path = format!(
+        "{}:runtime/python:{}",
+        std::env::var("PYTHONPATH").unwrap_or_default(),
+        dir.path().to_string_lossy(),
+    );
+    let status = Command::new("python3")
+        .arg("-c")
+        .arg(
+            r#"
+import testing
+value = testing.SerdeData__UnitVector([None, None])
+assert testing.SerdeData.bcs_deserialize(value.bcs_serialize()) == value
+"#,
+        )
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_with_strict_typing_passes_mypy_check() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let source_path = dir.path().join("testing.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_encodings(vec![Encoding::Bincode, Encoding::Bcs]);
+    let generator = python3::CodeGenerator::new(&config).with_strict_typing(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    // Only report errors in the generated module, not in the runtimes.
+    let status = Command::new("python3")
+        .arg("-m")
+        .arg("mypy")
+        .arg("--strict")
+        .arg("--follow-imports=silent")
+        .arg(&source_path)
+        .env("MYPYPATH", "runtime/python")
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_with_default_values_runs() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+
+    let source_path = dir.path().join("testing.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config).with_default_values(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    // Only trailing fields have default values.
+    assert!(content.contains(
+        r#"    f_option: typing.Optional["Struct"]
+    f_unit: st.unit
+    f_seq: typing.Sequence["Struct"]
+    f_tuple: typing.Tuple[st.uint8, st.uint16]
+    f_stringmap: typing.Dict[str, st.uint32] = dataclasses.field(default_factory=dict)
+    f_intset: typing.Dict[st.uint64, st.unit] = dataclasses.field(default_factory=dict)
+    f_nested_seq: typing.Sequence[typing.Sequence["Struct"]] = dataclasses.field(default_factory=list)
+"#
+    ));
+    assert!(content.contains("    f_char: typing.Optional[st.char] = None\n"));
+
+    let python_path = format!(
+        "{}:runtime/python:{}",
+        std::env::var("PYTHONPATH").unwrap_or_default(),
+        dir.path().to_string_lossy(),
+    );
+    let status = Command::new("python3")
+        .arg("-c")
+        .arg(
+            r#"
+import serde_types as st
+import testing
+value = testing.OtherTypes(
+    f_string="",
+    f_bytes=b"",
+    f_option=None,
+    f_unit=None,
+    f_seq=[],
+    f_tuple=(st.uint8(0), st.uint16(1)),
+)
+assert value.f_stringmap == {}
+assert value.f_intset == {}
+assert value.f_nested_seq == []
+"#,
+        )
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_parses_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let dir = tempdir().unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_encodings(vec![Encoding::Bincode, Encoding::Bcs]);
+    let installer = python3::Installer::new(dir.path().to_path_buf(), None);
+    installer.install_module(&config, &registry).unwrap();
+    installer.install_serde_runtime().unwrap();
+    installer.install_bincode_runtime().unwrap();
+    installer.install_bcs_runtime().unwrap();
+
+    let content = std::fs::read_to_string(dir.path().join("testing/__init__.py")).unwrap();
+    assert!(content.starts_with("# pyre-strict\n"));
+
+    let python_path = format!(
+        "{}:{}",
+        std::env::var("PYTHONPATH").unwrap_or_default(),
+        dir.path().to_string_lossy(),
+    );
+    let status = Command::new("python3")
+        .arg("-c")
+        .arg("import testing")
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_with_file_per_container_runs() {
+    use serde_reflection::{ContainerFormat, Format, Named, Registry};
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "TreeNode".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "value".to_string(),
+                value: Format::U32,
+            },
+            Named {
+                name: "children".to_string(),
+                value: Format::Seq(Box::new(Format::TypeName("Child".to_string()))),
+            },
+        ]),
+    );
+    registry.insert(
+        "Child".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("TreeNode".to_string()))),
+    );
+    let dir = tempdir().unwrap();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bcs]);
+    let installer =
+        python3::Installer::new(dir.path().to_path_buf(), None).with_file_per_container(true);
+    installer.install_module(&config, &registry).unwrap();
+    installer.install_serde_runtime().unwrap();
+    installer.install_bcs_runtime().unwrap();
+
+    let init = std::fs::read_to_string(dir.path().join("testing/__init__.py")).unwrap();
+    assert_eq!(
+        init,
+        r#"# pyre-strict
+from .tree_node import TreeNode
+from .child import Child
+
+__all__ = ["TreeNode", "Child"]
+"#
+    );
+    let content = std::fs::read_to_string(dir.path().join("testing/tree_node.py")).unwrap();
+    assert!(content.starts_with("# pyre-strict\n"));
+    assert!(content.ends_with("\nfrom .child import Child\n"));
+
+    let python_path = format!(
+        "{}:{}",
+        std::env::var("PYTHONPATH").unwrap_or_default(),
+        dir.path().to_string_lossy(),
+    );
+    let status = Command::new("python3")
+        .arg("-c")
+        .arg(
+            r#"
+from testing import Child, TreeNode
+leaf = TreeNode(value=2, children=[])
+tree = TreeNode(value=1, children=[Child(value=leaf)])
+assert TreeNode.bcs_deserialize(tree.bcs_serialize()) == tree
+assert Child.bcs_deserialize(Child(value=tree).bcs_serialize()) == Child(value=tree)
+"#,
+        )
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_with_keyword_field_names_runs() {
+    use serde_reflection::{ContainerFormat, Format, Named, Registry};
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Item".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "class".to_string(),
+                value: Format::Str,
+            },
+            Named {
+                name: "id".to_string(),
+                value: Format::U32,
+            },
+            Named {
+                name: "lambda".to_string(),
+                value: Format::Option(Box::new(Format::U8)),
+            },
+        ]),
+    );
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("testing.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let generator = python3::CodeGenerator::new(&config).with_default_values(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("import dataclasses\n"));
+    assert!(content.contains(
+        r#"    class_: str = dataclasses.field(metadata={"serde_name": "class"})
+    id: st.uint32
+    lambda_: typing.Optional[st.uint8] = dataclasses.field(default=None, metadata={"serde_name": "lambda"})
+"#
+    ));
+
+    let python_path = format!(
+        "{}:runtime/python:{}",
+        std::env::var("PYTHONPATH").unwrap_or_default(),
+        dir.path().to_string_lossy(),
+    );
+    let status = Command::new("python3")
+        .arg("-c")
+        .arg(
+            r#"
+import serde_json
+import serde_types as st
+import testing
+value = testing.Item(class_="A", id=st.uint32(1))
+assert testing.Item.bincode_deserialize(value.bincode_serialize()) == value
+content = serde_json.serialize(value, testing.Item)
+assert content == '{"class":"A","id":1,"lambda":null}', content
+assert serde_json.deserialize(content, testing.Item) == value
+"#,
+        )
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_python_code_with_variant_repr_runs() {
+    use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
+
+    let mut variants = BTreeMap::new();
+    variants.insert(
+        0,
+        Named {
+            name: "Point".to_string(),
+            value: VariantFormat::Unit,
+        },
+    );
+    variants.insert(
+        1,
+        Named {
+            name: "Label".to_string(),
+            value: VariantFormat::NewType(Box::new(Format::Str)),
+        },
+    );
+    variants.insert(
+        2,
+        Named {
+            name: "Pair".to_string(),
+            value: VariantFormat::Tuple(vec![Format::Str, Format::Bool]),
+        },
+    );
+    variants.insert(
+        3,
+        Named {
+            name: "Circle".to_string(),
+            value: VariantFormat::Struct(vec![
+                Named {
+                    name: "class".to_string(),
+                    value: Format::Str,
+                },
+                Named {
+                    name: "radius".to_string(),
+                    value: Format::Option(Box::new(Format::Str)),
+                },
+            ]),
+        },
+    );
+    variants.insert(
+        4,
+        Named {
+            name: "Nested".to_string(),
+            value: VariantFormat::NewType(Box::new(Format::TypeName("Shape".to_string()))),
+        },
+    );
+    let mut registry = Registry::new();
+    registry.insert("Shape".to_string(), ContainerFormat::Enum(variants));
+
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("testing.py");
+    let mut source = File::create(&source_path).unwrap();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let generator = python3::CodeGenerator::new(&config).with_variant_repr(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains(
+        r#"    def __repr__(self) -> str:
+        return f"Shape.Pair({self.value[0]!r}, {self.value[1]!r})"
+"#
+    ));
+
+    let python_path = format!(
+        "{}:runtime/python:{}",
+        std::env::var("PYTHONPATH").unwrap_or_default(),
+        dir.path().to_string_lossy(),
+    );
+    let status = Command::new("python3")
+        .arg("-c")
+        .arg(
+            r#"
+from testing import *
+assert repr(Shape__Point()) == "Shape.Point()"
+assert repr(Shape__Label("a")) == "Shape.Label('a')"
+assert repr(Shape__Pair(("a", True))) == "Shape.Pair('a', True)"
+circle = Shape__Circle(class_="c", radius=None)
+assert repr(circle) == "Shape.Circle(class_='c', radius=None)"
+assert repr(Shape__Nested(circle)) == "Shape.Nested(Shape.Circle(class_='c', radius=None))"
+# Variants are still frozen dataclasses.
+assert Shape.bincode_deserialize(circle.bincode_serialize()) == circle
+"#,
+        )
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_code_with_invalid_container_name() {
+    use serde_reflection::{ContainerFormat, Registry};
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config);
+    for name in ["1Thing", "My Thing", "None"] {
+        let mut registry = Registry::new();
+        registry.insert(name.to_string(), ContainerFormat::UnitStruct);
+        let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Container name `{}` is not a valid identifier in Python",
+                name
+            )
+        );
+    }
+}
+
+#[test]
+fn test_python_code_with_unresolved_formats() {
+    use serde_reflection::{ContainerFormat, Named, Registry, VariantFormat};
+
+    let mut registry = Registry::new();
+    registry.insert(
+        "Choice".to_string(),
+        ContainerFormat::Enum(
+            vec![(
+                0,
+                Named {
+                    name: "A".to_string(),
+                    value: VariantFormat::unknown(),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    // The error is returned instead of panicking.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let error = python3::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Unresolved format variable at Choice::A");
+}
+
+#[test]
+fn test_python_code_with_non_contiguous_variant_indices() {
+    use serde_reflection::{ContainerFormat, Named, Registry, VariantFormat};
+
+    let mut registry = Registry::new();
+    let variant = |name: &str| Named {
+        name: name.to_string(),
+        value: VariantFormat::Unit,
+    };
+    registry.insert(
+        "Choice".to_string(),
+        ContainerFormat::Enum(BTreeMap::from([(0, variant("A")), (2, variant("B"))])),
+    );
+    // The error is returned instead of panicking.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let error = python3::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Non-contiguous variant indices in enum `Choice`: expected 1, found 2"
+    );
+}
+
+#[test]
+fn test_python_code_with_c_style_enums_and_keywords() {
+    use serde_reflection::{ContainerFormat, Named, Registry, VariantFormat};
+
+    let variant = |name: &str| Named {
+        name: name.to_string(),
+        value: VariantFormat::Unit,
+    };
+    let mut registry = Registry::new();
+    registry.insert(
+        "Compression".to_string(),
+        ContainerFormat::Enum(BTreeMap::from([(0, variant("None")), (1, variant("Gzip"))])),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_c_style_enums(true);
+    let mut source = Vec::new();
+    python3::CodeGenerator::new(&config)
+        .output(&mut source, &registry)
+        .unwrap();
+    let content = String::from_utf8(source).unwrap();
+    assert!(content.contains("    None_ = 0\n    Gzip = 1\n"));
+
+    // Escaped names are reserved.
+    registry.insert(
+        "Compression".to_string(),
+        ContainerFormat::Enum(BTreeMap::from([(0, variant("None_"))])),
+    );
+    let error = python3::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Variant `None_` of C-style enum `Compression` is reserved for escaping Python keywords"
+    );
+}
@@ -2,8 +2,8 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::test_utils;
-use crate::test_utils::{Choice, Runtime, Test};
-use serde_generate::{python3, CodeGeneratorConfig};
+use crate::test_utils::{CStyleEnum, Choice, Runtime, Test};
+use serde_generate::{python3, CodeGeneratorConfig, Encoding};
 use std::{fs::File, io::Write, process::Command};
 use tempfile::tempdir;
 
@@ -153,3 +153,498 @@ for encoding in negative_encodings:
         .unwrap();
     assert!(status.success());
 }
+
+#[test]
+fn test_python_bcs_runtime_on_c_style_enums() {
+    test_python_runtime_on_c_style_enums(Runtime::Bcs);
+}
+
+#[test]
+fn test_python_bincode_runtime_on_c_style_enums() {
+    test_python_runtime_on_c_style_enums(Runtime::Bincode);
+}
+
+fn test_python_runtime_on_c_style_enums(runtime: Runtime) {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_encodings(vec![runtime.into()])
+        .with_c_style_enums(true);
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let reference = runtime.serialize(&CStyleEnum::C);
+    writeln!(
+        source,
+        r#"
+import enum
+import serde_types as st
+
+input = bytes({1:?})
+value = CStyleEnum.C
+assert isinstance(value, enum.IntEnum)
+assert value == 2
+
+s = value.{0}_serialize()
+assert s == input
+v = CStyleEnum.{0}_deserialize(s)
+assert v is CStyleEnum.C
+
+seen_error = False
+try:
+    CStyleEnum.{0}_deserialize(bytes({2:?}))
+except st.DeserializationError:
+    seen_error = True
+assert seen_error
+
+# Enums with variant data are not affected.
+assert not isinstance(List__Empty(), enum.IntEnum)
+"#,
+        runtime.name(),
+        reference,
+        runtime.serialize(&5u32),
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_bincode_runtime_on_hashable_data() {
+    let runtime = Runtime::Bincode;
+    let registry = test_utils::get_simple_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![runtime.into()]);
+    let generator = python3::CodeGenerator::new(&config).with_hashable(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let reference = runtime.serialize(&Test {
+        a: vec![4, 6],
+        b: (3, 5),
+        c: Choice::C { x: 7 },
+    });
+    writeln!(
+        source,
+        r#"
+input = bytes({1:?})
+value = Test((4, 6), (3, 5), Choice__C(7))
+assert len({{value, Test((4, 6), (3, 5), Choice__C(7))}}) == 1
+
+s = value.{0}_serialize()
+assert s == input
+
+v = Test.{0}_deserialize(s)
+assert v == value
+assert v.a == (4, 6)
+assert len({{v, value}}) == 1
+"#,
+        runtime.name(),
+        reference,
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_json_runtime_on_simple_data() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let references = [Choice::A, Choice::B(u64::MAX), Choice::C { x: 7 }]
+        .into_iter()
+        .map(|c| {
+            serde_json::to_string(&Test {
+                a: vec![4, 6],
+                b: (3, 5),
+                c,
+            })
+            .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(references[2], r#"{"a":[4,6],"b":[3,5],"c":{"C":{"x":7}}}"#);
+    std::fs::write(dir.path().join("input.txt"), references.join("\n")).unwrap();
+
+    writeln!(
+        source,
+        r#"
+import serde_json
+
+with open("{}") as f:
+    inputs = f.read().split("\n")
+values = [
+    Test([4, 6], (3, 5), Choice__A()),
+    Test([4, 6], (3, 5), Choice__B(2**64 - 1)),
+    Test([4, 6], (3, 5), Choice__C(7)),
+]
+
+for input, value in zip(inputs, values):
+    v = serde_json.deserialize(input, Test)
+    assert v == value
+    assert serde_json.serialize(v, Test) == input
+
+seen_error = False
+try:
+    serde_json.deserialize('{{"a":[4,6],"b":[3,5],"c":"D"}}', Test)
+except st.DeserializationError:
+    seen_error = True
+assert seen_error
+"#,
+        dir.path().join("input.txt").display(),
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_json_runtime_on_supported_types() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    // Empty struct variants are not supported. Some maps have keys that JSON cannot represent.
+    let references = test_utils::get_sample_values(false, false)
+        .iter()
+        .filter(|value| !matches!(value, test_utils::SerdeData::EmptyStructVariant {}))
+        .filter_map(|value| serde_json::to_string(value).ok())
+        .collect::<Vec<_>>();
+    assert!(references.len() > 10);
+    std::fs::write(dir.path().join("input.txt"), references.join("\n")).unwrap();
+
+    writeln!(
+        source,
+        r#"
+import serde_json
+
+with open("{}") as f:
+    inputs = f.read().split("\n")
+
+for input in inputs:
+    v = serde_json.deserialize(input, SerdeData)
+    assert serde_json.serialize(v, SerdeData) == input, input
+"#,
+        dir.path().join("input.txt").display(),
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_bcs_runtime_on_canonical_encodings() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Entries {
+        names: std::collections::BTreeMap<String, u32>,
+        data: Vec<u8>,
+    }
+
+    let mut tracer = serde_reflection::Tracer::new(serde_reflection::TracerConfig::default());
+    tracer.trace_simple_type::<Entries>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_encodings(vec![Encoding::Bcs, Encoding::Bincode]);
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    // Long enough for the sequence length to take two bytes in ULEB128.
+    let value = Entries {
+        names: vec![
+            ("b".to_string(), 2),
+            ("a".to_string(), 1),
+            ("ab".to_string(), 3),
+        ]
+        .into_iter()
+        .collect(),
+        data: vec![7; 200],
+    };
+    let bcs_reference = bcs::to_bytes(&value).unwrap();
+    let bincode_reference = bincode::serialize(&value).unwrap();
+    writeln!(
+        source,
+        r#"
+# Insert map entries in a non-canonical order.
+value = Entries({{"b": 2, "ab": 3, "a": 1}}, [7] * 200)
+
+assert value.bcs_serialize() == bytes({0:?})
+# Bincode keeps the insertion order of maps.
+assert value.bincode_serialize() != bytes({1:?})
+assert Entries(dict(sorted(value.names.items())), value.data).bincode_serialize() == bytes({1:?})
+assert Entries.bcs_deserialize(bytes({0:?})) == value
+
+seen_error = False
+try:
+    # Map entries must be sorted in BCS.
+    Entries.bcs_deserialize(bytes({2:?}))
+except st.DeserializationError:
+    seen_error = True
+assert seen_error
+"#,
+        bcs_reference,
+        bincode_reference,
+        // Swap the first two (single-character) entries in the BCS encoding.
+        {
+            let mut bytes = bcs_reference.clone();
+            bytes.swap(2, 8);
+            bytes
+        },
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_bcs_runtime_on_128_bit_integers() {
+    test_python_runtime_on_128_bit_integers(Runtime::Bcs);
+}
+
+#[test]
+fn test_python_bincode_runtime_on_128_bit_integers() {
+    test_python_runtime_on_128_bit_integers(Runtime::Bincode);
+}
+
+fn test_python_runtime_on_128_bit_integers(runtime: Runtime) {
+    use serde::{Deserialize, Serialize};
+    use serde_reflection::{Samples, Tracer, TracerConfig};
+
+    #[derive(Serialize, Deserialize)]
+    struct Big {
+        u: u128,
+        i: i128,
+        j: i128,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_type::<Big>(&Samples::new()).unwrap();
+    let registry = tracer.registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![runtime.into()]);
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let value = Big {
+        u: u128::MAX - 1,
+        i: i128::MIN,
+        j: (1 << 100) + 3,
+    };
+    let reference = runtime.serialize(&value);
+    writeln!(
+        source,
+        r#"
+input = bytes({1:?})
+v = Big.{0}_deserialize(input)
+assert int(v.u) == {2}
+assert int(v.i) == {3}
+assert int(v.j) == {4}
+value = Big(st.uint128({2}), st.int128({3}), st.int128({4}))
+assert v == value
+assert value.{0}_serialize() == input
+"#,
+        runtime.name(),
+        reference,
+        value.u,
+        value.i,
+        value.j,
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_json_runtime_with_fields_named_value() {
+    use serde::{Deserialize, Serialize};
+    use serde_reflection::{Samples, Tracer, TracerConfig};
+
+    #[derive(Serialize, Deserialize)]
+    struct Wrapper {
+        value: u32,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Id(u32);
+
+    #[derive(Serialize, Deserialize)]
+    enum Event {
+        Named { value: u32 },
+        Raw(u32),
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Message {
+        wrapper: Wrapper,
+        id: Id,
+        events: Vec<Event>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let samples = Samples::new();
+    tracer.trace_type::<Message>(&samples).unwrap();
+    tracer.trace_type::<Event>(&samples).unwrap();
+    let registry = tracer.registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let reference = serde_json::to_string(&Message {
+        wrapper: Wrapper { value: 1 },
+        id: Id(2),
+        events: vec![Event::Named { value: 3 }, Event::Raw(4)],
+    })
+    .unwrap();
+    assert_eq!(
+        reference,
+        r#"{"wrapper":{"value":1},"id":2,"events":[{"Named":{"value":3}},{"Raw":4}]}"#
+    );
+
+    writeln!(
+        source,
+        r#"
+import serde_json
+
+input = '{}'
+value = Message(Wrapper(1), Id(2), [Event__Named(3), Event__Raw(4)])
+assert serde_json.deserialize(input, Message) == value
+assert serde_json.serialize(value, Message) == input
+"#,
+        reference
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_python_json_runtime_on_c_style_enums_with_keywords() {
+    use serde::{Deserialize, Serialize};
+    use serde_reflection::{Samples, Tracer, TracerConfig};
+
+    #[derive(Serialize, Deserialize)]
+    enum Compression {
+        None,
+        Gzip,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Settings {
+        compressions: Vec<Compression>,
+    }
+
+    let mut tracer = Tracer::new(TracerConfig::default());
+    let samples = Samples::new();
+    tracer.trace_type::<Settings>(&samples).unwrap();
+    tracer.trace_type::<Compression>(&samples).unwrap();
+    let registry = tracer.registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.py");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_encodings(vec![Encoding::Bcs])
+        .with_c_style_enums(true);
+    let generator = python3::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let reference = serde_json::to_string(&Settings {
+        compressions: vec![Compression::None, Compression::Gzip],
+    })
+    .unwrap();
+    assert_eq!(reference, r#"{"compressions":["None","Gzip"]}"#);
+
+    writeln!(
+        source,
+        r#"
+import serde_json
+
+input = '{}'
+value = Settings([Compression.None_, Compression.Gzip])
+assert Compression.None_ == 0
+assert serde_json.deserialize(input, Settings) == value
+assert serde_json.serialize(value, Settings) == input
+assert Settings.bcs_deserialize(value.bcs_serialize()) == value
+"#,
+        reference
+    )
+    .unwrap();
+
+    let python_path = std::env::var("PYTHONPATH").unwrap_or_default() + ":runtime/python";
+    let status = Command::new("python3")
+        .arg(source_path)
+        .env("PYTHONPATH", python_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
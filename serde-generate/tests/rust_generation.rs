@@ -2,8 +2,19 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::test_utils;
-use serde_generate::{rust, CodeGeneratorConfig};
-use std::{fs::File, io::Write, process::Command};
+use maplit::btreemap;
+use serde_generate::{
+    analyzer,
+    rust::{self, EnumRepresentation, MapRepresentation, SeqRepresentation, Visibility},
+    CodeGeneratorConfig, Encoding, SourceInstaller,
+};
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::Write,
+    process::Command,
+};
 use tempfile::{tempdir, TempDir};
 
 // Quick test using rustc directly.
@@ -167,3 +178,3483 @@ fn test_that_rust_code_compiles_with_custom_code() {
     let content = std::fs::read_to_string(&source_path).unwrap();
     assert!(content.contains("// custom code\n"));
 }
+
+#[test]
+fn test_rust_code_generator_config_builder() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let comments = vec![(
+        vec!["testing".to_string(), "Test".to_string()],
+        "Some comments".to_string(),
+    )]
+    .into_iter()
+    .collect();
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_serialization(false)
+        .with_comments(comments);
+    assert_eq!(config.module_name(), "testing");
+
+    let generator = rust::CodeGenerator::new(&config)
+        .with_derive_macros(vec!["Clone".to_string()])
+        .with_track_visibility(false);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+
+    assert!(content.contains("/// Some comments\n#[derive(Clone)]\nstruct Test {\n"));
+    assert!(!content.contains("Serialize"));
+    assert!(!content.contains("pub "));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_hash_maps() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator =
+        rust::CodeGenerator::new(&config).with_map_representation(MapRepresentation::HashMap);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("use std::collections::HashMap as Map;\n"));
+    assert!(!content.contains("BTreeMap"));
+    assert!(content.contains("#[derive(Clone, Debug, PartialEq)]\n"));
+    assert!(!content.contains("PartialOrd"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_custom_maps() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let generator = rust::CodeGenerator::new(&config)
+        .with_map_representation(MapRepresentation::Custom("indexmap::IndexMap".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(!content.contains(" as Map;"));
+    assert!(content.contains("f_stringmap: indexmap::IndexMap<String, u32>,"));
+}
+
+#[test]
+fn test_rust_code_with_custom_derive_macros() {
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let registry = test_utils::get_simple_registry().unwrap();
+    let generator = rust::CodeGenerator::new(&config).with_derive_macros(
+        vec![
+            "Clone",
+            "Debug",
+            "PartialEq",
+            "Eq",
+            "Hash",
+            "Serialize",
+            "Hash",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect(),
+    );
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        "#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]\npub enum Choice {\n"
+    ));
+    assert!(!content.contains("PartialOrd"));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_no_std() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config).with_no_std(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.starts_with("#![no_std]\n"));
+    assert!(content.contains("extern crate alloc;\n"));
+    assert!(content.contains("use alloc::collections::BTreeMap as Map;\n"));
+    assert!(content.contains("use alloc::string::String;\n"));
+    assert!(content.contains("use alloc::vec::Vec;\n"));
+    assert!(content.contains("type Bytes = Vec<u8>;\n"));
+    assert!(!content.contains("std::"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_no_std_and_serialization() {
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let registry = test_utils::get_registry().unwrap();
+    let generator = rust::CodeGenerator::new(&config).with_no_std(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("use serde::{Serialize, Deserialize};\n"));
+    assert!(!content.contains("serde_bytes"));
+    assert!(content.contains("type Bytes = Vec<u8>;\n"));
+
+    let generator = rust::CodeGenerator::new(&config)
+        .with_no_std(true)
+        .with_map_representation(MapRepresentation::HashMap);
+    assert!(generator.output(&mut Vec::new(), &registry).is_err());
+}
+
+#[test]
+fn test_rust_code_with_non_exhaustive_containers() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let comments = vec![(
+        vec!["testing".to_string(), "Choice".to_string()],
+        "Some comments".to_string(),
+    )]
+    .into_iter()
+    .collect();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_comments(comments);
+
+    let generator = rust::CodeGenerator::new(&config).with_non_exhaustive_enums(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        "/// Some comments\n#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]\n#[non_exhaustive]\npub enum Choice {\n"
+    ));
+    assert_eq!(content.matches("#[non_exhaustive]").count(), 1);
+
+    let generator = rust::CodeGenerator::new(&config).with_non_exhaustive_structs(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("#[non_exhaustive]\npub struct Test {\n"));
+    assert_eq!(content.matches("#[non_exhaustive]").count(), 1);
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_explicit_discriminants() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config).with_explicit_discriminants(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    // All-unit enums use the Serde variant index.
+    assert!(content.contains("    A = 0,\n    B = 1,\n    C = 2,\n    D = 3,\n    E = 4,\n"));
+    // Other enums are unchanged.
+    assert!(content.contains("    Empty,\n"));
+    assert!(content.contains("    UnitVariant,\n"));
+    assert!(!content.contains("UnitVariant ="));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_c_repr_enums() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config).with_c_repr_enums(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("#[repr(u32)]\npub enum CStyleEnum {\n    A = 0,\n    B = 1,\n"));
+    // Enums with variant data are unchanged.
+    assert_eq!(content.matches("#[repr(u32)]").count(), 1);
+    assert!(!content.contains("UnitVariant ="));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_reachable_subregistry() {
+    let registry = test_utils::get_registry().unwrap();
+    let subregistry = analyzer::reachable_subregistry(&registry, &["OtherTypes"]).unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+    rust::CodeGenerator::new(&config)
+        .output(&mut source, &subregistry)
+        .unwrap();
+
+    // Generated code only depends on the selected containers.
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("pub struct OtherTypes {"));
+    assert!(!content.contains("SerdeData"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_unresolved_formats() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Choice".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "A".to_string(), value: VariantFormat::unknown() },
+        }),
+    );
+    // The error is returned instead of panicking.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let error = rust::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Unresolved format variable at Choice::A");
+}
+
+#[test]
+fn test_rust_code_with_non_contiguous_variant_indices() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Choice".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "A".to_string(), value: VariantFormat::Unit },
+            2 => Named { name: "B".to_string(), value: VariantFormat::Unit },
+        }),
+    );
+    // The error is returned instead of panicking.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let error = rust::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Non-contiguous variant indices in enum `Choice`: expected 1, found 2"
+    );
+}
+
+#[test]
+fn test_rust_code_with_skip_none_fields() {
+    let registry = test_utils::get_registry().unwrap();
+    let comments = vec![(
+        vec![
+            "testing".to_string(),
+            "OtherTypes".to_string(),
+            "f_option".to_string(),
+        ],
+        "Some comments".to_string(),
+    )]
+    .into_iter()
+    .collect();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_comments(comments);
+    let generator = rust::CodeGenerator::new(&config).with_skip_none_fields(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"
+    /// Some comments
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub f_option: Option<Struct>,
+    pub f_unit: (),
+"#
+    ));
+    assert!(content.contains(
+        r#"    pub f_bool: bool,
+    pub f_u8: u8,
+"#
+    ));
+    assert_eq!(
+        content
+            .matches(r#"#[serde(skip_serializing_if = "Option::is_none", default)]"#)
+            .count(),
+        4
+    );
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_default_implementations() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config).with_derive_default(true);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    let derive = "#[derive(Clone, Debug, PartialEq, PartialOrd, Default)]\n";
+    assert!(content.contains(&format!("{}pub struct UnitStruct;\n", derive)));
+    assert!(content.contains(&format!("{}pub struct Struct {{\n", derive)));
+    assert!(content.contains(
+        r#"impl Default for CStyleEnum {
+    fn default() -> Self {
+        Self::A
+    }
+}
+"#
+    ));
+    // The first variant of `SerdeData` has data.
+    assert!(!content.contains("impl Default for SerdeData"));
+    assert!(
+        content.contains("#[derive(Clone, Debug, PartialEq, PartialOrd)]\npub enum SerdeData {\n")
+    );
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_default_implementations_and_type_overrides() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let mut registry = Registry::new();
+    registry.insert("Config".to_string(), make_struct(&[("port", Format::U16)]));
+    registry.insert(
+        "Wrapper".to_string(),
+        make_struct(&[("config", type_name("Config"))]),
+    );
+    registry.insert("Other".to_string(), make_struct(&[("x", Format::U32)]));
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    // Overridden types are not assumed to implement `Default`.
+    let generator = rust::CodeGenerator::new(&config)
+        .with_derive_default(true)
+        .with_type_overrides(vec![(Format::U16, "Port".to_string())]);
+    generator.output(&mut source, &registry).unwrap();
+    writeln!(
+        source,
+        "#[derive(Clone, Debug, PartialEq, PartialOrd)]\npub struct Port(u16);"
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    let derive = "#[derive(Clone, Debug, PartialEq, PartialOrd)]\n";
+    assert!(content.contains(&format!("{}pub struct Config {{\n", derive)));
+    assert!(content.contains(&format!("{}pub struct Wrapper {{\n", derive)));
+    assert!(content
+        .contains("#[derive(Clone, Debug, PartialEq, PartialOrd, Default)]\npub struct Other {\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_recursive_optional_fields() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Node".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "value".to_string(),
+                value: Format::U64,
+            },
+            Named {
+                name: "next".to_string(),
+                value: Format::Option(Box::new(Format::TypeName("Node".to_string()))),
+            },
+        ]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("pub next: Option<Box<Node>>,\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_nested_modules() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "proto.messages.Header".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "kind".to_string(),
+                value: Format::TypeName("proto::types::Kind".to_string()),
+            },
+            Named {
+                name: "payload".to_string(),
+                value: Format::Option(Box::new(Format::TypeName(
+                    "proto.messages.Payload".to_string(),
+                ))),
+            },
+        ]),
+    );
+    registry.insert(
+        "proto.messages.Payload".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Seq(Box::new(Format::TypeName(
+            "Root".to_string(),
+        ))))),
+    );
+    registry.insert(
+        "proto::types::Kind".to_string(),
+        ContainerFormat::Enum(
+            vec![
+                (
+                    0,
+                    Named {
+                        name: "Empty".to_string(),
+                        value: VariantFormat::Unit,
+                    },
+                ),
+                (
+                    1,
+                    Named {
+                        name: "Header".to_string(),
+                        value: VariantFormat::NewType(Box::new(Format::TypeName(
+                            "proto.messages.Header".to_string(),
+                        ))),
+                    },
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    );
+    registry.insert(
+        "Root".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName(
+            "proto::types::Kind".to_string(),
+        ))),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("pub mod proto {\n    use super::*;\n"));
+    assert!(content.contains("    pub mod messages {\n        use super::*;\n"));
+    assert!(content.contains("pub struct Root(pub proto::types::Kind);"));
+    assert!(content.contains("pub kind: super::types::Kind,"));
+    assert!(content.contains("pub struct Payload(pub Vec<super::super::Root>);"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_rename_all() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator =
+        rust::CodeGenerator::new(&config).with_rename_all(Some("camelCase".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"#[serde(rename_all = "camelCase")]
+pub struct Test {"#
+    ));
+    assert!(content.contains(
+        r#"#[serde(rename_all = "camelCase")]
+pub enum Choice {"#
+    ));
+
+    let generator =
+        rust::CodeGenerator::new(&config).with_rename_all(Some("CamelCase".to_string()));
+    let mut source = Vec::new();
+    assert!(generator.output(&mut source, &registry).is_err());
+}
+
+#[test]
+fn test_rust_code_with_type_overrides() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_type_overrides(vec![(
+        Format::F32,
+        "ordered_float::OrderedFloat<f32>".to_string(),
+    )]);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("pub f_f32: Option<ordered_float::OrderedFloat<f32>>,\n"));
+    assert!(content.contains("pub f_f64: Option<f64>,\n"));
+
+    // Container names cannot be overridden.
+    let generator = rust::CodeGenerator::new(&config).with_type_overrides(vec![(
+        Format::TypeName("Struct".to_string()),
+        "my_crate::Struct".to_string(),
+    )]);
+    let mut source = Vec::new();
+    assert!(generator.output(&mut source, &registry).is_err());
+}
+
+#[test]
+fn test_rust_code_with_array_impl_threshold() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Signature".to_string(),
+        ContainerFormat::Struct(vec![Named {
+            name: "bytes".to_string(),
+            value: Format::TupleArray {
+                content: Box::new(Format::U8),
+                size: 64,
+            },
+        }]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+
+    let generator = rust::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("pub struct Signature {\n    pub bytes: [u8; 64],\n}"));
+    assert!(!content.contains("serde_big_array"));
+
+    let generator = rust::CodeGenerator::new(&config).with_array_impl_threshold(Some(32));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub struct Signature {
+    #[serde(with = "serde_big_array::BigArray")]
+    pub bytes: [u8; 64],
+}"#
+    ));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_large_byte_arrays() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "KeyPair".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "public_key".to_string(),
+                value: Format::TupleArray {
+                    content: Box::new(Format::U8),
+                    size: 32,
+                },
+            },
+            Named {
+                name: "private_key".to_string(),
+                value: Format::TupleArray {
+                    content: Box::new(Format::U8),
+                    size: 64,
+                },
+            },
+        ]),
+    );
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_array_impl_threshold(Some(32));
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer
+        .install_module_with_generator(&generator, &registry)
+        .unwrap();
+
+    let crate_path = dir.path().join("testing");
+    let cargo = std::fs::read_to_string(crate_path.join("Cargo.toml")).unwrap();
+    assert!(cargo.contains("serde-big-array = \"0.5\"\n"));
+    let source = std::fs::read_to_string(crate_path.join("src/lib.rs")).unwrap();
+    assert!(source.contains(
+        r#"pub struct KeyPair {
+    pub public_key: [u8; 32],
+    #[serde(with = "serde_big_array::BigArray")]
+    pub private_key: [u8; 64],
+}"#
+    ));
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_installer_with_custom_generator() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let mut definitions = BTreeMap::new();
+    definitions.insert("my_bytes".to_string(), vec!["Bytes".to_string()]);
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_external_definitions(definitions);
+    let generator = rust::CodeGenerator::new(&config)
+        .with_derive_macros(vec!["Clone".to_string(), "Hash".to_string()]);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer
+        .install_module_with_generator(&generator, &registry)
+        .unwrap();
+
+    let source = std::fs::read_to_string(dir.path().join("testing/src/lib.rs")).unwrap();
+    assert!(source.contains("#[derive(Serialize, Deserialize, Clone, Hash)]\npub struct Test {"));
+    assert!(source.contains("use my_bytes::{Bytes};"));
+    let cargo = std::fs::read_to_string(dir.path().join("testing/Cargo.toml")).unwrap();
+    assert!(!cargo.contains("serde_bytes"));
+}
+
+#[test]
+fn test_rust_installer_with_file_per_container() {
+    let mut registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let installer = rust::Installer::new(dir.path().to_path_buf()).with_file_per_container(true);
+    installer.install_module(&config, &registry).unwrap();
+
+    let source_path = dir.path().join("testing/src");
+    let lib = std::fs::read_to_string(source_path.join("lib.rs")).unwrap();
+    assert!(lib.contains("include!(\"SerdeData.rs\");\n"));
+    let definition = std::fs::read_to_string(source_path.join("UnitStruct.rs")).unwrap();
+    assert_eq!(
+        definition,
+        "#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]\npub struct UnitStruct;\n"
+    );
+    let modified = |name: &str| {
+        std::fs::metadata(source_path.join(name))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    let lib_time = modified("lib.rs");
+    let unit_struct_time = modified("UnitStruct.rs");
+    let newtype_struct_time = modified("NewTypeStruct.rs");
+
+    // Make sure that rewritten files would get a different modification time.
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    registry.insert(
+        "NewTypeStruct".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::U32)),
+    );
+    installer.install_module(&config, &registry).unwrap();
+
+    assert_eq!(modified("lib.rs"), lib_time);
+    assert_eq!(modified("UnitStruct.rs"), unit_struct_time);
+    assert_ne!(modified("NewTypeStruct.rs"), newtype_struct_time);
+    let definition = std::fs::read_to_string(source_path.join("NewTypeStruct.rs")).unwrap();
+    assert!(definition.contains("pub struct NewTypeStruct(pub u32);"));
+
+    // Definitions of removed containers are deleted, but not user files.
+    std::fs::write(source_path.join("main.rs"), "fn main() {}\n").unwrap();
+    std::fs::create_dir_all(source_path.join("bin")).unwrap();
+    std::fs::write(source_path.join("bin/tool.rs"), "fn main() {}\n").unwrap();
+    registry.insert("Extra".to_string(), ContainerFormat::UnitStruct);
+    registry.insert("extra::Nested".to_string(), ContainerFormat::UnitStruct);
+    installer.install_module(&config, &registry).unwrap();
+    assert!(source_path.join("Extra.rs").exists());
+    assert!(source_path.join("extra/Nested.rs").exists());
+    registry.remove("Extra");
+    registry.remove("extra::Nested");
+    installer.install_module(&config, &registry).unwrap();
+    assert!(!source_path.join("Extra.rs").exists());
+    assert!(!source_path.join("extra").exists());
+    assert!(source_path.join("main.rs").exists());
+    assert!(source_path.join("bin/tool.rs").exists());
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(dir.path().join("testing"))
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_runs_with_json_value_conversions() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_json_value_conversions(true);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer
+        .install_module_with_generator(&generator, &registry)
+        .unwrap();
+
+    let crate_path = dir.path().join("testing");
+    let cargo = std::fs::read_to_string(crate_path.join("Cargo.toml")).unwrap();
+    assert!(cargo.contains("serde_json = \"1.0\"\n"));
+    let source = std::fs::read_to_string(crate_path.join("src/lib.rs")).unwrap();
+    assert!(source.contains(
+        r#"impl core::convert::From<&UnitStruct> for serde_json::Value {
+    fn from(value: &UnitStruct) -> Self {
+        serde_json::to_value(value).expect("map keys must be representable in JSON")
+    }
+}
+"#
+    ));
+
+    let mut main = File::create(crate_path.join("src/main.rs")).unwrap();
+    writeln!(
+        main,
+        r#"
+use testing::{{NewTypeStruct, Struct, UnitStruct}};
+
+fn main() {{
+    assert_eq!(serde_json::Value::from(&NewTypeStruct(7)), serde_json::json!(7));
+    let value = Struct {{ x: 1, y: 2 }};
+    assert_eq!(serde_json::Value::from(&value), serde_json::json!({{"x": 1, "y": 2}}));
+    assert_eq!(serde_json::Value::from(&UnitStruct), serde_json::Value::Null);
+}}
+"#
+    )
+    .unwrap();
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("run")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_json_value_conversions_and_no_serialization() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_json_value_conversions(true);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "JSON value conversions require serialization to be enabled"
+    );
+}
+
+#[test]
+fn test_rust_installer_plan() {
+    let mut registry = Registry::new();
+    registry.insert("Foo".to_string(), ContainerFormat::UnitStruct);
+    registry.insert(
+        "Bar".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("Foo".to_string()))),
+    );
+    registry.insert(
+        "proto.Qux".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::TypeName("proto::Baz".to_string()))),
+    );
+    registry.insert("proto::Baz".to_string(), ContainerFormat::UnitStruct);
+    let dir = tempdir().unwrap();
+    let crate_path = dir.path().join("testing");
+
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    assert_eq!(
+        installer.plan("testing:0.2.0", &registry),
+        vec![crate_path.join("Cargo.toml"), crate_path.join("src/lib.rs")]
+    );
+
+    let installer = installer.with_file_per_container(true);
+    let paths = installer.plan("testing", &registry);
+    assert_eq!(
+        paths,
+        vec![
+            crate_path.join("Cargo.toml"),
+            crate_path.join("src/lib.rs"),
+            crate_path.join("src/Bar.rs"),
+            crate_path.join("src/Foo.rs"),
+            crate_path.join("src/proto/Qux.rs"),
+            crate_path.join("src/proto/Baz.rs"),
+        ]
+    );
+    assert!(!crate_path.exists());
+
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    installer.install_module(&config, &registry).unwrap();
+    for path in paths {
+        assert!(path.exists());
+    }
+    let lib = std::fs::read_to_string(crate_path.join("src/lib.rs")).unwrap();
+    assert!(lib.contains("include!(\"proto/Baz.rs\");\n"));
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_derive_arbitrary() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+
+    let generator = rust::CodeGenerator::new(&config).with_derive_arbitrary(true);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert!(error
+        .to_string()
+        .ends_with("uses `serde_bytes::ByteBuf`, which does not implement `Arbitrary`"));
+
+    let generator = generator.with_bytes_representation(rust::BytesRepresentation::VecU8);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer
+        .install_module_with_generator(&generator, &registry)
+        .unwrap();
+
+    let crate_path = dir.path().join("testing");
+    let cargo = std::fs::read_to_string(crate_path.join("Cargo.toml")).unwrap();
+    assert!(cargo.contains("arbitrary = { version = \"1\", features = [\"derive\"] }\n"));
+    let source = std::fs::read_to_string(crate_path.join("src/lib.rs")).unwrap();
+    assert!(source.contains("use arbitrary::Arbitrary;\n"));
+    assert!(source.contains(
+        "#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd, Arbitrary)]\npub struct UnitStruct;"
+    ));
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_runs_with_proptest_strategies() {
+    let mut registry = test_utils::get_registry().unwrap();
+    registry.insert(
+        "Segment".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "start".to_string(),
+                value: Format::TypeName("Point".to_string()),
+            },
+            Named {
+                name: "end".to_string(),
+                value: Format::Option(Box::new(Format::TypeName("Point".to_string()))),
+            },
+        ]),
+    );
+    registry.insert(
+        "Point".to_string(),
+        ContainerFormat::TupleStruct(vec![Format::I32, Format::I32]),
+    );
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_proptest_strategies(true);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer
+        .install_module_with_generator(&generator, &registry)
+        .unwrap();
+
+    let crate_path = dir.path().join("testing");
+    let cargo = std::fs::read_to_string(crate_path.join("Cargo.toml")).unwrap();
+    assert!(cargo.contains("proptest = \"1\"\n"));
+    let source = std::fs::read_to_string(crate_path.join("src/lib.rs")).unwrap();
+    assert!(source.contains(
+        r#"pub fn segment_strategy() -> proptest::strategy::BoxedStrategy<Segment> {
+    use proptest::strategy::Strategy;
+    (proptest::strategy::Just(()).prop_flat_map(|_| point_strategy()), proptest::option::of(proptest::strategy::Just(()).prop_flat_map(|_| point_strategy()))).prop_map(|(a0, a1)| Segment { start: a0, end: a1 })
+        .boxed()
+}
+"#
+    ));
+    assert!(source.contains(
+        r#"pub fn c_style_enum_strategy() -> proptest::strategy::BoxedStrategy<CStyleEnum> {
+    use proptest::strategy::Strategy;
+    proptest::prop_oneof![
+        proptest::strategy::LazyJust::new(|| CStyleEnum::A).boxed(),
+"#
+    ));
+
+    let mut main = File::create(crate_path.join("src/main.rs")).unwrap();
+    writeln!(
+        main,
+        r#"
+use proptest::strategy::{{Strategy, ValueTree}};
+use testing::{{segment_strategy, struct_strategy}};
+
+fn main() {{
+    let mut runner = proptest::test_runner::TestRunner::deterministic();
+    for _ in 0..10 {{
+        let segment = segment_strategy().new_tree(&mut runner).unwrap().current();
+        assert_eq!(segment.clone(), segment);
+        let value = struct_strategy().new_tree(&mut runner).unwrap().current();
+        assert_eq!(value.clone(), value);
+    }}
+}}
+"#
+    )
+    .unwrap();
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("run")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_cfg_feature() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config)
+        .with_derive_default(true)
+        .with_numeric_enum_conversions(true)
+        .with_display_for_unit_enums(true)
+        .with_cfg_feature(Some("schema".to_string()));
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert_eq!(
+        content
+            .matches("#[cfg(feature = \"schema\")]\n#[derive(")
+            .count(),
+        registry.len()
+    );
+    assert!(
+        content.contains("#[cfg(feature = \"schema\")]\nuse std::collections::BTreeMap as Map;\n")
+    );
+    assert!(content.contains("#[cfg(feature = \"schema\")]\ntype Bytes = Vec<u8>;\n"));
+    assert!(content.contains("#[cfg(feature = \"schema\")]\nimpl Default for CStyleEnum {"));
+    assert!(
+        content.contains("#[cfg(feature = \"schema\")]\nimpl core::fmt::Display for CStyleEnum {")
+    );
+
+    for features in [vec![], vec!["--cfg", "feature=\"schema\""]] {
+        let status = Command::new("rustc")
+            .current_dir(dir.path())
+            .arg("--crate-type")
+            .arg("lib")
+            .arg("--edition")
+            .arg("2018")
+            .arg("-D")
+            .arg("warnings")
+            .args(features)
+            .arg(&source_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+}
+
+#[test]
+fn test_rust_code_with_comments_on_variant_fields() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let comments = vec![(
+        vec![
+            "testing".to_string(),
+            "Choice".to_string(),
+            "C".to_string(),
+            "x".to_string(),
+        ],
+        "Some comments".to_string(),
+    )]
+    .into_iter()
+    .collect();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_comments(comments);
+    let generator = rust::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"
+    C {
+
+        /// Some comments
+        x: u8,
+    },
+"#
+    ));
+}
+
+#[test]
+fn test_rust_code_with_serde_with_overrides() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Event".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "at".to_string(),
+                value: Format::U64,
+            },
+            Named {
+                name: "until".to_string(),
+                value: Format::Option(Box::new(Format::U64)),
+            },
+        ]),
+    );
+    let mut overrides = BTreeMap::new();
+    for field in ["at", "until"] {
+        overrides.insert(
+            vec![
+                "testing".to_string(),
+                "Event".to_string(),
+                field.to_string(),
+            ],
+            "my_crate::ts_seconds".to_string(),
+        );
+    }
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_skip_none_fields(true)
+        .with_serde_with_overrides(overrides);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub struct Event {
+    #[serde(with = "my_crate::ts_seconds")]
+    pub at: u64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    #[serde(with = "my_crate::ts_seconds")]
+    pub until: Option<u64>,
+}"#
+    ));
+}
+
+#[test]
+fn test_rust_code_with_field_defaults() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Server".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "host".to_string(),
+                value: Format::Str,
+            },
+            Named {
+                name: "port".to_string(),
+                value: Format::U16,
+            },
+            Named {
+                name: "timeout".to_string(),
+                value: Format::Option(Box::new(Format::U64)),
+            },
+        ]),
+    );
+    let mut defaults = BTreeMap::new();
+    for (field, function) in [("port", "default_port"), ("timeout", "default_timeout")] {
+        defaults.insert(
+            vec![
+                "testing".to_string(),
+                "Server".to_string(),
+                field.to_string(),
+            ],
+            function.to_string(),
+        );
+    }
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_skip_none_fields(true)
+        .with_field_defaults(defaults);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub struct Server {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default = "default_timeout")]
+    pub timeout: Option<u64>,
+}"#
+    ));
+}
+
+#[test]
+fn test_rust_code_with_variant_renames() {
+    let mut registry = Registry::new();
+    let mut variants = BTreeMap::new();
+    variants.insert(
+        0,
+        Named {
+            name: "Started".to_string(),
+            value: VariantFormat::Unit,
+        },
+    );
+    variants.insert(
+        1,
+        Named {
+            name: "Stopped".to_string(),
+            value: VariantFormat::Struct(vec![Named {
+                name: "code".to_string(),
+                value: Format::U32,
+            }]),
+        },
+    );
+    registry.insert("Event".to_string(), ContainerFormat::Enum(variants));
+    let mut comments = BTreeMap::new();
+    comments.insert(
+        vec![
+            "testing".to_string(),
+            "Event".to_string(),
+            "Stopped".to_string(),
+        ],
+        "Stopped with an exit code.".to_string(),
+    );
+    let mut renames = BTreeMap::new();
+    renames.insert(
+        vec![
+            "testing".to_string(),
+            "Event".to_string(),
+            "Stopped".to_string(),
+        ],
+        "S".to_string(),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_comments(comments);
+    let generator = rust::CodeGenerator::new(&config).with_variant_renames(renames.clone());
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub enum Event {
+    Started,
+
+    /// Stopped with an exit code.
+    #[serde(rename = "S")]
+    Stopped {
+        code: u32,
+    },
+}"#
+    ));
+
+    // Renames only apply to serialization.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_variant_renames(renames);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    assert!(!String::from_utf8_lossy(&source).contains("rename"));
+}
+
+#[test]
+fn test_that_rust_code_runs_with_numeric_enum_conversions() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config).with_numeric_enum_conversions(true);
+    generator.output(&mut source, &registry).unwrap();
+    // Only enums without variant data are converted.
+    writeln!(
+        source,
+        r#"
+fn main() {{
+    use std::convert::TryFrom;
+    assert_eq!(CStyleEnum::try_from(2), Ok(CStyleEnum::C));
+    assert_eq!(CStyleEnum::try_from(5), Err(()));
+    assert_eq!(u32::from(&CStyleEnum::E), 4);
+}}
+"#
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(!content.contains("for SerdeData"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--edition")
+        .arg("2018")
+        .arg("-o")
+        .arg(dir.path().join("test"))
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new(dir.path().join("test")).status().unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_transparent_newtypes() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_transparent_newtypes(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("#[serde(transparent)]\npub struct NewTypeStruct(pub u64);"));
+    assert!(content.contains(
+        "#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]\npub struct TupleStruct(u32, u64);"
+    ));
+    assert_eq!(content.matches("#[serde(transparent)]").count(), 2);
+
+    // The attribute requires serialization.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_transparent_newtypes(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    assert!(!String::from_utf8_lossy(&source).contains("#[serde(transparent)]"));
+}
+
+fn make_struct(fields: &[(&str, Format)]) -> ContainerFormat {
+    ContainerFormat::Struct(
+        fields
+            .iter()
+            .map(|(name, value)| Named {
+                name: name.to_string(),
+                value: value.clone(),
+            })
+            .collect(),
+    )
+}
+
+fn type_name(name: &str) -> Format {
+    Format::TypeName(name.to_string())
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_minimal_boxing() {
+    let mut registry = Registry::new();
+    // Two-node cycle.
+    registry.insert(
+        "A".to_string(),
+        make_struct(&[("b", Format::Option(Box::new(type_name("B"))))]),
+    );
+    registry.insert(
+        "B".to_string(),
+        make_struct(&[("a", Format::Option(Box::new(type_name("A"))))]),
+    );
+    // Larger cluster where only the cycle `X -> Y -> Z -> X` requires boxing.
+    registry.insert(
+        "X".to_string(),
+        make_struct(&[
+            ("y", type_name("Y")),
+            ("zs", Format::Seq(Box::new(type_name("Z")))),
+        ]),
+    );
+    registry.insert(
+        "Y".to_string(),
+        make_struct(&[("z", Format::Option(Box::new(type_name("Z"))))]),
+    );
+    registry.insert(
+        "Z".to_string(),
+        make_struct(&[
+            ("x", Format::Option(Box::new(type_name("X")))),
+            ("w", type_name("W")),
+        ]),
+    );
+    // `W` refers back to the cluster only through a map.
+    registry.insert(
+        "W".to_string(),
+        make_struct(&[(
+            "xs",
+            Format::Map {
+                key: Box::new(Format::U8),
+                value: Box::new(type_name("X")),
+            },
+        )]),
+    );
+
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let generator = rust::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert_eq!(content.matches("Box<").count(), 2);
+    assert!(content.contains("pub w: W,\n"));
+    assert!(content.contains("pub xs: Map<u8, X>,\n"));
+    assert!(content.contains("pub zs: Vec<Z>,\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_boxes_types_at_most_once() {
+    let mut registry = Registry::new();
+    // Three-node cycle `A -> B -> C -> A` where references are nested in several layers of
+    // options, tuples, arrays, and variants.
+    let nested = |name: &str| {
+        Format::Option(Box::new(Format::Tuple(vec![
+            Format::Option(Box::new(type_name(name))),
+            Format::U8,
+        ])))
+    };
+    registry.insert("A".to_string(), make_struct(&[("b", nested("B"))]));
+    registry.insert(
+        "B".to_string(),
+        make_struct(&[(
+            "c",
+            Format::TupleArray {
+                content: Box::new(nested("C")),
+                size: 2,
+            },
+        )]),
+    );
+    let mut variants = BTreeMap::new();
+    variants.insert(
+        0,
+        Named {
+            name: "Leaf".to_string(),
+            value: VariantFormat::Unit,
+        },
+    );
+    variants.insert(
+        1,
+        Named {
+            name: "Node".to_string(),
+            value: VariantFormat::Tuple(vec![nested("A"), type_name("A")]),
+        },
+    );
+    registry.insert("C".to_string(), ContainerFormat::Enum(variants));
+
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+    let generator = rust::CodeGenerator::new(&config);
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("Box<"));
+    assert!(!content.contains("Box<Box<"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_colliding_external_definitions() {
+    let registry = test_utils::get_registry().unwrap();
+    let mut definitions = BTreeMap::new();
+    definitions.insert(
+        "my_types".to_string(),
+        vec!["Map".to_string(), "Struct".to_string()],
+    );
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_external_definitions(definitions);
+    let generator = rust::CodeGenerator::new(&config);
+
+    let mut out = Vec::new();
+    let error = generator.output(&mut out, &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "External definitions collide with containers of the registry: `Struct` (from module `my_types`)"
+    );
+}
+
+#[test]
+fn test_rust_code_with_visibility() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Point".to_string(),
+        make_struct(&[("x", Format::U32), ("y", Format::U32)]),
+    );
+    registry.insert(
+        "Meters".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::U64)),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+
+    for (visibility, keyword) in [
+        (rust::Visibility::Public, "pub "),
+        (rust::Visibility::Crate, "pub(crate) "),
+        (rust::Visibility::Private, ""),
+    ] {
+        let dir = tempdir().unwrap();
+        let source_path = dir.path().join("test.rs");
+        let mut source = File::create(&source_path).unwrap();
+        let generator = rust::CodeGenerator::new(&config).with_visibility(visibility);
+        generator.output(&mut source, &registry).unwrap();
+
+        let content = std::fs::read_to_string(&source_path).unwrap();
+        assert!(content.contains(&format!("\n{}struct Point {{\n", keyword)));
+        assert!(content.contains(&format!("    {}x: u32,\n", keyword)));
+        assert!(content.contains(&format!("    {}y: u32,\n", keyword)));
+        assert!(content.contains(&format!("\n{}struct Meters({}u64);\n", keyword, keyword)));
+
+        let status = Command::new("rustc")
+            .current_dir(dir.path())
+            .arg("--crate-type")
+            .arg("lib")
+            .arg("--edition")
+            .arg("2018")
+            .arg(&source_path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_wrapper_module() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Point".to_string(),
+        make_struct(&[
+            ("x", Format::U32),
+            ("next", Format::Option(Box::new(type_name("Point")))),
+        ]),
+    );
+    registry.insert(
+        "Shape".to_string(),
+        make_struct(&[("points", Format::Seq(Box::new(type_name("Point"))))]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator =
+        rust::CodeGenerator::new(&config).with_wrapper_module(Some("shapes".to_string()));
+
+    let dir = tempdir().unwrap();
+    let generated_path = dir.path().join("generated.rs");
+    let mut generated = File::create(&generated_path).unwrap();
+    generator.output(&mut generated, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&generated_path).unwrap();
+    assert!(content.starts_with("pub mod shapes {\n    #![allow(unused_imports)]\n"));
+    assert!(content.ends_with("\n    }\n}\n"));
+    assert!(content.contains("\n    pub struct Point {\n        pub x: u32,\n"));
+    assert!(content.contains("\n        pub next: Option<Box<Point>>,\n    }\n"));
+    assert!(content.contains("\n    pub struct Shape {\n        pub points: Vec<Point>,\n"));
+
+    let source_path = dir.path().join("lib.rs");
+    let mut source = File::create(&source_path).unwrap();
+    writeln!(
+        source,
+        r#"include!("generated.rs");
+
+pub fn origin() -> shapes::Shape {{
+    shapes::Shape {{ points: vec![shapes::Point {{ x: 0, next: None }}] }}
+}}
+"#
+    )
+    .unwrap();
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_to_string() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let mut out = Vec::new();
+    rust::CodeGenerator::new(&config)
+        .output(&mut out, &registry)
+        .unwrap();
+    assert_eq!(
+        rust::to_string(&registry, &config).unwrap(),
+        String::from_utf8(out).unwrap()
+    );
+
+    let mut definitions = BTreeMap::new();
+    definitions.insert("my_types".to_string(), vec!["Struct".to_string()]);
+    let config = config.with_external_definitions(definitions);
+    let error = rust::to_string(&registry, &config).unwrap_err();
+    let expected = rust::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(error.to_string(), expected.to_string());
+}
+
+#[test]
+fn test_rust_code_with_flattened_fields() {
+    let extra = Format::Map {
+        key: Box::new(Format::Str),
+        value: Box::new(Format::U64),
+    };
+    let mut registry = Registry::new();
+    registry.insert(
+        "Config".to_string(),
+        make_struct(&[("name", Format::Str), ("extra", extra.clone())]),
+    );
+    let path = |field: &str| {
+        vec![
+            "testing".to_string(),
+            "Config".to_string(),
+            field.to_string(),
+        ]
+    };
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_flattened_fields(vec![path("extra")].into_iter().collect());
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub struct Config {
+    pub name: String,
+    #[serde(flatten)]
+    pub extra: Map<String, u64>,
+}"#
+    ));
+
+    // Serialization is required.
+    let config_without_serialization =
+        CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config_without_serialization)
+        .with_flattened_fields(vec![path("extra")].into_iter().collect());
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Flattened fields require serialization to be enabled"
+    );
+
+    // Only maps can be flattened.
+    let generator = rust::CodeGenerator::new(&config)
+        .with_flattened_fields(vec![path("name")].into_iter().collect());
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot flatten field `name` of `Config`: only maps can be flattened"
+    );
+
+    // At most one field per struct can be flattened.
+    registry.insert(
+        "Config".to_string(),
+        make_struct(&[("extra", extra.clone()), ("more", extra)]),
+    );
+    let generator = rust::CodeGenerator::new(&config)
+        .with_flattened_fields(vec![path("extra"), path("more")].into_iter().collect());
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot flatten more than one field of `Config`: extra, more"
+    );
+}
+
+#[test]
+fn test_rust_code_with_bcs_forces_btree_maps() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_serialization(false)
+        .with_encodings(vec![Encoding::Bcs]);
+    let generator =
+        rust::CodeGenerator::new(&config).with_map_representation(MapRepresentation::HashMap);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("use std::collections::BTreeMap as Map;\n"));
+    assert!(!content.contains("HashMap"));
+    assert!(content.contains("#[derive(Clone, Debug, PartialEq, PartialOrd)]\n"));
+
+    // Other encodings keep the requested representation.
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_serialization(false)
+        .with_encodings(vec![Encoding::Bincode]);
+    let generator =
+        rust::CodeGenerator::new(&config).with_map_representation(MapRepresentation::HashMap);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    assert!(String::from_utf8_lossy(&source).contains("use std::collections::HashMap as Map;\n"));
+}
+
+#[test]
+fn test_rust_quote_container_definitions_with_derives() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Point".to_string(),
+        make_struct(&[("x", Format::U32), ("y", Format::U32)]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+
+    let definitions = rust::CodeGenerator::new(&config)
+        .with_derive_macros(Vec::new())
+        .with_track_visibility(false)
+        .quote_container_definitions(&registry)
+        .unwrap();
+    assert_eq!(
+        definitions["Point"],
+        "struct Point {\n    x: u32,\n    y: u32,\n}\n"
+    );
+
+    // Snippets follow the configuration of the generator, as in the full output.
+    let definitions = rust::CodeGenerator::new(&config)
+        .with_derive_macros(vec!["Clone".to_string(), "Debug".to_string()])
+        .quote_container_definitions(&registry)
+        .unwrap();
+    assert_eq!(
+        definitions["Point"],
+        "#[derive(Clone, Debug)]\npub struct Point {\n    pub x: u32,\n    pub y: u32,\n}\n"
+    );
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_piecewise_assembly() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config);
+    let definitions = generator.quote_container_definitions(&registry).unwrap();
+    assert_eq!(generator.quote_preamble(), rust::preamble(&config));
+
+    let dependencies = analyzer::get_dependency_map(&registry).unwrap();
+    let mut source = generator.quote_preamble();
+    source.push_str("pub fn hand_written() -> u32 {\n    1\n}\n\n");
+    // Containers have a known size once they are defined.
+    let mut known_sizes = HashSet::new();
+    for name in analyzer::best_effort_topological_sort(&dependencies) {
+        let definition = generator
+            .quote_container(name, &registry[name], &known_sizes)
+            .unwrap();
+        assert_eq!(definition, definitions[name]);
+        source.push_str(&definition);
+        source.push('\n');
+        known_sizes.insert(name);
+    }
+
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    std::fs::write(&source_path, source).unwrap();
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_quoting_with_variables() {
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config);
+    let format = Format::Seq(Box::new(Format::unknown()));
+    assert!(generator.quote_type(&format, None).is_err());
+    assert!(rust::format_to_rust(&format, None).is_err());
+
+    let container = ContainerFormat::Enum(btreemap! {
+        0 => Named {
+            name: "A".to_string(),
+            value: VariantFormat::unknown(),
+        },
+    });
+    assert!(generator
+        .quote_container("Test", &container, &HashSet::new())
+        .is_err());
+    assert!(rust::container("Test", &container, &HashSet::new(), &config).is_err());
+}
+
+#[test]
+fn test_rust_code_with_bytes_as_seq_u8() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Blob".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "data".to_string(),
+                value: Format::Seq(Box::new(Format::U8)),
+            },
+            Named {
+                name: "chunks".to_string(),
+                value: Format::Seq(Box::new(Format::Seq(Box::new(Format::U8)))),
+            },
+            Named {
+                name: "other".to_string(),
+                value: Format::Seq(Box::new(Format::U16)),
+            },
+        ]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_bytes_as_seq_u8(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub struct Blob {
+    #[serde(with = "serde_bytes")]
+    pub data: Bytes,
+    pub chunks: Vec<Bytes>,
+    pub other: Vec<u16>,
+}"#
+    ));
+
+    // Without serialization, only the type changes.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_bytes_as_seq_u8(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("type Bytes = Vec<u8>;\n"));
+    assert!(content.contains("    pub data: Bytes,\n"));
+    assert!(!content.contains("serde_bytes"));
+}
+
+#[test]
+fn test_rust_code_with_display_for_unit_enums() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Color".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "DarkRed".to_string(), value: VariantFormat::Unit },
+            1 => Named { name: "Blue".to_string(), value: VariantFormat::Unit },
+        }),
+    );
+    registry.insert(
+        "Shape".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "Point".to_string(), value: VariantFormat::Unit },
+            1 => Named { name: "Circle".to_string(), value: VariantFormat::NewType(Box::new(Format::U32)) },
+        }),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_display_for_unit_enums(true)
+        .with_rename_all(Some("SCREAMING_SNAKE_CASE".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"impl core::fmt::Display for Color {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::DarkRed => f.write_str("DARK_RED"),
+            Self::Blue => f.write_str("BLUE"),
+        }
+    }
+}"#
+    ));
+    // Enums with variant data are not affected.
+    assert!(!content.contains("impl core::fmt::Display for Shape"));
+
+    // Without serialization, variant names are not renamed.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config)
+        .with_display_for_unit_enums(true)
+        .with_rename_all(Some("SCREAMING_SNAKE_CASE".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("Self::DarkRed => f.write_str(\"DarkRed\"),"));
+
+    // Variant names may start with a non-ASCII character.
+    registry.insert(
+        "Dessert".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "Éclair".to_string(), value: VariantFormat::Unit },
+            1 => Named { name: "IceCream".to_string(), value: VariantFormat::Unit },
+        }),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_display_for_unit_enums(true)
+        .with_rename_all(Some("camelCase".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("Self::Éclair => f.write_str(\"Éclair\"),"));
+    assert!(content.contains("Self::IceCream => f.write_str(\"iceCream\"),"));
+
+    // Explicit renames take precedence over `rename_all`.
+    let renames = btreemap! {
+        vec!["testing".to_string(), "Color".to_string(), "DarkRed".to_string()] => "crimson".to_string(),
+    };
+    let generator = rust::CodeGenerator::new(&config)
+        .with_display_for_unit_enums(true)
+        .with_rename_all(Some("SCREAMING_SNAKE_CASE".to_string()))
+        .with_variant_renames(renames);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains("#[serde(rename = \"crimson\")]\n    DarkRed,"));
+    assert!(content.contains("Self::DarkRed => f.write_str(\"crimson\"),"));
+    assert!(content.contains("Self::Blue => f.write_str(\"BLUE\"),"));
+}
+
+#[test]
+fn test_rust_code_with_container_attributes() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Point".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "x".to_string(),
+                value: Format::U32,
+            },
+            Named {
+                name: "y".to_string(),
+                value: Format::U32,
+            },
+        ]),
+    );
+    registry.insert("Unit".to_string(), ContainerFormat::UnitStruct);
+    let mut comments = BTreeMap::new();
+    comments.insert(
+        vec!["testing".to_string(), "Point".to_string()],
+        "A point.".to_string(),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_comments(comments);
+    let mut attributes = BTreeMap::new();
+    attributes.insert(
+        "Point".to_string(),
+        vec![
+            "#[repr(C)]".to_string(),
+            "#[cfg_attr(feature = \"foo\", derive(Bar))]".to_string(),
+        ],
+    );
+    let generator = rust::CodeGenerator::new(&config).with_container_attributes(attributes);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"/// A point.
+#[repr(C)]
+#[cfg_attr(feature = "foo", derive(Bar))]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]
+pub struct Point {"#
+    ));
+    assert!(content.contains(
+        r#"
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]
+pub struct Unit;"#
+    ));
+}
+
+#[test]
+fn test_rust_code_with_field_attributes() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Message".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Request".to_string(),
+                value: VariantFormat::Struct(vec![
+                    Named { name: "id".to_string(), value: Format::U64 },
+                    Named { name: "retries".to_string(), value: Format::U32 },
+                ]),
+            },
+        }),
+    );
+    let mut comments = BTreeMap::new();
+    comments.insert(
+        vec![
+            "testing".to_string(),
+            "Message".to_string(),
+            "Request".to_string(),
+            "retries".to_string(),
+        ],
+        "Number of retries.".to_string(),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_comments(comments);
+    let mut attributes = BTreeMap::new();
+    attributes.insert(
+        vec![
+            "testing".to_string(),
+            "Message".to_string(),
+            "Request".to_string(),
+            "retries".to_string(),
+        ],
+        vec![
+            "#[serde(default)]".to_string(),
+            "#[cfg(feature = \"x\")]".to_string(),
+        ],
+    );
+    let generator = rust::CodeGenerator::new(&config).with_field_attributes(attributes);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"    Request {
+        id: u64,
+
+        /// Number of retries.
+        #[serde(default)]
+        #[cfg(feature = "x")]
+        retries: u32,
+    },"#
+    ));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_empty_registry() {
+    use serde_generate::SourceInstaller;
+
+    let registry = Registry::new();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    rust::CodeGenerator::new(&config)
+        .output(&mut source, &registry)
+        .unwrap();
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // The installer also produces a valid crate.
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bcs]);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer.install_module(&config, &registry).unwrap();
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(dir.path().join("testing"))
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_sorted_members() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Color".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "Red".to_string(), value: VariantFormat::Unit },
+            1 => Named { name: "Blue".to_string(), value: VariantFormat::Unit },
+        }),
+    );
+    registry.insert(
+        "Point".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "y".to_string(),
+                value: Format::U32,
+            },
+            Named {
+                name: "x".to_string(),
+                value: Format::U32,
+            },
+        ]),
+    );
+
+    // Without serialization, reordering is safe with explicit discriminants.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config)
+        .with_sort_members(true)
+        .with_explicit_discriminants(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"pub enum Color {
+    Blue = 1,
+    Red = 0,
+}"#
+    ));
+    assert!(content.contains(
+        r#"pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}"#
+    ));
+
+    // Otherwise, the numeric values of variants would change.
+    let generator = rust::CodeGenerator::new(&config).with_sort_members(true);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Sorting the variants of `Color` requires explicit discriminants"
+    );
+
+    // With serialization, reordering would change the serialization format.
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_sort_members(true)
+        .with_explicit_discriminants(true);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Sorting the variants of `Color` would change its serialization format"
+    );
+    registry.remove("Color");
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Sorting the fields of `Point` would change its serialization format"
+    );
+
+    // Members that are already sorted are accepted.
+    registry.insert(
+        "Point".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "x".to_string(),
+                value: Format::U32,
+            },
+            Named {
+                name: "y".to_string(),
+                value: Format::U32,
+            },
+        ]),
+    );
+    generator.output(&mut Vec::new(), &registry).unwrap();
+}
+
+#[test]
+fn test_rust_code_with_total_float_eq() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Point".to_string(),
+        make_struct(&[
+            ("x", Format::F64),
+            ("label", Format::Option(Box::new(Format::Str))),
+        ]),
+    );
+    registry.insert(
+        "Shape".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named { name: "Empty".to_string(), value: VariantFormat::Unit },
+            1 => Named { name: "Polygon".to_string(), value: VariantFormat::NewType(Box::new(Format::Seq(Box::new(type_name("Point"))))) },
+            2 => Named { name: "Circle".to_string(), value: VariantFormat::Struct(vec![
+                Named { name: "center".to_string(), value: type_name("Point") },
+                Named { name: "radius".to_string(), value: Format::Option(Box::new(Format::F32)) },
+            ]) },
+        }),
+    );
+    registry.insert(
+        "Label".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_total_float_eq(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"#[derive(Clone, Debug)]
+pub struct Point {
+    pub x: f64,
+    pub label: Option<String>,
+}
+
+impl PartialEq for Point {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(core::cmp::Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Point {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        let Self { x: a0, label: a1 } = self;
+        let Self { x: b0, label: b1 } = other;
+        match { let (x, y) = (a0.to_bits() as i64, b0.to_bits() as i64); Some((x ^ (((x >> 63) as u64) >> 1) as i64).cmp(&(y ^ (((y >> 63) as u64) >> 1) as i64))) } {
+            Some(core::cmp::Ordering::Equal) => (),
+            ordering => return ordering,
+        }
+        a1.partial_cmp(b1)
+    }
+}"#
+    ));
+    assert!(content.contains("impl PartialOrd for Shape {"));
+    // Containers without floats still derive the comparison traits.
+    assert!(content.contains("#[derive(Clone, Debug, PartialEq, PartialOrd)]\npub struct Label("));
+
+    // Floats are compared with a total order.
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+    writeln!(
+        source,
+        r#"
+fn main() {{
+    let nan = Point {{ x: f64::NAN, label: None }};
+    assert!(nan == nan.clone());
+    let zero = Point {{ x: 0.0, label: None }};
+    let negative_zero = Point {{ x: -0.0, label: None }};
+    assert!(negative_zero < zero);
+    assert!(Shape::Empty < Shape::Polygon(vec![]));
+    assert!(Shape::Polygon(vec![zero.clone()]) < Shape::Polygon(vec![zero.clone(), zero.clone()]));
+    let circle = |radius| Shape::Circle {{ center: nan.clone(), radius }};
+    assert!(circle(None) < circle(Some(f32::NAN)));
+    assert!(circle(Some(f32::NAN)) == circle(Some(f32::NAN)));
+    assert!(circle(Some(-f32::NAN)) < circle(Some(f32::NEG_INFINITY)));
+    assert!(circle(Some(f32::INFINITY)) < circle(Some(f32::NAN)));
+    assert!(circle(Some(-1.0)) < circle(Some(-0.5)));
+}}"#
+    )
+    .unwrap();
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new(dir.path().join("test")).status().unwrap();
+    assert!(status.success());
+
+    // Nested floats are supported in all formats.
+    let registry = test_utils::get_registry().unwrap();
+    let source_path = dir.path().join("lib.rs");
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // The comparison traits must be derived.
+    let generator = rust::CodeGenerator::new(&config)
+        .with_total_float_eq(true)
+        .with_derive_macros(vec!["Clone".to_string(), "Debug".to_string()]);
+    let mut source = Vec::new();
+    assert!(generator.output(&mut source, &registry).is_err());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_serde_crate_path() {
+    use serde_generate::SourceInstaller;
+
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer.install_module(&config, &registry).unwrap();
+
+    let generator =
+        rust::CodeGenerator::new(&config).with_serde_crate_path(Some("my_serde".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+    assert!(!content.contains("use my_serde::"));
+    assert!(!content.contains("use serde::"));
+    assert!(content.contains(
+        r#"#[derive(my_serde::Serialize, my_serde::Deserialize, Clone, Debug, PartialEq, PartialOrd)]
+#[serde(crate = "my_serde")]
+pub struct UnitStruct;"#
+    ));
+
+    // Serde is available under another name.
+    let mut lib = File::create(dir.path().join("testing/src/lib.rs")).unwrap();
+    lib.write_all(&source).unwrap();
+    writeln!(lib, "extern crate serde as my_serde;").unwrap();
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(dir.path().join("testing"))
+        .arg("build")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_bytes_representation() {
+    use rust::BytesRepresentation;
+
+    let mut registry = Registry::new();
+    registry.insert("Blob".to_string(), make_struct(&[("data", Format::Bytes)]));
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let output = |representation: BytesRepresentation| {
+        let generator = rust::CodeGenerator::new(&config).with_bytes_representation(representation);
+        let mut source = Vec::new();
+        generator.output(&mut source, &registry).unwrap();
+        String::from_utf8(source).unwrap()
+    };
+
+    let content = output(BytesRepresentation::ByteBuf);
+    assert!(content.contains("use serde_bytes::ByteBuf as Bytes;\n"));
+    assert!(!content.contains("type Bytes"));
+    assert!(content.contains("pub data: Bytes,"));
+
+    let content = output(BytesRepresentation::VecU8);
+    assert!(!content.contains("serde_bytes"));
+    assert!(content.contains("type Bytes = Vec<u8>;\n"));
+    assert!(content.contains("pub data: Bytes,"));
+
+    let content = output(BytesRepresentation::Custom {
+        import: "bytes::Bytes".to_string(),
+    });
+    assert!(!content.contains("serde_bytes"));
+    assert!(!content.contains("type Bytes"));
+    assert!(content.contains("pub data: bytes::Bytes,"));
+}
+
+#[test]
+fn test_rust_code_with_keyword_field_names() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Token".to_string(),
+        make_struct(&[
+            ("type", Format::Str),
+            ("async", Format::Bool),
+            ("self", Format::U8),
+        ]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = String::from_utf8_lossy(&source);
+    assert!(content.contains(
+        r#"    #[serde(rename = "type")]
+    pub r#type: String,
+    #[serde(rename = "async")]
+    pub r#async: bool,
+    #[serde(rename = "self")]
+    pub self_: u8,
+"#
+    ));
+
+    // Without serialization, raw identifiers are still used.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let mut source = File::create(&source_path).unwrap();
+    rust::CodeGenerator::new(&config)
+        .output(&mut source, &registry)
+        .unwrap();
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("pub r#type: String,"));
+    assert!(!content.contains("rename"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_lint_allows() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let output = |generator: rust::CodeGenerator| {
+        let mut source = Vec::new();
+        generator.output(&mut source, &registry).unwrap();
+        String::from_utf8(source).unwrap()
+    };
+
+    let content = output(rust::CodeGenerator::new(&config));
+    assert!(content.starts_with("#![allow(unused_imports)]\n"));
+
+    let lints = vec!["unused_imports".to_string(), "dead_code".to_string()];
+    let content = output(rust::CodeGenerator::new(&config).with_lint_allows(lints.clone()));
+    assert!(content.starts_with("#![allow(unused_imports, dead_code)]\n"));
+
+    // The attribute is scoped inside the wrapper module.
+    let content = output(
+        rust::CodeGenerator::new(&config)
+            .with_lint_allows(lints)
+            .with_wrapper_module(Some("wrapper".to_string())),
+    );
+    assert!(content.starts_with("pub mod wrapper {\n    #![allow(unused_imports, dead_code)]\n"));
+
+    // Without lints, the generated file can be included as is.
+    let content = output(rust::CodeGenerator::new(&config).with_lint_allows(Vec::new()));
+    assert!(!content.contains("#!["));
+    let dir = tempdir().unwrap();
+    std::fs::write(dir.path().join("generated.rs"), content).unwrap();
+    let source_path = dir.path().join("lib.rs");
+    std::fs::write(&source_path, "include!(\"generated.rs\");\n").unwrap();
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_unordered_map_keys() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Histogram".to_string(),
+        make_struct(&[(
+            "buckets",
+            Format::Map {
+                key: Box::new(Format::F64),
+                value: Box::new(Format::U64),
+            },
+        )]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let error = rust::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Map keys of type F64 in container `Histogram` do not implement `Ord`, as required by `BTreeMap`"
+    );
+
+    // Containers used as keys must derive `Ord`.
+    let mut registry = Registry::new();
+    registry.insert(
+        "Key".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+    registry.insert(
+        "Index".to_string(),
+        make_struct(&[(
+            "entries",
+            Format::Map {
+                key: Box::new(type_name("Key")),
+                value: Box::new(Format::U64),
+            },
+        )]),
+    );
+    assert!(rust::CodeGenerator::new(&config)
+        .output(&mut Vec::new(), &registry)
+        .is_err());
+    let derive_macros = vec!["Clone", "Debug", "PartialEq", "Eq", "PartialOrd", "Ord"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    assert!(rust::CodeGenerator::new(&config)
+        .with_derive_macros(derive_macros)
+        .output(&mut Vec::new(), &registry)
+        .is_ok());
+
+    // Other map representations are not affected.
+    assert!(rust::CodeGenerator::new(&config)
+        .with_map_representation(MapRepresentation::Custom("MyMap".to_string()))
+        .output(&mut Vec::new(), &registry)
+        .is_ok());
+}
+
+#[test]
+fn test_rust_code_with_invalid_container_name() {
+    let mut registry = Registry::new();
+    registry.insert("1Thing".to_string(), ContainerFormat::UnitStruct);
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Container name `1Thing` is not a valid identifier in Rust"
+    );
+
+    let mut registry = Registry::new();
+    registry.insert("proto.type.Thing".to_string(), ContainerFormat::UnitStruct);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Container name `proto.type.Thing` is not a valid identifier in Rust"
+    );
+}
+
+fn get_zero_copy_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Message".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "name".to_string(),
+                value: Format::Str,
+            },
+            Named {
+                name: "payload".to_string(),
+                value: Format::Bytes,
+            },
+            Named {
+                name: "count".to_string(),
+                value: Format::U32,
+            },
+        ]),
+    );
+    registry.insert(
+        "Envelope".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Empty".to_string(),
+                value: VariantFormat::Unit,
+            },
+            1 => Named {
+                name: "Single".to_string(),
+                value: VariantFormat::NewType(Box::new(Format::TypeName("Message".to_string()))),
+            },
+            2 => Named {
+                name: "Labeled".to_string(),
+                value: VariantFormat::Tuple(vec![
+                    Format::U64,
+                    Format::Option(Box::new(Format::Str)),
+                ]),
+            },
+        }),
+    );
+    registry.insert(
+        "Name".to_string(),
+        ContainerFormat::NewTypeStruct(Box::new(Format::Str)),
+    );
+    registry.insert(
+        "Point".to_string(),
+        ContainerFormat::TupleStruct(vec![Format::I32, Format::I32]),
+    );
+    registry
+}
+
+#[test]
+fn test_rust_code_with_zero_copy() {
+    let registry = get_zero_copy_registry();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_zero_copy(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let source = String::from_utf8(source).unwrap();
+
+    assert!(source.contains("use std::borrow::Cow;\n"));
+    assert!(source.contains(
+        r#"pub struct Message<'a> {
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    #[serde(borrow)]
+    pub payload: Cow<'a, [u8]>,
+    pub count: u32,
+}
+"#
+    ));
+    assert!(source.contains(
+        r#"pub enum Envelope<'a> {
+    Empty,
+    Single(#[serde(borrow)] Message<'a>),
+    Labeled(u64, #[serde(borrow)] Option<Cow<'a, str>>),
+}
+"#
+    ));
+    assert!(source.contains("pub struct Name<'a>(#[serde(borrow)] pub Cow<'a, str>);\n"));
+    assert!(source.contains("pub struct Point(i32, i32);\n"));
+
+    // Without serialization, only the lifetimes remain.
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_zero_copy(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let source = String::from_utf8(source).unwrap();
+    assert!(source.contains("    Single(Message<'a>),\n"));
+    assert!(!source.contains("borrow)]"));
+}
+
+#[test]
+fn test_that_rust_code_runs_with_zero_copy() {
+    let mut registry = test_utils::get_registry().unwrap();
+    registry.extend(get_zero_copy_registry());
+    let dir = tempdir().unwrap();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let generator = rust::CodeGenerator::new(&config).with_zero_copy(true);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer
+        .install_module_with_generator(&generator, &registry)
+        .unwrap();
+
+    let crate_path = dir.path().join("testing");
+    let samples = test_utils::Runtime::Bincode
+        .get_positive_samples()
+        .iter()
+        .map(|bytes| format!("vec!{:?}", bytes))
+        .collect::<Vec<_>>();
+    let mut main = File::create(crate_path.join("src/main.rs")).unwrap();
+    writeln!(
+        main,
+        r#"
+use std::borrow::Cow;
+use testing::{{runtime::bincode, Message, SerdeData}};
+
+fn main() {{
+    for sample in vec![{}] {{
+        let value = bincode::deserialize::<SerdeData>(&sample).unwrap();
+        assert_eq!(bincode::serialize(&value).unwrap(), sample);
+    }}
+
+    let message = Message {{
+        name: Cow::Owned("hello".to_string()),
+        payload: Cow::Owned(vec![1, 2, 3]),
+        count: 4,
+    }};
+    let bytes = bincode::serialize(&message).unwrap();
+    let value = bincode::deserialize::<Message>(&bytes).unwrap();
+    assert_eq!(value, message);
+    assert!(matches!(value.name, Cow::Borrowed("hello")));
+    assert!(matches!(value.payload, Cow::Borrowed(&[1, 2, 3])));
+}}
+"#,
+        samples.join(", ")
+    )
+    .unwrap();
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("run")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_zero_copy_and_proptest_strategies() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_zero_copy(true)
+        .with_proptest_strategies(true);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Proptest strategies are not available with zero-copy types"
+    );
+}
+
+fn get_shape_registry() -> Registry {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Shape".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Circle".to_string(),
+                value: VariantFormat::Struct(vec![Named {
+                    name: "radius".to_string(),
+                    value: Format::U32,
+                }]),
+            },
+            1 => Named {
+                name: "Empty".to_string(),
+                value: VariantFormat::Unit,
+            },
+        }),
+    );
+    registry
+}
+
+#[test]
+fn test_rust_code_with_enum_representations() {
+    let registry = get_shape_registry();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    for (representation, expected) in [
+        (EnumRepresentation::External, ""),
+        (
+            EnumRepresentation::Adjacent {
+                tag: "type".to_string(),
+                content: "value".to_string(),
+            },
+            "#[serde(tag = \"type\", content = \"value\")]\n",
+        ),
+        (
+            EnumRepresentation::Internal {
+                tag: "kind".to_string(),
+            },
+            "#[serde(tag = \"kind\")]\n",
+        ),
+        (EnumRepresentation::Untagged, "#[serde(untagged)]\n"),
+    ] {
+        let generator = rust::CodeGenerator::new(&config).with_enum_representation(representation);
+        let mut source = Vec::new();
+        generator.output(&mut source, &registry).unwrap();
+        let source = String::from_utf8(source).unwrap();
+        assert!(source.contains(&format!(
+            "#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd)]\n{}pub enum Shape {{",
+            expected
+        )));
+    }
+}
+
+#[test]
+fn test_rust_code_with_invalid_internally_tagged_enums() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator =
+        rust::CodeGenerator::new(&config).with_enum_representation(EnumRepresentation::Internal {
+            tag: "type".to_string(),
+        });
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Enum `Choice` cannot be internally tagged because variant `B` is not a struct or unit variant"
+    );
+}
+
+#[test]
+fn test_rust_code_with_ambiguous_untagged_enums() {
+    let mut registry = get_shape_registry();
+    // A struct variant with the same fields as `Circle`.
+    registry.insert(
+        "Figure".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Disk".to_string(),
+                value: VariantFormat::Struct(vec![Named {
+                    name: "radius".to_string(),
+                    value: Format::U32,
+                }]),
+            },
+            1 => Named {
+                name: "Ring".to_string(),
+                value: VariantFormat::Struct(vec![Named {
+                    name: "radius".to_string(),
+                    value: Format::U32,
+                }]),
+            },
+        }),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator =
+        rust::CodeGenerator::new(&config).with_enum_representation(EnumRepresentation::Untagged);
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Enum `Figure` cannot be untagged because variant `Ring` may be deserialized as variant `Disk`"
+    );
+
+    // Unit variants and newtype variants of unit are both serialized as `null`.
+    let mut registry = Registry::new();
+    registry.insert(
+        "Nothing".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "A".to_string(),
+                value: VariantFormat::Unit,
+            },
+            1 => Named {
+                name: "B".to_string(),
+                value: VariantFormat::NewType(Box::new(Format::Unit)),
+            },
+        }),
+    );
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Enum `Nothing` cannot be untagged because variant `B` may be deserialized as variant `A`"
+    );
+
+    let field = |name: &str, value: Format| Named {
+        name: name.to_string(),
+        value,
+    };
+    let enum_registry = |first: VariantFormat, second: VariantFormat| {
+        let mut registry = Registry::new();
+        registry.insert(
+            "Choice".to_string(),
+            ContainerFormat::Enum(btreemap! {
+                0 => Named { name: "A".to_string(), value: first },
+                1 => Named { name: "B".to_string(), value: second },
+            }),
+        );
+        registry
+    };
+    let shadowed_message =
+        "Enum `Choice` cannot be untagged because variant `B` may be deserialized as variant `A`";
+    for (first, second) in [
+        // Unknown fields are ignored.
+        (
+            VariantFormat::Struct(vec![field("x", Format::U32)]),
+            VariantFormat::Struct(vec![field("x", Format::U32), field("y", Format::U32)]),
+        ),
+        // Missing optional fields are `None`.
+        (
+            VariantFormat::Struct(vec![
+                field("x", Format::U32),
+                field("y", Format::Option(Box::new(Format::Str))),
+            ]),
+            VariantFormat::Struct(vec![field("x", Format::U64)]),
+        ),
+        (
+            VariantFormat::NewType(Box::new(Format::U32)),
+            VariantFormat::NewType(Box::new(Format::U64)),
+        ),
+        (
+            VariantFormat::NewType(Box::new(Format::Str)),
+            VariantFormat::NewType(Box::new(Format::Char)),
+        ),
+        (
+            VariantFormat::NewType(Box::new(Format::Option(Box::new(Format::U8)))),
+            VariantFormat::NewType(Box::new(Format::U8)),
+        ),
+        (
+            VariantFormat::NewType(Box::new(Format::Seq(Box::new(Format::U8)))),
+            VariantFormat::Tuple(vec![Format::U8, Format::U16]),
+        ),
+    ] {
+        let error = generator
+            .output(&mut Vec::new(), &enum_registry(first, second))
+            .unwrap_err();
+        assert_eq!(error.to_string(), shadowed_message);
+    }
+
+    // Variants with more fields, or more specific values, may come first.
+    for (first, second) in [
+        (
+            VariantFormat::Struct(vec![field("x", Format::U32), field("y", Format::U32)]),
+            VariantFormat::Struct(vec![field("x", Format::U32)]),
+        ),
+        (
+            VariantFormat::NewType(Box::new(Format::U64)),
+            VariantFormat::NewType(Box::new(Format::F64)),
+        ),
+        (
+            VariantFormat::NewType(Box::new(Format::Char)),
+            VariantFormat::NewType(Box::new(Format::Bool)),
+        ),
+    ] {
+        assert!(generator
+            .output(&mut Vec::new(), &enum_registry(first, second))
+            .is_ok());
+    }
+
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let generator =
+        rust::CodeGenerator::new(&config).with_enum_representation(EnumRepresentation::Untagged);
+    let error = generator
+        .output(&mut Vec::new(), &get_shape_registry())
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Untagged enums are not supported by the Bincode and BCS encodings"
+    );
+}
+
+#[test]
+fn test_rust_code_with_tagged_enums_and_bincode() {
+    let registry = get_shape_registry();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let generator =
+        rust::CodeGenerator::new(&config).with_enum_representation(EnumRepresentation::Adjacent {
+            tag: "type".to_string(),
+            content: "value".to_string(),
+        });
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Tagged enum representations are not supported by the Bincode and BCS encodings"
+    );
+}
+
+#[test]
+fn test_that_rust_code_runs_with_enum_representations() {
+    let registry = get_shape_registry();
+    for (representation, expected) in [
+        (
+            EnumRepresentation::Adjacent {
+                tag: "type".to_string(),
+                content: "value".to_string(),
+            },
+            r#"{"type":"Circle","value":{"radius":1}}"#,
+        ),
+        (
+            EnumRepresentation::Internal {
+                tag: "type".to_string(),
+            },
+            r#"{"type":"Circle","radius":1}"#,
+        ),
+        (EnumRepresentation::Untagged, r#"{"radius":1}"#),
+    ] {
+        let dir = tempdir().unwrap();
+        let config = CodeGeneratorConfig::new("testing".to_string());
+        // JSON value conversions bring in the `serde_json` dependency.
+        let generator = rust::CodeGenerator::new(&config)
+            .with_enum_representation(representation)
+            .with_json_value_conversions(true);
+        let installer = rust::Installer::new(dir.path().to_path_buf());
+        installer
+            .install_module_with_generator(&generator, &registry)
+            .unwrap();
+
+        let crate_path = dir.path().join("testing");
+        let mut main = File::create(crate_path.join("src/main.rs")).unwrap();
+        writeln!(
+            main,
+            r##"
+use testing::Shape;
+
+fn main() {{
+    let value = Shape::Circle {{ radius: 1 }};
+    let json = serde_json::to_string(&value).unwrap();
+    assert_eq!(json, r#"{}"#);
+    assert_eq!(serde_json::from_str::<Shape>(&json).unwrap(), value);
+}}
+"##,
+            expected
+        )
+        .unwrap();
+
+        // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+        let target_dir = std::env::current_dir().unwrap().join("../target");
+        let status = Command::new("cargo")
+            .current_dir(&crate_path)
+            .arg("run")
+            .arg("--target-dir")
+            .arg(target_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_variant_tags() {
+    let mut registry = test_utils::get_registry().unwrap();
+    registry.insert(
+        "TrafficLight".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Red".to_string(),
+                value: VariantFormat::Unit,
+            },
+            1 => Named {
+                name: "Yellow".to_string(),
+                value: VariantFormat::NewType(Box::new(Format::U8)),
+            },
+            2 => Named {
+                name: "FlashingGreen".to_string(),
+                value: VariantFormat::Unit,
+            },
+        }),
+    );
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_emit_variant_tags(true);
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+    writeln!(
+        source,
+        r#"
+pub fn tag_of(value: &TrafficLight) -> u32 {{
+    match value {{
+        TrafficLight::Red => traffic_light_tags::RED,
+        TrafficLight::Yellow(_) => traffic_light_tags::YELLOW,
+        TrafficLight::FlashingGreen => traffic_light_tags::FLASHING_GREEN,
+    }}
+}}
+"#
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains(
+        r#"pub mod traffic_light_tags {
+    pub const RED: u32 = 0;
+    pub const YELLOW: u32 = 1;
+    pub const FLASHING_GREEN: u32 = 2;
+}
+"#
+    ));
+    assert!(content.contains("pub mod c_style_enum_tags {\n    pub const A: u32 = 0;\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_custom_formatting() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let formatting = rust::Formatting {
+        indent: "\t".to_string(),
+        newline: "\r\n".to_string(),
+    };
+    let generator = rust::CodeGenerator::new(&config)
+        .with_formatting(formatting)
+        .with_wrapper_module(Some("types".to_string()));
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let source = String::from_utf8(source).unwrap();
+
+    assert!(
+        source.contains("\tpub struct Struct {\r\n\t\tpub x: u32,\r\n\t\tpub y: u64,\r\n\t}\r\n")
+    );
+    assert!(!source.contains("    "));
+    assert!(!source.replace("\r\n", "").contains('\n'));
+
+    // The default formatting is unchanged.
+    let generator = rust::CodeGenerator::new(&config).with_formatting(Default::default());
+    let mut default_source = Vec::new();
+    generator.output(&mut default_source, &registry).unwrap();
+    let mut expected_source = Vec::new();
+    rust::CodeGenerator::new(&config)
+        .output(&mut expected_source, &registry)
+        .unwrap();
+    assert_eq!(default_source, expected_source);
+}
+
+#[test]
+fn test_rust_code_with_rustfmt() {
+    if Command::new("rustfmt").arg("--version").output().is_err() {
+        // `rustfmt` is not available.
+        return;
+    }
+    let mut registry = Registry::new();
+    registry.insert(
+        "Point".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "x".to_string(),
+                value: Format::I64,
+            },
+            Named {
+                name: "y".to_string(),
+                value: Format::I64,
+            },
+        ]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let derive_macros = vec![
+        "Clone",
+        "Copy",
+        "Debug",
+        "Default",
+        "PartialEq",
+        "Eq",
+        "PartialOrd",
+        "Ord",
+        "Hash",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect::<Vec<_>>();
+    let generator = rust::CodeGenerator::new(&config).with_derive_macros(derive_macros);
+
+    let mut raw = Vec::new();
+    generator.output(&mut raw, &registry).unwrap();
+    let raw = String::from_utf8(raw).unwrap();
+    let mut formatted = Vec::new();
+    generator
+        .with_run_rustfmt(true)
+        .output(&mut formatted, &registry)
+        .unwrap();
+    let formatted = String::from_utf8(formatted).unwrap();
+
+    // The derive line is too long for `rustfmt`.
+    let derive = "#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]\n";
+    assert!(raw.contains(derive));
+    assert!(!formatted.contains(derive));
+    assert!(formatted.contains(
+        r#"#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+pub struct Point {
+"#
+    ));
+    // Imports are sorted.
+    assert!(raw.contains("use serde::{Serialize, Deserialize};\n"));
+    assert!(formatted.contains("use serde::{Deserialize, Serialize};\n"));
+    let definition = "pub struct Point {\n    pub x: i64,\n    pub y: i64,\n}\n";
+    assert!(raw.contains(definition));
+    assert!(formatted.contains(definition));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_vec_deques() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator =
+        rust::CodeGenerator::new(&config).with_seq_representation(SeqRepresentation::VecDeque);
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("use std::collections::VecDeque;\n"));
+    assert!(content.contains("    pub f_seq: VecDeque<Struct>,\n"));
+    assert!(content.contains("    pub f_nested_seq: VecDeque<VecDeque<Struct>>,\n"));
+    // Only the `Bytes` alias still uses `Vec`.
+    assert_eq!(content.matches("Vec<").count(), 1);
+    assert!(content.contains("type Bytes = Vec<u8>;\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_custom_seqs() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_seq_representation(
+        SeqRepresentation::Custom("std::collections::LinkedList".to_string()),
+    );
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let source = String::from_utf8(source).unwrap();
+    assert!(source.contains("    pub a: std::collections::LinkedList<u32>,\n"));
+    assert!(!source.contains("VecDeque"));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_custom_seqs() {
+    // Includes recursive definitions through sequences, e.g. `Tree`.
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_seq_representation(
+        SeqRepresentation::Custom("std::collections::LinkedList".to_string()),
+    );
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("    pub f_seq: std::collections::LinkedList<Struct>,\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_accessors() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "Person".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "name".to_string(),
+                value: Format::Str,
+            },
+            Named {
+                name: "friends".to_string(),
+                value: Format::Seq(Box::new(Format::TypeName("Person".to_string()))),
+            },
+        ]),
+    );
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_visibility(Visibility::Crate)
+        .with_generate_accessors(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let source = String::from_utf8(source).unwrap();
+    assert!(source.contains(
+        r#"impl Person {
+    pub fn name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn friends(&self) -> &Vec<Person> {
+        &self.friends
+    }
+}
+"#
+    ));
+
+    // Public fields do not need accessors.
+    let generator = rust::CodeGenerator::new(&config).with_generate_accessors(true);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let source = String::from_utf8(source).unwrap();
+    assert!(!source.contains("impl Person"));
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_accessors() {
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let generator = rust::CodeGenerator::new(&config)
+        .with_visibility(Visibility::Crate)
+        .with_generate_accessors(true);
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+    writeln!(
+        source,
+        "pub(crate) fn get_y(value: &Struct) -> u64 {{\n    *value.y()\n}}"
+    )
+    .unwrap();
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_external_aliases() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "User".to_string(),
+        ContainerFormat::Struct(vec![
+            Named {
+                name: "id".to_string(),
+                value: Format::TypeName("Uuid".to_string()),
+            },
+            Named {
+                name: "friends".to_string(),
+                value: Format::Seq(Box::new(Format::TypeName("Uuid".to_string()))),
+            },
+        ]),
+    );
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let config = CodeGeneratorConfig::new("testing".to_string())
+        .with_serialization(false)
+        .with_external_definitions(btreemap! {
+            "uuid".to_string() => vec!["Uuid".to_string()],
+        });
+    let generator = rust::CodeGenerator::new(&config).with_external_aliases(btreemap! {
+        "Uuid".to_string() => "UserId".to_string(),
+    });
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+    // Stand-in for the `uuid` crate.
+    writeln!(
+        source,
+        "mod uuid {{\n    #[derive(Clone, Debug, PartialEq, PartialOrd)]\n    pub struct Uuid(pub u128);\n}}\n"
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("use uuid::{Uuid as UserId};\n"));
+    assert!(content.contains("    pub id: UserId,\n    pub friends: Vec<UserId>,\n"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_invalid_external_aliases() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config).with_external_aliases(btreemap! {
+        "Uuid".to_string() => "UserId".to_string(),
+    });
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot rename `Uuid`, which is not imported from an external module"
+    );
+}
+
+#[test]
+fn test_that_rust_code_compiles_with_error_impls() {
+    let mut registry = Registry::new();
+    registry.insert(
+        "IoError".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "NotFound".to_string(),
+                value: VariantFormat::Unit,
+            },
+            1 => Named {
+                name: "PermissionDenied".to_string(),
+                value: VariantFormat::Struct(vec![Named {
+                    name: "path".to_string(),
+                    value: Format::Str,
+                }]),
+            },
+        }),
+    );
+    registry.insert(
+        "AppError".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Io".to_string(),
+                value: VariantFormat::NewType(Box::new(Format::TypeName("IoError".to_string()))),
+            },
+            1 => Named {
+                name: "Parse".to_string(),
+                value: VariantFormat::Struct(vec![
+                    Named {
+                        name: "line".to_string(),
+                        value: Format::U32,
+                    },
+                    Named {
+                        name: "cause".to_string(),
+                        value: Format::TypeName("IoError".to_string()),
+                    },
+                ]),
+            },
+            2 => Named {
+                name: "Unknown".to_string(),
+                value: VariantFormat::Tuple(vec![Format::U32, Format::Str]),
+            },
+        }),
+    );
+    registry.insert(
+        "Status".to_string(),
+        ContainerFormat::Enum(btreemap! {
+            0 => Named {
+                name: "Ok".to_string(),
+                value: VariantFormat::Unit,
+            },
+        }),
+    );
+    let dir = tempdir().unwrap();
+    let source_path = dir.path().join("test.rs");
+    let config = CodeGeneratorConfig::new("testing".to_string()).with_serialization(false);
+    let generator = rust::CodeGenerator::new(&config).with_error_impls_for(
+        vec!["AppError".to_string(), "IoError".to_string()]
+            .into_iter()
+            .collect(),
+    );
+    let mut source = File::create(&source_path).unwrap();
+    generator.output(&mut source, &registry).unwrap();
+    writeln!(
+        source,
+        r#"
+pub fn check() {{
+    use std::error::Error;
+    let error = AppError::Parse {{ line: 3, cause: IoError::NotFound }};
+    assert_eq!(error.to_string(), "Parse");
+    assert_eq!(error.source().unwrap().to_string(), "NotFound");
+    assert!(AppError::Unknown(1, String::new()).source().is_none());
+}}
+"#
+    )
+    .unwrap();
+
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains(
+        r#"impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(source) => Some(source),
+            Self::Parse { cause: source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+"#
+    ));
+    assert!(content.contains("impl std::error::Error for IoError {}\n"));
+    assert!(content.contains(
+        "            Self::PermissionDenied { .. } => f.write_str(\"PermissionDenied\"),\n"
+    ));
+    assert!(!content.contains("for Status"));
+
+    let status = Command::new("rustc")
+        .current_dir(dir.path())
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--edition")
+        .arg("2018")
+        .arg(&source_path)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_code_with_invalid_error_impls() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = rust::CodeGenerator::new(&config)
+        .with_error_impls_for(vec!["Test".to_string()].into_iter().collect());
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot implement `Error` for `Test`, which is not an enum of the registry"
+    );
+    // `core::error::Error` is not available in the supported versions of Rust.
+    let generator = rust::CodeGenerator::new(&config)
+        .with_no_std(true)
+        .with_error_impls_for(vec!["Choice".to_string()].into_iter().collect());
+    let error = generator.output(&mut Vec::new(), &registry).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Error implementations are not available in `no_std` mode"
+    );
+}
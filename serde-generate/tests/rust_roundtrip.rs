@@ -0,0 +1,160 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Round-trip tests of the generated Rust code for a corpus of registries. For each registry,
+//! we install a crate with the Bincode runtime and run `cargo test` on a generated test that
+//! deserializes and re-serializes sample values. This takes a while, hence these tests are only
+//! enabled with the `rust-roundtrip-tests` feature:
+//! `cargo test -p serde-generate --features rust-roundtrip-tests -- rust_roundtrip`
+
+use crate::test_utils;
+use crate::test_utils::Runtime;
+use serde::{Deserialize, Serialize};
+use serde_generate::{rust, CodeGeneratorConfig, Encoding};
+use serde_reflection::{Registry, Tracer, TracerConfig};
+use std::{fs::File, io::Write, process::Command};
+use tempfile::tempdir;
+
+#[derive(Serialize, Deserialize)]
+enum Expr {
+    Num(i64),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Tree {
+    label: String,
+    children: Vec<Tree>,
+}
+
+// Three-node cycle `A -> B -> C -> A`.
+#[derive(Serialize, Deserialize)]
+struct A {
+    b: Option<Box<B>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct B {
+    cs: Vec<C>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum C {
+    Leaf,
+    Node(A),
+}
+
+// Full test using cargo. This may take a while.
+fn test_rust_round_trip(registry: &Registry, root: &str, samples: Vec<Vec<u8>>) {
+    let dir = tempdir().unwrap();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    let generator = rust::CodeGenerator::new(&config);
+    installer
+        .install_module_with_generator(&generator, registry)
+        .unwrap();
+
+    let crate_path = dir.path().join("testing");
+    std::fs::create_dir(crate_path.join("tests")).unwrap();
+    let mut source = File::create(crate_path.join("tests/round_trip.rs")).unwrap();
+    let samples = samples
+        .iter()
+        .map(|bytes| format!("vec!{:?}", bytes))
+        .collect::<Vec<_>>();
+    writeln!(
+        source,
+        r#"
+use testing::runtime::bincode;
+
+#[test]
+fn test_round_trip() {{
+    for sample in vec![{}] {{
+        let value = bincode::deserialize::<testing::{}>(&sample).unwrap();
+        assert_eq!(bincode::serialize(&value).unwrap(), sample);
+    }}
+}}
+"#,
+        samples.join(", "),
+        root,
+    )
+    .unwrap();
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("test")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+#[test]
+fn test_rust_round_trip_with_serde_data() {
+    let registry = test_utils::get_registry().unwrap();
+    let samples = Runtime::Bincode.get_positive_samples();
+    test_rust_round_trip(&registry, "SerdeData", samples);
+}
+
+#[test]
+fn test_rust_round_trip_with_simple_registry() {
+    let registry = test_utils::get_simple_registry().unwrap();
+    let value = test_utils::Test {
+        a: vec![4, 6],
+        b: (-3, 5),
+        c: test_utils::Choice::C { x: 7 },
+    };
+    let samples = vec![bincode::serialize(&value).unwrap()];
+    test_rust_round_trip(&registry, "Test", samples);
+}
+
+#[test]
+fn test_rust_round_trip_with_recursive_types() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<Expr>().unwrap();
+    tracer.trace_simple_type::<Tree>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let expr = Expr::Add(
+        Box::new(Expr::Num(1)),
+        Box::new(Expr::Neg(Box::new(Expr::Num(2)))),
+    );
+    test_rust_round_trip(&registry, "Expr", vec![bincode::serialize(&expr).unwrap()]);
+
+    let tree = Tree {
+        label: "root".to_string(),
+        children: vec![
+            Tree {
+                label: "leaf".to_string(),
+                children: Vec::new(),
+            },
+            Tree {
+                label: "node".to_string(),
+                children: vec![Tree {
+                    label: "leaf".to_string(),
+                    children: Vec::new(),
+                }],
+            },
+        ],
+    };
+    test_rust_round_trip(&registry, "Tree", vec![bincode::serialize(&tree).unwrap()]);
+}
+
+#[test]
+fn test_rust_round_trip_with_mutually_recursive_types() {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    tracer.trace_simple_type::<A>().unwrap();
+    tracer.trace_simple_type::<C>().unwrap();
+    let registry = tracer.registry().unwrap();
+
+    let value = A {
+        b: Some(Box::new(B {
+            cs: vec![C::Leaf, C::Node(A { b: None })],
+        })),
+    };
+    test_rust_round_trip(&registry, "A", vec![bincode::serialize(&value).unwrap()]);
+}
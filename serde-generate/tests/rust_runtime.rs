@@ -3,7 +3,7 @@
 
 use crate::test_utils;
 use crate::test_utils::Runtime;
-use serde_generate::{rust, CodeGeneratorConfig};
+use serde_generate::{rust, CodeGeneratorConfig, Encoding, SourceInstaller};
 use std::{fs::File, io::Write, process::Command};
 use tempfile::tempdir;
 
@@ -82,3 +82,48 @@ fn main() {{
         .unwrap();
     assert!(status.success());
 }
+
+#[test]
+fn test_rust_installed_crate_with_bincode_runtime() {
+    let registry = test_utils::get_registry().unwrap();
+    let dir = tempdir().unwrap();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bincode]);
+    let installer = rust::Installer::new(dir.path().to_path_buf());
+    installer.install_module(&config, &registry).unwrap();
+
+    let crate_path = dir.path().join("testing");
+    let cargo = std::fs::read_to_string(crate_path.join("Cargo.toml")).unwrap();
+    assert!(cargo.contains("bincode = \"1.3\"\n"));
+
+    let mut source = File::create(crate_path.join("src/main.rs")).unwrap();
+    writeln!(
+        source,
+        r#"
+use testing::{{runtime, NewTypeStruct}};
+
+fn main() {{
+    let value = NewTypeStruct(7);
+    let bytes = runtime::bincode::serialize(&value).unwrap();
+    assert_eq!(bytes, vec![7, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(runtime::bincode::deserialize::<NewTypeStruct>(&bytes).unwrap(), value);
+}}
+"#
+    )
+    .unwrap();
+
+    // Re-installing the crate keeps user files.
+    installer.install_module(&config, &registry).unwrap();
+    assert!(crate_path.join("src/main.rs").exists());
+
+    // Use a stable `target` dir to avoid downloading and recompiling crates everytime.
+    let target_dir = std::env::current_dir().unwrap().join("../target");
+    let status = Command::new("cargo")
+        .current_dir(&crate_path)
+        .arg("run")
+        .arg("--target-dir")
+        .arg(target_dir)
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
@@ -97,6 +97,16 @@ fn test_that_swift_code_compiles() {
     test_that_swift_code_compiles_with_config(&config);
 }
 
+#[test]
+fn test_that_swift_code_compiles_with_empty_registry() {
+    let config =
+        CodeGeneratorConfig::new("Testing".to_string()).with_encodings(vec![Encoding::Bcs]);
+    let (_dir, source_path) =
+        test_that_swift_code_compiles_with_config_and_registry(&config, &Registry::new());
+    let content = std::fs::read_to_string(&source_path).unwrap();
+    assert!(content.contains("import Serde\n"));
+}
+
 #[test]
 fn test_that_swift_code_compiles_without_serialization() {
     let config = CodeGeneratorConfig::new("Testing".to_string()).with_serialization(false);
@@ -188,3 +198,19 @@ fn test_that_swift_code_compiles_with_custom_code() {
     let content = std::fs::read_to_string(&source_path).unwrap();
     assert!(content.contains("// custom1"));
 }
+
+#[test]
+fn test_swift_code_with_indirect_enums() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = swift::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+
+    // Only recursive enums are marked as `indirect`.
+    assert!(content.contains("\npublic enum CStyleEnum: Hashable {\n"));
+    assert!(content.contains("\nindirect public enum List: Hashable {\n"));
+    assert!(content.contains("\nindirect public enum SerdeData: Hashable {\n"));
+    assert_eq!(content.matches("indirect public enum").count(), 2);
+}
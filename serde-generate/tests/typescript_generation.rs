@@ -118,3 +118,37 @@ fn test_typescript_code_compiles_with_external_definitions() {
 
     test_typescript_code_compiles_with_config(dir.path(), &config);
 }
+
+#[test]
+fn test_typescript_code_type_mapping() {
+    let registry = test_utils::get_registry().unwrap();
+    let config = CodeGeneratorConfig::new("testing".to_string());
+    let generator = typescript::CodeGenerator::new(&config);
+    let mut source = Vec::new();
+    generator.output(&mut source, &registry).unwrap();
+    let content = String::from_utf8_lossy(&source);
+
+    assert!(content.contains("public f_option: Optional<Struct>"));
+    assert!(content.contains("public f_seq: Seq<Struct>"));
+    assert!(content.contains("public f_stringmap: Map<str,uint32>"));
+    assert!(content.contains("public f_tuple: Tuple<[uint8, uint16]>"));
+    // Enums are abstract classes with one subclass per variant.
+    assert!(content.contains("export abstract class List {"));
+    assert!(content.contains("export class ListVariantNode extends List {"));
+    assert!(content.contains("case 1: return ListVariantNode.load(deserializer);"));
+}
+
+#[test]
+fn test_typescript_code_with_empty_registry() {
+    let registry = serde_reflection::Registry::new();
+    let config =
+        CodeGeneratorConfig::new("testing".to_string()).with_encodings(vec![Encoding::Bcs]);
+    let mut source = Vec::new();
+    typescript::CodeGenerator::new(&config)
+        .output(&mut source, &registry)
+        .unwrap();
+
+    let content = String::from_utf8(source).unwrap();
+    assert!(content.contains("import { BcsSerializer, BcsDeserializer } from '../bcs/mod.ts';"));
+    assert!(content.contains("export class Helpers {\n}"));
+}